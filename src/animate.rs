@@ -0,0 +1,651 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbImage, RgbaImage};
+use rand::Rng;
+
+use crate::metric::Metric;
+use crate::noise::Simplex;
+use crate::plane::ImagePlane;
+use crate::{
+    Args, Format, Pipeline, ScoreFn, cells, exit_on_err, export, for_image, generate_voronoi,
+    importance, load_image, max_dists, output, reject_options, sample_points,
+    sample_points_print_progress, score_fn_for, seeded_rng, treemap,
+};
+
+type Point = (u32, u32, [u8; 3]);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct AnimateArgs {
+    #[command(flatten)]
+    pub args: Args,
+
+    /// Morph into the diagram of this image over the animation
+    #[arg(long)]
+    pub morph_to: Option<PathBuf>,
+
+    /// Loop the still with sites drifting on smooth noise: "amplitude,frames",
+    /// amplitude in pixels
+    #[arg(long, conflicts_with_all = ["morph_to", "frames"])]
+    pub wobble: Option<Wobble>,
+
+    /// Zoom into a focal point, topping up sites so detail stays constant:
+    /// "factor[,x,y]", with the focal point as fractions of the image size
+    #[arg(long, conflicts_with_all = ["morph_to", "wobble"])]
+    pub zoom: Option<Zoom>,
+
+    /// Shatter the diagram: cells fly apart, spin and fall towards the viewer,
+    /// leaving the frame transparent behind them
+    #[arg(long, conflicts_with_all = ["morph_to", "wobble", "zoom"])]
+    pub shatter: bool,
+
+    /// Build the diagram up from its single most important site to all of
+    /// them, adding sites in order of how much each adds to the picture
+    #[arg(long, conflicts_with_all = ["morph_to", "wobble", "zoom", "shatter"])]
+    pub reveal: bool,
+
+    /// Number of frames to render for --morph-to, --zoom, --shatter and
+    /// --reveal
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(2..))]
+    pub frames: u32,
+
+    /// Delay between frames in milliseconds
+    #[arg(long, default_value_t = 50)]
+    pub frame_delay: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Wobble {
+    amplitude: f64,
+    frames: u32,
+}
+
+impl FromStr for Wobble {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amplitude, frames) = s
+            .split_once(',')
+            .ok_or_else(|| format!("expected \"amplitude,frames\", got \"{s}\""))?;
+        let amplitude = amplitude
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid amplitude \"{amplitude}\": {err}"))?;
+        let frames = frames
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid frame count \"{frames}\": {err}"))?;
+        if frames < 2 {
+            return Err("a wobble needs at least 2 frames".to_string());
+        }
+        Ok(Self { amplitude, frames })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Zoom {
+    factor: f64,
+    focus: (f64, f64),
+}
+
+impl FromStr for Zoom {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|err| format!("invalid number \"{value}\": {err}"))
+        };
+        let mut parts = s.split(',');
+        let factor = parse(parts.next().unwrap_or_default())?;
+        if factor < 1.0 {
+            return Err(format!("zoom factor must be at least 1, got {factor}"));
+        }
+        let focus = match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => (0.5, 0.5),
+            (Some(x), Some(y), None) => (parse(x)?, parse(y)?),
+            _ => return Err(format!("expected \"factor[,x,y]\", got \"{s}\"")),
+        };
+        if !(0.0..=1.0).contains(&focus.0) || !(0.0..=1.0).contains(&focus.1) {
+            return Err("zoom focal point must be within 0..1".to_string());
+        }
+        Ok(Self { factor, focus })
+    }
+}
+
+/// Minimum-cost perfect matching of an `n`x`n` cost matrix (Hungarian
+/// algorithm with potentials, O(n³)). Returns the column assigned to each row.
+fn hungarian(n: usize, cost: impl Fn(usize, usize) -> f64) -> Vec<usize> {
+    // 1-indexed, with row/column 0 as the virtual start
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; n + 1];
+    let mut row_of = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+    for row in 1..=n {
+        row_of[0] = row;
+        let mut col = 0;
+        let mut min_to = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[col] = true;
+            let current = row_of[col];
+            let mut delta = f64::INFINITY;
+            let mut next = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost(current - 1, j - 1) - u[current] - v[j];
+                if reduced < min_to[j] {
+                    min_to[j] = reduced;
+                    way[j] = col;
+                }
+                if min_to[j] < delta {
+                    delta = min_to[j];
+                    next = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_to[j] -= delta;
+                }
+            }
+            col = next;
+            if row_of[col] == 0 {
+                break;
+            }
+        }
+        // Flip the augmenting path
+        while col != 0 {
+            let prev = way[col];
+            row_of[col] = row_of[prev];
+            col = prev;
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for j in 1..=n {
+        assignment[row_of[j] - 1] = j - 1;
+    }
+    assignment
+}
+
+/// Pairs every point of `from` with a distinct point of `to`, using the same
+/// score the diagram assigns pixels with as the matching cost.
+fn match_points(
+    from: &[Point],
+    to: &[Point],
+    img: &RgbImage,
+    max_color_dist: f64,
+    max_pos_dist: f64,
+    score: &ScoreFn,
+    args: &Args,
+) -> Vec<Point> {
+    let metric = Metric::new(args.projection, img.width(), img.height());
+    let assignment = hungarian(from.len(), |i, j| {
+        score(
+            &from[i],
+            &to[j],
+            img,
+            &metric,
+            args.weight,
+            max_color_dist,
+            max_pos_dist,
+        )
+    });
+    assignment.into_iter().map(|j| to[j]).collect()
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn lerp_u8(a: u8, b: u8, t: f64) -> u8 {
+    (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u8
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn lerp_point(a: &Point, b: &Point, t: f64) -> Point {
+    let lerp = |a: u32, b: u32| (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u32;
+    (
+        lerp(a.0, b.0),
+        lerp(a.1, b.1),
+        [0, 1, 2].map(|c| lerp_u8(a.2[c], b.2[c], t)),
+    )
+}
+
+fn cross_fade(a: &RgbImage, b: &RgbImage, t: f64) -> RgbImage {
+    RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y).0, b.get_pixel(x, y).0);
+        image::Rgb([0, 1, 2].map(|c| lerp_u8(pa[c], pb[c], t)))
+    })
+}
+
+/// Encodes `count` frames produced by `render` into a looping GIF at `path`.
+/// Fully transparent pixels of frames with alpha stay transparent.
+pub fn write_gif<I: Into<image::DynamicImage>>(
+    path: &Path,
+    force: bool,
+    frame_delay: u32,
+    count: u32,
+    mut render: impl FnMut(u32) -> I,
+) {
+    let result = output::save(path, force, |path| -> image::ImageResult<()> {
+        let mut encoder = GifEncoder::new_with_speed(File::create(path)?, 10);
+        encoder.set_repeat(Repeat::Infinite)?;
+        let delay = Delay::from_numer_denom_ms(frame_delay, 1);
+        for frame in 0..count {
+            eprint!("\rRendering frame {} / {count}", frame + 1);
+            let rgba = render(frame).into().into_rgba8();
+            encoder.encode_frame(Frame::from_parts(rgba, 0, 0, delay))?;
+        }
+        eprintln!();
+        Ok(())
+    });
+    exit_on_err(result, "write animation");
+    eprintln!("Saved animation to {}", path.display());
+}
+
+fn morph(animate: &AnimateArgs, morph_to: &Path) {
+    let args = &animate.args;
+    let from_img = load_image(&args.input, args);
+    let (width, height) = from_img.dimensions();
+    let args = &for_image(args, &from_img);
+    let to_img = load_image(morph_to, args);
+    let to_img = if to_img.dimensions() == (width, height) {
+        to_img
+    } else {
+        image::imageops::resize(
+            &to_img,
+            width,
+            height,
+            image::imageops::FilterType::Triangle,
+        )
+    };
+
+    let (max_color_dist, max_pos_dist) = max_dists(&from_img, args);
+    let mut rng = seeded_rng(args);
+    let from_points = sample_points_print_progress(&from_img, args, args.points, &mut rng);
+    let to_points = sample_points_print_progress(&to_img, args, args.points, &mut rng);
+    let mut from_points = args.seed_color.apply(&from_img, &from_points);
+    let mut to_points = args.seed_color.apply(&to_img, &to_points);
+    // Symmetry orbits can overshoot the point count differently per image
+    to_points.truncate(from_points.len());
+    from_points.truncate(to_points.len());
+
+    eprint!("Matching {} points...", from_points.len());
+    let to_points = match_points(
+        &from_points,
+        &to_points,
+        &from_img,
+        max_color_dist,
+        max_pos_dist,
+        &*score_fn_for(&ImagePlane::new(&from_img, args.blur), args),
+        args,
+    );
+    eprintln!("\rMatching {} points... Done", from_points.len());
+
+    write_gif(
+        &args.output,
+        args.force,
+        animate.frame_delay,
+        animate.frames,
+        |frame| {
+            let t = f64::from(frame) / f64::from(animate.frames - 1);
+            let img = cross_fade(&from_img, &to_img, t);
+            let points: Vec<Point> = from_points
+                .iter()
+                .zip(&to_points)
+                .map(|(a, b)| lerp_point(a, b, t))
+                .collect();
+            let img = ImagePlane::new(&img, args.blur);
+            let score = score_fn_for(&img, args);
+            generate_voronoi(&img, &points, max_color_dist, max_pos_dist, &*score, args).image
+        },
+    );
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn wobble(animate: &AnimateArgs, wobble: Wobble) {
+    let args = &animate.args;
+    let img = load_image(&args.input, args);
+    let (width, height) = img.dimensions();
+    let args = &for_image(args, &img);
+    let (max_color_dist, max_pos_dist) = max_dists(&img, args);
+    let mut rng = seeded_rng(args);
+    let points = sample_points_print_progress(&img, args, args.points, &mut rng);
+    let points = args.seed_color.apply(&img, &points);
+    let noise = Simplex::new(&mut rng);
+
+    // Each site walks a circle through noise space, so the last frame leads
+    // back into the first; sites sit far apart in it to drift independently.
+    let radius = 0.8;
+    let max_x = f64::from(width - 1);
+    let max_y = f64::from(height - 1);
+    let img = ImagePlane::new(&img, args.blur);
+    let score = score_fn_for(&img, args);
+    write_gif(
+        &args.output,
+        args.force,
+        animate.frame_delay,
+        wobble.frames,
+        |frame| {
+            let angle = std::f64::consts::TAU * f64::from(frame) / f64::from(wobble.frames);
+            let (cos, sin) = (angle.cos() * radius, angle.sin() * radius);
+            let points: Vec<Point> = points
+                .iter()
+                .enumerate()
+                .map(|(i, &(x, y, color))| {
+                    let offset = 7.3 * i as f64;
+                    let dx = noise.at(cos + offset, sin);
+                    let dy = noise.at(cos - offset, sin + 101.7);
+                    let x = (f64::from(x) + dx * wobble.amplitude).clamp(0.0, max_x);
+                    let y = (f64::from(y) + dy * wobble.amplitude).clamp(0.0, max_y);
+                    (x.round() as u32, y.round() as u32, color)
+                })
+                .collect();
+            generate_voronoi(&img, &points, max_color_dist, max_pos_dist, &*score, args).image
+        },
+    );
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn zoom(animate: &AnimateArgs, zoom: Zoom) {
+    let args = &animate.args;
+    let img = load_image(&args.input, args);
+    let (width, height) = img.dimensions();
+    let args = &for_image(args, &img);
+    let (w, h) = (f64::from(width), f64::from(height));
+    let (max_color_dist, max_pos_dist) = max_dists(&img, args);
+    let mut rng = seeded_rng(args);
+    let focus = (zoom.focus.0 * w, zoom.focus.1 * h);
+
+    // Sites live in source image coordinates so they persist across frames
+    let mut sites: Vec<(f64, f64, [u8; 3])> = Vec::with_capacity(args.points);
+    write_gif(
+        &args.output,
+        args.force,
+        animate.frame_delay,
+        animate.frames,
+        |frame| {
+            let t = f64::from(frame) / f64::from(animate.frames - 1);
+            let scale = zoom.factor.powf(t);
+            // The viewport shrinks towards the focal point, which stays put on screen
+            let origin = (focus.0 * (1.0 - 1.0 / scale), focus.1 * (1.0 - 1.0 / scale));
+            let to_source = |x: f64, y: f64| (origin.0 + x / scale, origin.1 + y / scale);
+
+            let view = RgbImage::from_fn(width, height, |x, y| {
+                let (sx, sy) = to_source(f64::from(x) + 0.5, f64::from(y) + 0.5);
+                let (sx, sy) = (
+                    (sx - 0.5).clamp(0.0, w - 1.0),
+                    (sy - 0.5).clamp(0.0, h - 1.0),
+                );
+                image::imageops::interpolate_bilinear(&img, sx as f32, sy as f32)
+                    .unwrap_or(*img.get_pixel(sx as u32, sy as u32))
+            });
+
+            let mut points: Vec<Point> = sites
+                .iter()
+                .map(|&(sx, sy, color)| ((sx - origin.0) * scale, (sy - origin.1) * scale, color))
+                .filter(|&(x, y, _)| (0.0..w).contains(&x) && (0.0..h).contains(&y))
+                .map(|(x, y, color)| (x as u32, y as u32, color))
+                .collect();
+
+            // Top up the sites that zoomed out of view with new ones in the viewport
+            if points.len() < args.points {
+                let added = sample_points(&view, args, args.points - points.len(), &mut rng);
+                let added = args.seed_color.apply(&view, &added);
+                for &(x, y, color) in &added {
+                    let (sx, sy) = to_source(f64::from(x), f64::from(y));
+                    sites.push((sx, sy, color));
+                }
+                points.extend(added);
+            }
+
+            let view = ImagePlane::new(&view, args.blur);
+            let score = score_fn_for(&view, args);
+            generate_voronoi(&view, &points, max_color_dist, max_pos_dist, &*score, args).image
+        },
+    );
+}
+
+/// A cell cut out of the diagram, moving as a rigid body once shattered.
+struct Shard {
+    cutout: RgbaImage,
+    /// Centroid of the cell in image coordinates, which it spins around
+    center: (f64, f64),
+    /// Centroid relative to the cutout's top-left corner
+    pivot: (f64, f64),
+    /// Initial velocity in image diagonals per animation, with the third
+    /// component towards the viewer in multiples of the shard's size
+    velocity: (f64, f64, f64),
+    /// Turns over the whole animation
+    spin: f64,
+}
+
+/// How far shards fall over the animation under gravity, in image diagonals.
+const SHATTER_GRAVITY: f64 = 1.2;
+
+impl Shard {
+    /// Draws the shard `t` of the way through the animation onto `frame`.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn draw(&self, frame: &mut RgbaImage, t: f64, diagonal: f64) {
+        let (vx, vy, vz) = self.velocity;
+        let center = (
+            self.center.0 + vx * diagonal * t,
+            self.center.1 + (vy * t + 0.5 * SHATTER_GRAVITY * t * t) * diagonal,
+        );
+        let scale = 1.0 + vz * t;
+        let (sin, cos) = (std::f64::consts::TAU * self.spin * t).sin_cos();
+
+        // The cutout's farthest corner bounds where the shard can land
+        let (w, h) = (
+            f64::from(self.cutout.width()),
+            f64::from(self.cutout.height()),
+        );
+        let reach = self
+            .pivot
+            .0
+            .max(w - self.pivot.0)
+            .hypot(self.pivot.1.max(h - self.pivot.1))
+            * scale;
+        let (max_x, max_y) = (f64::from(frame.width()), f64::from(frame.height()));
+        let x0 = (center.0 - reach).floor().clamp(0.0, max_x) as u32;
+        let x1 = (center.0 + reach).ceil().clamp(0.0, max_x) as u32;
+        let y0 = (center.1 - reach).floor().clamp(0.0, max_y) as u32;
+        let y1 = (center.1 + reach).ceil().clamp(0.0, max_y) as u32;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // Map the frame pixel back onto the cutout
+                let dx = (f64::from(x) + 0.5 - center.0) / scale;
+                let dy = (f64::from(y) + 0.5 - center.1) / scale;
+                let sx = cos * dx + sin * dy + self.pivot.0;
+                let sy = -sin * dx + cos * dy + self.pivot.1;
+                if sx < 0.0 || sy < 0.0 || sx >= w || sy >= h {
+                    continue;
+                }
+                let pixel = self.cutout.get_pixel(sx as u32, sy as u32);
+                if pixel.0[3] > 0 {
+                    frame.put_pixel(x, y, *pixel);
+                }
+            }
+        }
+    }
+}
+
+fn shatter(animate: &AnimateArgs) {
+    let args = &animate.args;
+    let img = load_image(&args.input, args);
+    let (width, height) = img.dimensions();
+    let args = &for_image(args, &img);
+    let (max_color_dist, max_pos_dist) = max_dists(&img, args);
+    let mut rng = seeded_rng(args);
+    let points = sample_points_print_progress(&img, args, args.points, &mut rng);
+    let points = args.seed_color.apply(&img, &points);
+    let plane = ImagePlane::new(&img, args.blur);
+    let score = score_fn_for(&plane, args);
+    let diagram = generate_voronoi(&plane, &points, max_color_dist, max_pos_dist, &*score, args);
+    let stats = cells::cell_stats(&img, &diagram.labels, diagram.colors.len());
+
+    // Shards burst away from the middle of the image
+    let middle = (f64::from(width) / 2.0, f64::from(height) / 2.0);
+    let diagonal = f64::from(width).hypot(f64::from(height));
+    let mut shards: Vec<Shard> =
+        export::cell_cutouts(&diagram.image, &diagram.labels, diagram.colors.len())
+            .into_iter()
+            .zip(&stats)
+            .filter_map(|(cutout, stats)| {
+                let (x, y, cutout) = cutout?;
+                let center = stats.centroid()?;
+                let (dx, dy) = (center.0 - middle.0, center.1 - middle.1);
+                let length = dx.hypot(dy).max(1.0);
+                let speed = rng.random_range(0.2..0.6);
+                Some(Shard {
+                    cutout,
+                    center,
+                    pivot: (center.0 - f64::from(x), center.1 - f64::from(y)),
+                    velocity: (
+                        dx / length * speed,
+                        dy / length * speed - rng.random_range(0.2..0.5),
+                        rng.random_range(0.0..1.5),
+                    ),
+                    spin: rng.random_range(-1.0..1.0),
+                })
+            })
+            .collect();
+    // Shards nearer the viewer are drawn last
+    shards.sort_by(|a, b| a.velocity.2.total_cmp(&b.velocity.2));
+
+    write_gif(
+        &args.output,
+        args.force,
+        animate.frame_delay,
+        animate.frames,
+        |frame| {
+            let t = f64::from(frame) / f64::from(animate.frames - 1);
+            let mut image = RgbaImage::new(width, height);
+            for shard in &shards {
+                shard.draw(&mut image, t, diagonal);
+            }
+            image
+        },
+    );
+}
+
+fn reveal(animate: &AnimateArgs) {
+    let args = &animate.args;
+    let img = load_image(&args.input, args);
+    let (width, height) = img.dimensions();
+    let args = &for_image(args, &img);
+    let (max_color_dist, max_pos_dist) = max_dists(&img, args);
+    let mut rng = seeded_rng(args);
+    let points = sample_points_print_progress(&img, args, args.points, &mut rng);
+    let points = args.seed_color.apply(&img, &points);
+    let plane = ImagePlane::new(&img, args.blur);
+    let score = score_fn_for(&plane, args);
+    let diagram = generate_voronoi(&plane, &points, max_color_dist, max_pos_dist, &*score, args);
+    let positions: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(x, y, _)| (f64::from(x) + 0.5, f64::from(y) + 0.5))
+        .collect();
+    let order = importance::rank(&img, &positions, &diagram.colors).order;
+
+    #[allow(clippy::cast_precision_loss)]
+    let total = order.len() as f64;
+    write_gif(
+        &args.output,
+        args.force,
+        animate.frame_delay,
+        animate.frames,
+        |frame| {
+            // Geometric steps, so early frames add sites one or two at a time
+            let t = f64::from(frame) / f64::from(animate.frames - 1);
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let count = (total.powf(t).round() as usize).clamp(1, order.len());
+            let sites: Vec<(f64, f64)> = order[..count].iter().map(|&i| positions[i]).collect();
+            let labels = treemap::power_labels(width, height, &sites, &vec![0.0; count]);
+            RgbImage::from_fn(width, height, |x, y| {
+                let site = order[labels[(y * width + x) as usize] as usize];
+                image::Rgb(diagram.colors[site])
+            })
+        },
+    );
+}
+
+/// Exits if `args` asks for anything only still renders do: the steps
+/// before and after cell assignment, side outputs and other formats.
+fn reject_still_options(args: &Args) {
+    reject_options(
+        "animate",
+        &[
+            ("--like", args.like.is_some()),
+            ("--pipeline", args.pipeline != Pipeline::Rgb),
+            ("--ensemble", args.ensemble.is_some()),
+            ("--passes", args.passes > 1),
+            ("--viewport", args.viewport.is_some()),
+            ("--shard", args.shard.is_some()),
+            ("--cache-dir", args.cache_dir.is_some()),
+            ("--snap-to-edges", args.snap_to_edges.is_some()),
+            ("--relax", args.relax > 0),
+            ("--text-safety", args.text_safety.is_some()),
+            ("--optimize", args.optimize.is_some()),
+            ("--reject-outliers", args.reject_outliers.is_some()),
+            ("--site-colors", args.site_colors > 1),
+            ("--levels", args.levels > 1),
+            ("--region-mask", args.region_mask.is_some()),
+            ("--post", args.post.is_some()),
+            ("--caption", args.caption.is_some()),
+            ("--watermark", args.watermark.is_some()),
+            ("--format", args.format != Format::Image),
+            ("--copy-metadata", args.copy_metadata),
+            ("--also-write", !args.also_write.is_empty()),
+            ("--separations", args.separations.is_some()),
+            ("--stitch-chart", args.stitch_chart.is_some()),
+            ("--jigsaw", args.jigsaw.is_some()),
+            ("--normal-map", args.normal_map.is_some()),
+            ("--boundary-matte", args.boundary_matte.is_some()),
+            ("--export-cells", args.export_cells.is_some()),
+            ("--sprite-sheet", args.sprite_sheet.is_some()),
+            ("--pyramid", args.pyramid.is_some()),
+            ("--dzi", args.dzi.is_some()),
+            ("--distance-field", args.distance_field.is_some()),
+            ("--edge-field", args.edge_field.is_some()),
+            ("--confidence", args.confidence.is_some()),
+            ("--debug-dir", args.debug_dir.is_some()),
+            ("--heightmap", args.heightmap.is_some()),
+            ("--mesh", args.mesh.is_some()),
+            ("--timings", args.timings),
+        ],
+    );
+}
+
+/// Renders the `animate` subcommand to an animated GIF.
+pub fn run(animate: &AnimateArgs) {
+    reject_still_options(&animate.args);
+    exit_on_err(
+        output::check_clobber(&animate.args.output, animate.args.force),
+        "write animation",
+    );
+    if let Some(params) = animate.wobble {
+        wobble(animate, params);
+    } else if let Some(params) = animate.zoom {
+        zoom(animate, params);
+    } else if animate.shatter {
+        shatter(animate);
+    } else if animate.reveal {
+        reveal(animate);
+    } else if let Some(morph_to) = &animate.morph_to {
+        morph(animate, morph_to);
+    } else {
+        eprintln!("Nothing to animate: pass --morph-to, --wobble, --zoom, --shatter or --reveal");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,184 @@
+//! Simulated annealing of site positions and colors, for `--optimize`.
+//!
+//! Each step nudges one site, redraws only what the nudge can change with
+//! the incremental [`Editor`], and keeps the nudge if the diagram's total
+//! color error dropped, or, while the temperature is still high, with a
+//! chance that falls the more the error grew.
+
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::incremental::{Editor, Rect};
+use crate::{Args, rng};
+
+type Point = (u32, u32, [u8; 3]);
+
+/// Iterations of `--optimize anneal` when the count is left out.
+const DEFAULT_ITERATIONS: u32 = 1000;
+
+/// Share of steps that nudge a site's color rather than its position.
+const COLOR_MOVES: f64 = 0.25;
+
+/// Largest change to a color channel in one color nudge.
+const COLOR_STEP: i16 = 16;
+
+/// Starting temperature as a share of the average error per site, and how
+/// much of it is left by the last step.
+const START_TEMPERATURE: f64 = 0.05;
+const END_TEMPERATURE: f64 = 1e-3;
+
+/// An optimization run after sampling and relaxation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Optimize {
+    /// Simulated annealing over this many steps
+    Anneal { iterations: u32 },
+}
+
+impl FromStr for Optimize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (method, iterations) = match s.split_once(':') {
+            Some((method, iterations)) => (method, Some(iterations)),
+            None => (s, None),
+        };
+        if method != "anneal" {
+            return Err(format!(
+                "unknown optimization \"{method}\", expected anneal[:iterations]"
+            ));
+        }
+        let iterations = match iterations {
+            Some(iterations) => iterations
+                .parse()
+                .ok()
+                .filter(|&iterations| iterations > 0)
+                .ok_or_else(|| format!("invalid iteration count \"{iterations}\""))?,
+            None => DEFAULT_ITERATIONS,
+        };
+        Ok(Self::Anneal { iterations })
+    }
+}
+
+/// Squared RGB distance between two colors.
+fn error(a: [u8; 3], b: [u8; 3]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| (f64::from(a) - f64::from(b)).powi(2))
+        .sum()
+}
+
+/// Squared error of every pixel of `drawn` against `img`.
+struct Errors {
+    per_pixel: Vec<f64>,
+    total: f64,
+}
+
+impl Errors {
+    fn new(img: &image::RgbImage, drawn: &image::RgbImage) -> Self {
+        let per_pixel: Vec<f64> = img
+            .pixels()
+            .zip(drawn.pixels())
+            .map(|(a, b)| error(a.0, b.0))
+            .collect();
+        let total = per_pixel.iter().sum();
+        Self { per_pixel, total }
+    }
+
+    /// Measures the pixels in `rect` again after `drawn` changed there.
+    fn update(&mut self, img: &image::RgbImage, drawn: &image::RgbImage, rect: Option<Rect>) {
+        let Some((x0, y0, x1, y1)) = rect else {
+            return;
+        };
+        let width = img.width();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let index = (y * width + x) as usize;
+                let new = error(img.get_pixel(x, y).0, drawn.get_pixel(x, y).0);
+                self.total += new - self.per_pixel[index];
+                self.per_pixel[index] = new;
+            }
+        }
+    }
+}
+
+/// `point` nudged by up to `radius` pixels, or in color, at random.
+fn nudge(
+    point: Point,
+    radius: f64,
+    (width, height): (u32, u32),
+    rng: &mut rng::Generator,
+) -> Point {
+    let (x, y, color) = point;
+    if rng.random_bool(COLOR_MOVES) {
+        let color = color.map(|c| {
+            let c = i16::from(c) + rng.random_range(-COLOR_STEP..=COLOR_STEP);
+            u8::try_from(c.clamp(0, 255)).unwrap_or(u8::MAX)
+        });
+        return (x, y, color);
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let mut shift = |v: u32, size: u32| {
+        let offset = rng.random_range(-radius..=radius).round();
+        (f64::from(v) + offset).clamp(0.0, f64::from(size - 1)) as u32
+    };
+    (shift(x, width), shift(y, height), color)
+}
+
+/// Anneals `points` on `img` over `iterations` steps, cooling
+/// geometrically from a temperature set by the starting error. Returns
+/// the best points seen.
+#[must_use]
+pub fn anneal(
+    img: &image::RgbImage,
+    points: Vec<Point>,
+    iterations: u32,
+    args: &Args,
+) -> Vec<Point> {
+    let dimensions = img.dimensions();
+    let mut rng = args.rng.seeded(args.seed.unwrap_or_default());
+    let count = points.len();
+    let mut editor = Editor::new(img.clone(), points, args);
+    let mut errors = Errors::new(img, editor.image());
+    let start_error = errors.total;
+    let (mut best, mut best_error) = (editor.points().to_vec(), errors.total);
+
+    #[allow(clippy::cast_precision_loss)]
+    let start_temperature = START_TEMPERATURE * errors.total / count as f64;
+    // Steps start a quarter cell wide and end a pixel wide
+    #[allow(clippy::cast_precision_loss)]
+    let start_radius =
+        ((f64::from(dimensions.0) * f64::from(dimensions.1) / count as f64).sqrt() / 4.0).max(1.0);
+    let mut accepted = 0;
+    for i in 0..iterations {
+        if i % 16 == 0 {
+            eprint!("\rAnnealing... {i} / {iterations}");
+        }
+        let progress = f64::from(i) / f64::from(iterations);
+        let temperature = start_temperature * END_TEMPERATURE.powf(progress);
+        let radius = start_radius.powf(1.0 - progress);
+
+        let site = rng.random_range(0..count);
+        let old = editor.points()[site];
+        let before = errors.total;
+        let changed = editor.set_site(site, nudge(old, radius, dimensions, &mut rng));
+        errors.update(img, editor.image(), changed);
+        let growth = errors.total - before;
+        if growth <= 0.0 || rng.random_bool((-growth / temperature).exp().min(1.0)) {
+            accepted += 1;
+            if errors.total < best_error {
+                best_error = errors.total;
+                best = editor.points().to_vec();
+            }
+        } else {
+            let changed = editor.set_site(site, old);
+            errors.update(img, editor.image(), changed);
+        }
+    }
+    eprintln!("\rAnnealing... {iterations} / {iterations}");
+    println!(
+        "Annealing kept {accepted} of {iterations} moves, error down {:.1}%",
+        (1.0 - best_error / start_error.max(f64::MIN_POSITIVE)) * 100.0
+    );
+    best
+}
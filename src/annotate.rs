@@ -0,0 +1,176 @@
+//! Captions and watermarks drawn onto the finished image, for `--caption`
+//! and `--watermark`.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::color::{blend, luminance};
+use crate::{Args, exit_on_err, font};
+
+/// Image width per unit of caption scale, so captions grow with the image.
+const CAPTION_WIDTH_PER_SCALE: u32 = 320;
+const MAX_CAPTION_SCALE: u32 = 8;
+
+/// Gap between the image edge and a caption or watermark, in caption scale
+/// units.
+const MARGIN: u32 = 4;
+
+/// Largest share of the image's width or height a watermark may cover
+/// before it is scaled down.
+const MAX_WATERMARK_SHARE: u32 = 4;
+
+/// Corner of the image a watermark sits in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Self::TopLeft),
+            "top-right" => Ok(Self::TopRight),
+            "bottom-left" => Ok(Self::BottomLeft),
+            "bottom-right" => Ok(Self::BottomRight),
+            _ => Err(format!(
+                "invalid corner '{s}' (expected top-left, top-right, bottom-left or bottom-right)"
+            )),
+        }
+    }
+}
+
+/// A logo blended into a corner, as `path[@corner[:opacity]]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    pub path: PathBuf,
+    pub corner: Corner,
+    pub opacity: f64,
+}
+
+impl FromStr for Watermark {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((path, placement)) = s.rsplit_once('@') else {
+            return Ok(Self {
+                path: s.into(),
+                corner: Corner::BottomRight,
+                opacity: 0.5,
+            });
+        };
+        let (corner, opacity) = match placement.split_once(':') {
+            Some((corner, opacity)) => {
+                let opacity = opacity
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|opacity| (0.0..=1.0).contains(opacity))
+                    .ok_or_else(|| format!("invalid opacity '{opacity}' (expected 0 to 1)"))?;
+                (corner, opacity)
+            }
+            None => (placement, 0.5),
+        };
+        Ok(Self {
+            path: path.into(),
+            corner: corner.parse()?,
+            opacity,
+        })
+    }
+}
+
+/// Glyph scale of a caption on an image `width` pixels wide: larger on
+/// larger images, but small enough for `text` to fit between the margins.
+fn caption_scale(text: &str, width: u32) -> u32 {
+    let mut scale = (width / CAPTION_WIDTH_PER_SCALE).clamp(1, MAX_CAPTION_SCALE);
+    while scale > 1 && font::text_width(text, scale) + 2 * MARGIN * scale > width {
+        scale -= 1;
+    }
+    scale
+}
+
+/// Draws `text` along the bottom left of `image`, in black or white,
+/// whichever stands out more from the pixels behind it, over a one-pixel
+/// shadow of the other.
+pub fn caption(image: &mut image::RgbImage, text: &str) {
+    let (width, height) = image.dimensions();
+    let scale = caption_scale(text, width);
+    let margin = MARGIN * scale;
+    let (x, y) = (
+        margin,
+        height.saturating_sub(margin + font::GLYPH_HEIGHT * scale),
+    );
+    let (right, bottom) = (
+        (x + font::text_width(text, scale)).min(width),
+        (y + font::GLYPH_HEIGHT * scale).min(height),
+    );
+    let (mut sum, mut count) = (0.0, 0.0);
+    for py in y..bottom {
+        for px in x.min(right)..right {
+            sum += luminance(image.get_pixel(px, py).0);
+            count += 1.0;
+        }
+    }
+    let (ink, shadow) = if count == 0.0 || sum / count < 128.0 {
+        ([255; 3], [0; 3])
+    } else {
+        ([0; 3], [255; 3])
+    };
+    let (x, y) = (i64::from(x), i64::from(y));
+    let offset = i64::from(scale.div_ceil(2));
+    font::draw_text(image, x + offset, y + offset, text, scale, shadow);
+    font::draw_text(image, x, y, text, scale, ink);
+}
+
+/// Blends the watermark's image into its corner of `image`, scaled down if
+/// it would cover more than a quarter of the width or height, through its
+/// own alpha times its opacity.
+pub fn watermark(image: &mut image::RgbImage, watermark: &Watermark, max_pixels: u64) {
+    let logo = exit_on_err(
+        crate::open_image(&watermark.path, max_pixels),
+        "open watermark",
+    );
+    let (width, height) = image.dimensions();
+    let (max_width, max_height) = (
+        (width / MAX_WATERMARK_SHARE).max(1),
+        (height / MAX_WATERMARK_SHARE).max(1),
+    );
+    let logo = if logo.width() > max_width || logo.height() > max_height {
+        logo.resize(max_width, max_height, image::imageops::FilterType::Triangle)
+    } else {
+        logo
+    }
+    .into_rgba8();
+    let margin = MARGIN * caption_scale("", width);
+    let left = match watermark.corner {
+        Corner::TopLeft | Corner::BottomLeft => margin,
+        Corner::TopRight | Corner::BottomRight => width.saturating_sub(margin + logo.width()),
+    };
+    let top = match watermark.corner {
+        Corner::TopLeft | Corner::TopRight => margin,
+        Corner::BottomLeft | Corner::BottomRight => height.saturating_sub(margin + logo.height()),
+    };
+    for (x, y, pixel) in logo.enumerate_pixels() {
+        let (px, py) = (left + x, top + y);
+        if px >= width || py >= height {
+            continue;
+        }
+        let [r, g, b, a] = pixel.0;
+        let alpha = f64::from(a) / 255.0 * watermark.opacity;
+        let under = image.get_pixel_mut(px, py);
+        under.0 = blend(under.0, [r, g, b], alpha);
+    }
+}
+
+/// Draws `--watermark` and then `--caption` onto `image`, if given.
+pub fn apply(image: &mut image::RgbImage, args: &Args) {
+    if let Some(mark) = &args.watermark {
+        watermark(image, mark, args.max_pixels);
+    }
+    if let Some(text) = &args.caption {
+        caption(image, text);
+    }
+}
@@ -0,0 +1,92 @@
+//! Parameters suggested by analyzing the input image, for `--auto`.
+
+use crate::{Args, edges};
+
+/// Sobel gradient of the 0-255 luminance above which a pixel counts as an
+/// edge; about a 25-level step between neighbours.
+const EDGE_GRADIENT: f64 = 100.0;
+
+/// Points per megapixel of a flat image, and how many more a fully edged
+/// one gets.
+const BASE_DENSITY: f64 = 800.0;
+const EDGE_DENSITY: f64 = 8000.0;
+
+/// What `--auto` measured and what it picked from it.
+#[derive(Debug, Clone, Copy)]
+pub struct Suggestion {
+    pub megapixels: f64,
+    /// Fraction of the pixels lying on an edge
+    pub edge_density: f64,
+    /// Hasler and Süsstrunk's colorfulness, roughly 0 for gray images to
+    /// over 100 for vivid ones
+    pub colorfulness: f64,
+    pub points: usize,
+    pub weight: f64,
+    pub blur: f32,
+}
+
+/// Hasler and Süsstrunk's colorfulness of `img`, from the spread and mean of
+/// its red-green and yellow-blue opponent channels.
+fn colorfulness(img: &image::RgbImage) -> f64 {
+    let mut sums = [0.0; 4];
+    for pixel in img.pixels() {
+        let [r, g, b] = pixel.0.map(f64::from);
+        let (rg, yb) = (r - g, 0.5 * (r + g) - b);
+        for (sum, value) in sums.iter_mut().zip([rg, yb, rg * rg, yb * yb]) {
+            *sum += value;
+        }
+    }
+    let n = f64::from(img.width()) * f64::from(img.height());
+    let [rg, yb, rg_sq, yb_sq] = sums.map(|sum| sum / n);
+    let spread = ((rg_sq - rg * rg).max(0.0) + (yb_sq - yb * yb).max(0.0)).sqrt();
+    spread + 0.3 * rg.hypot(yb)
+}
+
+/// Suggests parameters for `img`: more points the busier its edges, more
+/// color weight the more colorful it is, and more blur the larger it is.
+#[must_use]
+pub fn suggest(img: &image::RgbImage) -> Suggestion {
+    let megapixels = f64::from(img.width()) * f64::from(img.height()) / 1e6;
+    let gradients = edges::gradient_map(img);
+    #[allow(clippy::cast_precision_loss)]
+    let edge_density = gradients
+        .iter()
+        .filter(|&&gradient| gradient >= EDGE_GRADIENT)
+        .count() as f64
+        / gradients.len().max(1) as f64;
+    let colorfulness = colorfulness(img);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let points = (megapixels * (BASE_DENSITY + EDGE_DENSITY * edge_density))
+        .round()
+        .clamp(64.0, 20_000.0) as usize;
+    let weight = (1.0 + colorfulness / 40.0).clamp(0.5, 5.0);
+    #[allow(clippy::cast_possible_truncation)]
+    let blur = (megapixels.sqrt() * 3.0).clamp(1.0, 8.0) as f32;
+    Suggestion {
+        megapixels,
+        edge_density,
+        colorfulness,
+        points,
+        weight: (weight * 10.0).round() / 10.0,
+        blur: (blur * 10.0).round() / 10.0,
+    }
+}
+
+/// Replaces the points, weight and blur of `args` with those suggested for
+/// `img`, printing what was measured and picked.
+pub fn apply(args: &mut Args, img: &image::RgbImage) {
+    let suggestion = suggest(img);
+    println!(
+        "Auto: {:.2} megapixels, {:.1}% edges, colorfulness {:.0}",
+        suggestion.megapixels,
+        suggestion.edge_density * 100.0,
+        suggestion.colorfulness
+    );
+    println!(
+        "Auto: picked --points {} --weight {} --blur {}; pass these instead of --auto to refine them",
+        suggestion.points, suggestion.weight, suggestion.blur
+    );
+    args.points = suggestion.points;
+    args.weight = suggestion.weight;
+    args.blur = suggestion.blur;
+}
@@ -0,0 +1,141 @@
+//! Brick mosaics: the diagram snapped to a stud grid in brick colors.
+
+use crate::color::{blend, to_u8};
+
+/// Solid brick colors: name and RGB.
+const BRICK_COLORS: &[(&str, [u8; 3])] = &[
+    ("White", [0xff, 0xff, 0xff]),
+    ("Black", [0x05, 0x13, 0x1d]),
+    ("Red", [0xc9, 0x1a, 0x09]),
+    ("Dark Red", [0x72, 0x0e, 0x0f]),
+    ("Blue", [0x00, 0x55, 0xbf]),
+    ("Dark Blue", [0x0a, 0x34, 0x63]),
+    ("Medium Blue", [0x5a, 0x93, 0xdb]),
+    ("Medium Azure", [0x36, 0xae, 0xbf]),
+    ("Sand Blue", [0x60, 0x74, 0xa1]),
+    ("Dark Turquoise", [0x00, 0x8f, 0x9b]),
+    ("Yellow", [0xf2, 0xcd, 0x37]),
+    ("Bright Light Yellow", [0xff, 0xf0, 0x3a]),
+    ("Bright Light Orange", [0xf8, 0xbb, 0x3d]),
+    ("Orange", [0xfe, 0x8a, 0x18]),
+    ("Dark Orange", [0xa9, 0x55, 0x00]),
+    ("Green", [0x23, 0x78, 0x41]),
+    ("Bright Green", [0x4b, 0x9f, 0x4a]),
+    ("Dark Green", [0x18, 0x46, 0x32]),
+    ("Lime", [0xbb, 0xe9, 0x0b]),
+    ("Olive Green", [0x9b, 0x9a, 0x5a]),
+    ("Sand Green", [0xa0, 0xbc, 0xac]),
+    ("Tan", [0xe4, 0xcd, 0x9e]),
+    ("Dark Tan", [0x95, 0x8a, 0x73]),
+    ("Light Nougat", [0xf6, 0xd7, 0xb3]),
+    ("Nougat", [0xd0, 0x91, 0x68]),
+    ("Medium Nougat", [0xaa, 0x7d, 0x55]),
+    ("Reddish Brown", [0x58, 0x2a, 0x12]),
+    ("Dark Brown", [0x35, 0x21, 0x00]),
+    ("Light Bluish Gray", [0xa0, 0xa5, 0xa9]),
+    ("Dark Bluish Gray", [0x6c, 0x6e, 0x68]),
+    ("Bright Pink", [0xe4, 0xad, 0xc8]),
+    ("Coral", [0xff, 0x69, 0x8f]),
+    ("Magenta", [0x92, 0x39, 0x78]),
+    ("Lavender", [0xe1, 0xd5, 0xed]),
+    ("Medium Lavender", [0xac, 0x78, 0xba]),
+    ("Dark Purple", [0x3f, 0x36, 0x91]),
+];
+
+/// The brick color of each stud on a grid over the image.
+pub struct BrickLayout {
+    columns: u32,
+    rows: u32,
+    /// Index into [`BRICK_COLORS`] of each stud, in row-major order
+    studs: Vec<usize>,
+    size: u32,
+}
+
+fn nearest_brick(color: [u8; 3]) -> usize {
+    let dist = |rgb: [u8; 3]| -> u32 {
+        (0..3)
+            .map(|c| u32::from(rgb[c].abs_diff(color[c])).pow(2))
+            .sum()
+    };
+    (0..BRICK_COLORS.len())
+        .min_by_key(|&i| dist(BRICK_COLORS[i].1))
+        .unwrap_or(0)
+}
+
+impl BrickLayout {
+    /// Lays out `size`×`size` pixel studs, each taking the brick color closest
+    /// to the cell under its center.
+    #[must_use]
+    pub fn new(labels: &[u32], colors: &[[u8; 3]], width: u32, height: u32, size: u32) -> Self {
+        let size = size.max(1);
+        let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+        let mut studs = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for col in 0..columns {
+                let x = (col * size + size / 2).min(width - 1);
+                let y = (row * size + size / 2).min(height - 1);
+                studs.push(nearest_brick(
+                    colors[labels[(y * width + x) as usize] as usize],
+                ));
+            }
+        }
+        Self {
+            columns,
+            rows,
+            studs,
+            size,
+        }
+    }
+
+    /// Draws the mosaic as seen from above, with a shaded stud on each plate.
+    #[must_use]
+    pub fn render(&self, width: u32, height: u32) -> image::RgbImage {
+        let size = f64::from(self.size);
+        let radius = size * 0.3;
+        image::RgbImage::from_fn(width, height, |x, y| {
+            let (col, row) = (x / self.size, y / self.size);
+            let base = BRICK_COLORS[self.studs[(row * self.columns + col) as usize]].1;
+            // Offset from the stud center, in pixels
+            let dx = f64::from(x % self.size) + 0.5 - size / 2.0;
+            let dy = f64::from(y % self.size) + 0.5 - size / 2.0;
+            let dist = dx.hypot(dy);
+            let color = if dist < radius {
+                // Light from the top left
+                let shade = -(dx + dy) / (radius * 2.0);
+                blend(
+                    base,
+                    if shade > 0.0 { [255; 3] } else { [0; 3] },
+                    shade.abs() * 0.35,
+                )
+            } else if dist < radius + 1.0 {
+                base.map(|c| to_u8(f64::from(c) * 0.75))
+            } else {
+                base
+            };
+            image::Rgb(color)
+        })
+    }
+
+    /// Number of 1×1 plates needed per color, most used first.
+    #[must_use]
+    pub fn parts(&self) -> Vec<(&'static str, usize)> {
+        let mut counts = vec![0; BRICK_COLORS.len()];
+        for &stud in &self.studs {
+            counts[stud] += 1;
+        }
+        let mut parts: Vec<_> = BRICK_COLORS
+            .iter()
+            .zip(counts)
+            .filter(|&(_, count)| count > 0)
+            .map(|(&(name, _), count)| (name, count))
+            .collect();
+        parts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        parts
+    }
+
+    /// Size of the mosaic in studs.
+    #[must_use]
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.columns, self.rows)
+    }
+}
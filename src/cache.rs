@@ -0,0 +1,81 @@
+//! On-disk cache of sampled points, so renders that only change how cells
+//! are drawn skip sampling.
+
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::Args;
+
+/// 64-bit FNV-1a, which unlike `std`'s hasher is stable across releases.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Cache key for sampling `img` with `args`: the pixels plus every option
+/// that affects which points get picked.
+pub fn key(img: &image::RgbImage, args: &Args) -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(&img.width().to_le_bytes());
+    hash.write(&img.height().to_le_bytes());
+    hash.write(img.as_raw());
+    let params = format!(
+        "{} {} {} {:?} {:?} {} {} {} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        args.sampler.name(),
+        args.points,
+        args.seed,
+        args.rng,
+        args.selection_power,
+        args.selection_offset,
+        args.proxy_scale,
+        args.projection,
+        args.symmetry,
+    );
+    hash.write(params.as_bytes());
+    hash.0
+}
+
+fn path(dir: &Path, key: u64) -> PathBuf {
+    dir.join(format!("{key:016x}.points"))
+}
+
+/// Points cached under `key`, if there are any and they parse.
+pub fn load(dir: &Path, key: u64) -> Option<Vec<(u32, u32, [u8; 3])>> {
+    let text = std::fs::read_to_string(path(dir, key)).ok()?;
+    text.lines()
+        .map(|line| {
+            let mut fields = line.split(' ').map(str::parse::<u32>);
+            let mut next = || fields.next()?.ok();
+            let (x, y) = (next()?, next()?);
+            let color = [next()?, next()?, next()?].map(|c| u8::try_from(c).ok());
+            Some((x, y, [color[0]?, color[1]?, color[2]?]))
+        })
+        .collect()
+}
+
+/// Stores `points` under `key`, creating `dir` if needed.
+///
+/// # Errors
+///
+/// Returns any I/O error from creating `dir` or writing the cache file.
+pub fn store(dir: &Path, key: u64, points: &[(u32, u32, [u8; 3])]) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut text = String::new();
+    for (x, y, [r, g, b]) in points {
+        let _ = writeln!(text, "{x} {y} {r} {g} {b}");
+    }
+    crate::output::save(&path(dir, key), true, |path| std::fs::write(path, &text))
+        .map_err(io::Error::other)
+}
@@ -0,0 +1,309 @@
+//! Per-cell statistics gathered from a label map.
+
+/// Accumulated statistics for the pixels assigned to a single cell.
+#[derive(Debug, Clone, Default)]
+pub struct CellStats {
+    /// Number of pixels in the cell
+    pub count: u64,
+    /// Sum of each color channel over the cell
+    pub color_sum: [f64; 3],
+    /// Sum of each color channel squared over the cell
+    pub color_sq_sum: [f64; 3],
+    /// Sums of x, y, x², xy and y² over the cell
+    pub pos_sum: [f64; 5],
+    /// Sums of each color channel multiplied by x and by y
+    pub color_pos_sum: [[f64; 2]; 3],
+}
+
+/// A linear color ramp `mean + dx * (x - cx) + dy * (y - cy)` per channel.
+#[derive(Debug, Clone, Copy)]
+pub struct LinearGradient {
+    pub center: (f64, f64),
+    pub mean: [f64; 3],
+    pub dx: [f64; 3],
+    pub dy: [f64; 3],
+}
+
+impl LinearGradient {
+    /// Evaluates the gradient at pixel `(x, y)`.
+    #[must_use]
+    pub fn at(&self, x: u32, y: u32) -> [u8; 3] {
+        let ox = f64::from(x) - self.center.0;
+        let oy = f64::from(y) - self.center.1;
+        std::array::from_fn(|c| {
+            crate::color::to_u8(self.mean[c] + self.dx[c] * ox + self.dy[c] * oy)
+        })
+    }
+}
+
+impl CellStats {
+    /// Counts pixel `(x, y)` of color `pixel` towards the cell.
+    pub fn add(&mut self, x: u32, y: u32, pixel: [u8; 3]) {
+        self.accumulate(x, y, pixel, 1.0);
+        self.count += 1;
+    }
+
+    /// Takes back a pixel previously counted with [`CellStats::add`].
+    pub fn remove(&mut self, x: u32, y: u32, pixel: [u8; 3]) {
+        self.accumulate(x, y, pixel, -1.0);
+        self.count -= 1;
+    }
+
+    fn accumulate(&mut self, x: u32, y: u32, pixel: [u8; 3], sign: f64) {
+        let (x, y) = (f64::from(x), f64::from(y));
+        for (pos, value) in self.pos_sum.iter_mut().zip([x, y, x * x, x * y, y * y]) {
+            *pos += sign * value;
+        }
+        for (c, &value) in pixel.iter().enumerate() {
+            let value = sign * f64::from(value);
+            self.color_sum[c] += value;
+            self.color_sq_sum[c] += value * f64::from(pixel[c]);
+            self.color_pos_sum[c][0] += value * x;
+            self.color_pos_sum[c][1] += value * y;
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn count_f64(&self) -> f64 {
+        self.count as f64
+    }
+
+    /// Average color of the cell, or `None` if the cell is empty.
+    #[must_use]
+    pub fn average(&self) -> Option<[u8; 3]> {
+        if self.count == 0 {
+            return None;
+        }
+        let count = self.count_f64();
+        Some(self.color_sum.map(|sum| crate::color::to_u8(sum / count)))
+    }
+
+    /// Color variance of the cell, averaged over the channels, or `None` if
+    /// the cell is empty.
+    #[must_use]
+    pub fn color_variance(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let count = self.count_f64();
+        let variance: f64 = Iterator::zip(self.color_sum.iter(), &self.color_sq_sum)
+            .map(|(sum, sq_sum)| sq_sum / count - (sum / count).powi(2))
+            .sum();
+        Some(variance / 3.0)
+    }
+
+    /// Centroid of the cell, or `None` if the cell is empty.
+    #[must_use]
+    pub fn centroid(&self) -> Option<(f64, f64)> {
+        if self.count == 0 {
+            return None;
+        }
+        let count = self.count_f64();
+        Some((self.pos_sum[0] / count, self.pos_sum[1] / count))
+    }
+
+    /// Covariance `(xx, xy, yy)` of the cell's pixel positions.
+    #[must_use]
+    pub fn covariance(&self) -> Option<(f64, f64, f64)> {
+        let (cx, cy) = self.centroid()?;
+        let n = self.count_f64();
+        Some((
+            self.pos_sum[2] / n - cx * cx,
+            self.pos_sum[3] / n - cx * cy,
+            self.pos_sum[4] / n - cy * cy,
+        ))
+    }
+
+    /// Eccentricity in `0..1` of the ellipse with the cell's second moments;
+    /// 0 for round cells, approaching 1 for elongated ones.
+    #[must_use]
+    pub fn eccentricity(&self) -> Option<f64> {
+        let (sxx, sxy, syy) = self.covariance()?;
+        let mean = f64::midpoint(sxx, syy);
+        let spread = (((sxx - syy) / 2.0).powi(2) + sxy * sxy).sqrt();
+        let (major, minor) = (mean + spread, mean - spread);
+        if major <= 0.0 {
+            return Some(0.0);
+        }
+        Some((1.0 - (minor / major).max(0.0)).sqrt())
+    }
+
+    /// Least-squares linear color gradient over the cell's pixels.
+    ///
+    /// Cells too thin to fit a plane get a flat gradient at their average color.
+    #[must_use]
+    pub fn gradient(&self) -> Option<LinearGradient> {
+        let (cx, cy) = self.centroid()?;
+        let (sxx, sxy, syy) = self.covariance()?;
+        let n = self.count_f64();
+        let mean = self.color_sum.map(|sum| sum / n);
+        let det = sxx * syy - sxy * sxy;
+        let mut dx = [0.0; 3];
+        let mut dy = [0.0; 3];
+        if det > 1e-6 {
+            for c in 0..3 {
+                let scx = self.color_pos_sum[c][0] / n - mean[c] * cx;
+                let scy = self.color_pos_sum[c][1] / n - mean[c] * cy;
+                dx[c] = (syy * scx - sxy * scy) / det;
+                dy[c] = (sxx * scy - sxy * scx) / det;
+            }
+        }
+        Some(LinearGradient {
+            center: (cx, cy),
+            mean,
+            dx,
+            dy,
+        })
+    }
+}
+
+/// The sums behind a [`CellStats`] in integers, so sums of separate bands
+/// of pixels add up to the same totals however the image is split.
+#[derive(Debug, Clone, Copy, Default)]
+struct ExactSums {
+    count: u64,
+    color_sum: [u64; 3],
+    color_sq_sum: [u64; 3],
+    pos_sum: [u128; 5],
+    color_pos_sum: [[u128; 2]; 3],
+}
+
+impl ExactSums {
+    fn add(&mut self, x: u32, y: u32, pixel: [u8; 3]) {
+        let (x, y) = (u128::from(x), u128::from(y));
+        for (pos, value) in self.pos_sum.iter_mut().zip([x, y, x * x, x * y, y * y]) {
+            *pos += value;
+        }
+        for (c, &value) in pixel.iter().enumerate() {
+            self.color_sum[c] += u64::from(value);
+            self.color_sq_sum[c] += u64::from(value) * u64::from(value);
+            self.color_pos_sum[c][0] += u128::from(value) * x;
+            self.color_pos_sum[c][1] += u128::from(value) * y;
+        }
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        let pairs = self.color_sum.iter_mut().zip(&other.color_sum);
+        for (sum, value) in pairs.chain(self.color_sq_sum.iter_mut().zip(&other.color_sq_sum)) {
+            *sum += value;
+        }
+        let pairs = self.pos_sum.iter_mut().zip(&other.pos_sum);
+        let pairs = pairs.chain(
+            self.color_pos_sum
+                .as_flattened_mut()
+                .iter_mut()
+                .zip(other.color_pos_sum.as_flattened()),
+        );
+        for (sum, value) in pairs {
+            *sum += value;
+        }
+    }
+}
+
+impl From<ExactSums> for CellStats {
+    #[allow(clippy::cast_precision_loss)]
+    fn from(sums: ExactSums) -> Self {
+        Self {
+            count: sums.count,
+            color_sum: sums.color_sum.map(|sum| sum as f64),
+            color_sq_sum: sums.color_sq_sum.map(|sum| sum as f64),
+            pos_sum: sums.pos_sum.map(|sum| sum as f64),
+            color_pos_sum: sums.color_pos_sum.map(|sums| sums.map(|sum| sum as f64)),
+        }
+    }
+}
+
+/// Rows below which [`cell_stats`] does not split the image between threads.
+const MIN_BAND_ROWS: u32 = 64;
+
+/// Bytes the per-band sums of [`cell_stats`] may take together. Each band
+/// keeps a full table of sums for every cell, so diagrams with many cells
+/// are summed in fewer bands, down to one, rather than in one per thread.
+const BAND_SUMS_BUDGET: usize = 256 << 20;
+
+/// How many bands [`cell_stats`] splits `height` rows of `cells` cells into.
+fn band_count(height: u32, cells: usize, threads: usize) -> u32 {
+    let table = (cells * std::mem::size_of::<ExactSums>()).max(1);
+    let tables = BAND_SUMS_BUDGET / table;
+    u32::try_from(threads.min(tables))
+        .unwrap_or(u32::MAX)
+        .min(height / MIN_BAND_ROWS)
+        .max(1)
+}
+
+/// Gathers statistics for each of `cells` cells from `img`, where `labels`
+/// holds the cell index of every pixel in row-major order.
+///
+/// Large images are split into bands of rows, each summed on its own thread
+/// in integers, which are then merged band by band and only converted to
+/// floating point at the end, so the result does not depend on how the
+/// image was split.
+#[must_use]
+pub fn cell_stats(img: &image::RgbImage, labels: &[u32], cells: usize) -> Vec<CellStats> {
+    let (width, height) = img.dimensions();
+    cell_stats_of(width, height, |x, y| img.get_pixel(x, y).0, labels, cells)
+}
+
+/// [`cell_stats`] of a `width` by `height` image whose pixel `(x, y)` is
+/// `pixel(x, y)`, for images not held as RGB.
+#[must_use]
+pub fn cell_stats_of(
+    width: u32,
+    height: u32,
+    pixel: impl Fn(u32, u32) -> [u8; 3] + Sync,
+    labels: &[u32],
+    cells: usize,
+) -> Vec<CellStats> {
+    let threads = std::thread::available_parallelism().map_or(1, std::num::NonZero::get);
+    let bands = band_count(height, cells, threads);
+    let band_rows = height.div_ceil(bands);
+    let band = |first: u32| {
+        let mut stats = vec![ExactSums::default(); cells];
+        for y in first..(first + band_rows).min(height) {
+            let row = &labels[(y * width) as usize..][..width as usize];
+            for (x, &label) in (0..width).zip(row) {
+                stats[label as usize].add(x, y, pixel(x, y));
+            }
+        }
+        stats
+    };
+    let sums = if bands == 1 {
+        band(0)
+    } else {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..bands)
+                .map(|i| scope.spawn(move || band(i * band_rows)))
+                .collect();
+            let mut handles = handles.into_iter();
+            let mut stats = handles
+                .next()
+                .expect("at least one band")
+                .join()
+                .expect("cell statistics thread panicked");
+            for handle in handles {
+                let partial = handle.join().expect("cell statistics thread panicked");
+                for (total, partial) in stats.iter_mut().zip(&partial) {
+                    total.merge(partial);
+                }
+            }
+            stats
+        })
+    };
+    sums.into_iter().map(CellStats::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn many_cells_are_summed_in_fewer_bands() {
+        assert_eq!(band_count(4096, 100, 8), 8);
+        assert_eq!(band_count(100, 100, 8), 1);
+        let table = std::mem::size_of::<ExactSums>();
+        assert_eq!(band_count(4096, BAND_SUMS_BUDGET / table / 3, 8), 3);
+        assert_eq!(band_count(4096, BAND_SUMS_BUDGET / table * 2, 8), 1);
+    }
+}
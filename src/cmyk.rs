@@ -0,0 +1,114 @@
+//! CMYK TIFF output for prepress, converted through an ICC output profile or,
+//! without one, by a plain formula with adjustable black generation.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+use tiff::encoder::{TiffEncoder, colortype::CMYK8};
+use tiff::tags::Tag;
+
+/// How much of the gray shared by cyan, magenta and yellow is printed with
+/// black ink instead, when converting without a profile.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum BlackGeneration {
+    /// No black at all, only cyan, magenta and yellow
+    None,
+    /// A quarter of the gray component
+    Light,
+    /// Half of the gray component
+    Medium,
+    /// Three quarters of the gray component
+    Heavy,
+    /// All of the gray component, for the least ink
+    Maximum,
+}
+
+impl BlackGeneration {
+    /// Fraction of the gray component replaced by black.
+    fn amount(self) -> f64 {
+        match self {
+            Self::None => 0.0,
+            Self::Light => 0.25,
+            Self::Medium => 0.5,
+            Self::Heavy => 0.75,
+            Self::Maximum => 1.0,
+        }
+    }
+}
+
+/// `image` as interleaved CMYK bytes by the device formula, replacing the
+/// part of the gray component `black` asks for with black ink.
+fn convert_plain(image: &image::RgbImage, black: BlackGeneration) -> Vec<u8> {
+    let amount = black.amount();
+    image
+        .pixels()
+        .flat_map(|pixel| {
+            let [r, g, b] = pixel.0.map(|c| f64::from(c) / 255.0);
+            let k = amount * (1.0 - r.max(g).max(b));
+            let ink = |c: f64| {
+                if k < 1.0 {
+                    (1.0 - c - k) / (1.0 - k)
+                } else {
+                    0.0
+                }
+            };
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            [ink(r), ink(g), ink(b), k].map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+        })
+        .collect()
+}
+
+/// `image`, taken to be sRGB, as interleaved CMYK bytes for the CMYK output
+/// profile in `icc`.
+fn convert_profile(image: &image::RgbImage, icc: &[u8]) -> Result<Vec<u8>, String> {
+    let profile =
+        ColorProfile::new_from_slice(icc).map_err(|err| format!("invalid ICC profile: {err}"))?;
+    if profile.color_space != DataColorSpace::Cmyk {
+        return Err(format!(
+            "ICC profile is for {:?}, not CMYK",
+            profile.color_space
+        ));
+    }
+    let transform = ColorProfile::new_srgb()
+        .create_transform_8bit(
+            Layout::Rgb,
+            &profile,
+            Layout::Rgba,
+            TransformOptions::default(),
+        )
+        .map_err(|err| format!("cannot convert to the ICC profile: {err}"))?;
+    let mut cmyk = vec![0; image.as_raw().len() / 3 * 4];
+    transform
+        .transform(image.as_raw(), &mut cmyk)
+        .map_err(|err| format!("cannot convert to the ICC profile: {err}"))?;
+    Ok(cmyk)
+}
+
+/// Saves `image` as a CMYK TIFF at `path`. With `icc`, the colors go
+/// through that output profile, which is embedded in the file; without, by
+/// the plain formula with `black` generation.
+pub fn save(
+    path: &Path,
+    image: &image::RgbImage,
+    icc: Option<&[u8]>,
+    black: BlackGeneration,
+) -> Result<(), String> {
+    let cmyk = match icc {
+        Some(icc) => convert_profile(image, icc)?,
+        None => convert_plain(image, black),
+    };
+    // The encoder seeks back to patch offsets, so build the file in memory
+    let mut bytes = Cursor::new(Vec::new());
+    let mut encoder = TiffEncoder::new(&mut bytes).map_err(|err| err.to_string())?;
+    let mut tiff = encoder
+        .new_image::<CMYK8>(image.width(), image.height())
+        .map_err(|err| err.to_string())?;
+    if let Some(icc) = icc {
+        tiff.encoder()
+            .write_tag(Tag::IccProfile, icc)
+            .map_err(|err| err.to_string())?;
+    }
+    tiff.write_data(&cmyk).map_err(|err| err.to_string())?;
+    std::fs::write(path, bytes.into_inner()).map_err(|err| err.to_string())
+}
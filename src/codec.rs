@@ -0,0 +1,455 @@
+//! A lossy image codec keeping only a diagram's sites: the `.vor` format.
+//!
+//! A file is the magic bytes `VOR`, a version byte, a flags byte, then the
+//! width, height and site count as little-endian `u32`s. Each site follows as
+//! its x and y (`u16`s when both dimensions fit in 16 bits, `u32`s
+//! otherwise) and its red, green and blue bytes. When flag bit 0 is set, a
+//! little-endian `f32` power-diagram weight per site comes last. Decoding
+//! draws each pixel in the color of the site nearest it, less its weight.
+//!
+//! Sites are stored most important first, so like a progressive JPEG any
+//! prefix of them decodes to a coarser preview of the whole image.
+
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use crate::{
+    Args, Pipeline, build_diagram, drawing_options, exit_on_err, export, for_image, importance,
+    legibility, load_image, output, reject_options, side_output_options, timings, treemap,
+};
+
+type Point = (u32, u32, [u8; 3]);
+
+const MAGIC: &[u8; 3] = b"VOR";
+const VERSION: u8 = 1;
+const HAS_WEIGHTS: u8 = 1;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct EncodeArgs {
+    /// The usual options; OUTPUT is the .vor file to write
+    #[command(flatten)]
+    pub args: Args,
+
+    /// Rounds of fitting a power-diagram weight to each site so the decoded
+    /// cells keep the areas the color-aware scoring gave them; costs a full
+    /// pass over the image per round, and 0 stores no weights
+    #[arg(long, default_value_t = 0)]
+    fit_weights: u32,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct DecodeArgs {
+    /// File written by `encode`
+    input: PathBuf,
+
+    /// Output file path; an .svg extension writes vector cells
+    output: PathBuf,
+
+    /// Decode this many times larger than the image was encoded at
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=64))]
+    scale: u32,
+
+    /// Refuse decoded sizes with more pixels than this
+    #[arg(long, default_value_t = crate::DEFAULT_MAX_PIXELS, value_parser = clap::value_parser!(u64).range(1..))]
+    max_pixels: u64,
+
+    /// Decode only the first this many sites, a preview of the full image
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    sites: Option<u64>,
+
+    /// Also write progressive previews into this directory, decoded from
+    /// the first 1, 2, 4 and so on sites
+    #[arg(long)]
+    previews: Option<PathBuf>,
+
+    /// Overwrite the output if it exists
+    #[arg(short, long)]
+    force: bool,
+}
+
+/// The contents of a `.vor` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Encoded {
+    pub width: u32,
+    pub height: u32,
+    pub sites: Vec<Point>,
+    /// Power-diagram weight of each site in square pixels, if fitted
+    pub weights: Option<Vec<f32>>,
+}
+
+impl Encoded {
+    /// Whether coordinates are stored in 16 bits rather than 32.
+    fn narrow(&self) -> bool {
+        self.width <= 1 << 16 && self.height <= 1 << 16
+    }
+
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(if self.weights.is_some() {
+            HAS_WEIGHTS
+        } else {
+            0
+        });
+        let count = u32::try_from(self.sites.len()).unwrap_or(u32::MAX);
+        for value in [self.width, self.height, count] {
+            bytes.extend(value.to_le_bytes());
+        }
+        for &(x, y, color) in &self.sites {
+            if self.narrow() {
+                #[allow(clippy::cast_possible_truncation)]
+                bytes.extend([x as u16, y as u16].iter().flat_map(|c| c.to_le_bytes()));
+            } else {
+                bytes.extend([x, y].iter().flat_map(|c| c.to_le_bytes()));
+            }
+            bytes.extend(color);
+        }
+        for weight in self.weights.iter().flatten() {
+            bytes.extend(weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut rest = bytes;
+        let mut take = |n: usize| {
+            if rest.len() < n {
+                return Err("file is truncated".to_string());
+            }
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+        if take(3)? != MAGIC {
+            return Err("not a .vor file".to_string());
+        }
+        let version = take(1)?[0];
+        if version != VERSION {
+            return Err(format!("unsupported .vor version {version}"));
+        }
+        let flags = take(1)?[0];
+        let mut word = || take(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        let (width, height, count) = (word()?, word()?, word()?);
+        if width == 0 || height == 0 || count == 0 {
+            return Err(format!(
+                "empty diagram: {width}x{height} with {count} sites"
+            ));
+        }
+        let mut encoded = Self {
+            width,
+            height,
+            sites: Vec::new(),
+            weights: None,
+        };
+        let narrow = encoded.narrow();
+        for _ in 0..count {
+            let (x, y) = if narrow {
+                let b = take(4)?;
+                (
+                    u32::from(u16::from_le_bytes([b[0], b[1]])),
+                    u32::from(u16::from_le_bytes([b[2], b[3]])),
+                )
+            } else {
+                let b = take(8)?;
+                (
+                    u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                    u32::from_le_bytes([b[4], b[5], b[6], b[7]]),
+                )
+            };
+            if x >= width || y >= height {
+                return Err(format!(
+                    "site ({x}, {y}) is outside the {width}x{height} image"
+                ));
+            }
+            let b = take(3)?;
+            encoded.sites.push((x, y, [b[0], b[1], b[2]]));
+        }
+        if flags & HAS_WEIGHTS != 0 {
+            let weights = (0..count)
+                .map(|_| take(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+                .collect::<Result<_, _>>()?;
+            encoded.weights = Some(weights);
+        }
+        Ok(encoded)
+    }
+
+    /// The first `count` sites alone.
+    #[must_use]
+    pub fn truncated(&self, count: usize) -> Self {
+        let count = count.min(self.sites.len());
+        Self {
+            sites: self.sites[..count].to_vec(),
+            weights: self
+                .weights
+                .as_ref()
+                .map(|weights| weights[..count].to_vec()),
+            ..*self
+        }
+    }
+
+    /// Reorders the sites by how much each one's color adds to the decoded
+    /// picture of `img`, so any prefix of them is close to the best preview
+    /// of its size.
+    fn order_by_importance(&mut self, img: &image::RgbImage) {
+        let colors: Vec<[u8; 3]> = self.sites.iter().map(|&(_, _, color)| color).collect();
+        let order = importance::rank(img, &self.positions(1), &colors).order;
+        self.sites = order.iter().map(|&i| self.sites[i]).collect();
+        if let Some(weights) = &mut self.weights {
+            *weights = order.iter().map(|&i| weights[i]).collect();
+        }
+    }
+
+    /// Site positions at their pixel centres, scaled up `scale` times.
+    fn positions(&self, scale: u32) -> Vec<(f64, f64)> {
+        self.sites
+            .iter()
+            .map(|&(x, y, _)| {
+                (
+                    (f64::from(x) + 0.5) * f64::from(scale),
+                    (f64::from(y) + 0.5) * f64::from(scale),
+                )
+            })
+            .collect()
+    }
+
+    /// The label of every pixel of the decoded image, `scale` times larger.
+    #[must_use]
+    pub fn labels(&self, scale: u32) -> Vec<u32> {
+        let area = f64::from(scale * scale);
+        let weights: Vec<f64> = match &self.weights {
+            Some(weights) => weights.iter().map(|&w| f64::from(w) * area).collect(),
+            None => vec![0.0; self.sites.len()],
+        };
+        treemap::power_labels(
+            self.width * scale,
+            self.height * scale,
+            &self.positions(scale),
+            &weights,
+        )
+    }
+}
+
+/// Power-diagram weights for `sites` whose cells come as close as `rounds`
+/// of adjustment get them to the pixel counts of the cells in `labels`.
+fn fit_weights(encoded: &Encoded, labels: &[u32], rounds: u32) -> Vec<f32> {
+    let mut targets = vec![0.0; encoded.sites.len()];
+    for &label in labels {
+        targets[label as usize] += 1.0;
+    }
+    let positions = encoded.positions(1);
+    let mut weights = vec![0.0; encoded.sites.len()];
+    for i in 0..rounds {
+        eprint!("\rFitting weights... {i} / {rounds}");
+        let labels = treemap::power_labels(encoded.width, encoded.height, &positions, &weights);
+        let mut counts = vec![0.0; weights.len()];
+        for label in labels {
+            counts[label as usize] += 1.0;
+        }
+        treemap::adjust_weights(&positions, &mut weights, &counts, &targets);
+    }
+    eprintln!("\rFitting weights... {rounds} / {rounds}");
+    #[allow(clippy::cast_possible_truncation)]
+    weights.into_iter().map(|w| w as f32).collect()
+}
+
+/// Exits if `args` asks for anything a `.vor` file cannot hold: it keeps
+/// one diagram's sites and cell colors over the whole image, and nothing
+/// drawn on or beside them.
+fn reject_unstored_options(args: &Args) {
+    let mut options = vec![
+        ("--like", args.like.is_some()),
+        ("--pipeline", args.pipeline != Pipeline::Rgb),
+        ("--ensemble", args.ensemble.is_some()),
+        ("--passes", args.passes > 1),
+        ("--viewport", args.viewport.is_some()),
+        ("--shard", args.shard.is_some()),
+        (
+            "--text-safety exclude",
+            args.text_safety == Some(legibility::TextSafety::Exclude),
+        ),
+    ];
+    options.extend(drawing_options(args));
+    options.extend(side_output_options(args));
+    reject_options("encode", &options);
+}
+
+pub fn run_encode(encode: &EncodeArgs) {
+    reject_unstored_options(&encode.args);
+    let mut args = encode.args.clone();
+    exit_on_err(
+        output::check_clobber(&args.output, args.force),
+        "write output",
+    );
+    args.seed.get_or_insert_with(|| rand::rng().random());
+    let mut timings = timings::Timings::default();
+    let img = load_image(&args.input, &args);
+    let args = for_image(&args, &img);
+    let (voronoi, points) = build_diagram(&img, &args, &mut timings);
+
+    let mut encoded = Encoded {
+        width: img.width(),
+        height: img.height(),
+        sites: points
+            .iter()
+            .zip(&voronoi.colors)
+            .map(|(&(x, y, _), &color)| (x, y, color))
+            .collect(),
+        weights: None,
+    };
+    if encode.fit_weights > 0 {
+        encoded.weights = Some(fit_weights(&encoded, &voronoi.labels, encode.fit_weights));
+    }
+    encoded.order_by_importance(&img);
+    let bytes = encoded.to_bytes();
+    exit_on_err(
+        output::save(&args.output, args.force, |path| {
+            std::fs::write(path, &bytes)
+        }),
+        "save encoded diagram",
+    );
+    let raw = f64::from(img.width()) * f64::from(img.height()) * 3.0;
+    #[allow(clippy::cast_precision_loss)]
+    let ratio = raw / bytes.len() as f64;
+    eprintln!(
+        "Saved {} sites in {} bytes ({ratio:.0}:1 against raw RGB) to {}",
+        encoded.sites.len(),
+        bytes.len(),
+        args.output.display()
+    );
+    if args.timings {
+        timings.print();
+    }
+}
+
+/// Draws `encoded` `scale` times larger and saves it to `path`, as vector
+/// cells if it has an .svg extension.
+fn save_decoded(encoded: &Encoded, scale: u32, path: &Path, force: bool) -> Result<(), String> {
+    let (width, height) = (encoded.width * scale, encoded.height * scale);
+    let labels = encoded.labels(scale);
+    let colors: Vec<[u8; 3]> = encoded.sites.iter().map(|&(_, _, color)| color).collect();
+    let is_svg = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    output::save(path, force, |path| {
+        if is_svg {
+            std::fs::write(path, export::svg(&labels, &colors, width, height))
+                .map_err(image::ImageError::IoError)
+        } else {
+            image::RgbImage::from_fn(width, height, |x, y| {
+                image::Rgb(colors[labels[(y * width + x) as usize] as usize])
+            })
+            .save(path)
+        }
+    })
+}
+
+pub fn run_decode(decode: &DecodeArgs) {
+    exit_on_err(
+        output::check_clobber(&decode.output, decode.force),
+        "write output",
+    );
+    let bytes = exit_on_err(std::fs::read(&decode.input), "read encoded diagram");
+    let mut encoded = exit_on_err(Encoded::from_bytes(&bytes), "decode diagram");
+    let (width, height) = exit_on_err(
+        crate::scaled_size(
+            encoded.width,
+            encoded.height,
+            decode.scale,
+            decode.max_pixels,
+        ),
+        "check decoded size",
+    );
+    let total = encoded.sites.len();
+    if let Some(sites) = decode.sites {
+        encoded = encoded.truncated(usize::try_from(sites).unwrap_or(usize::MAX));
+    }
+    println!(
+        "Sites: {} of {total}, decoding at {width}x{height}",
+        encoded.sites.len()
+    );
+
+    if let Some(dir) = &decode.previews {
+        exit_on_err(std::fs::create_dir_all(dir), "create preview directory");
+        let counts = std::iter::successors(Some(1_usize), |&count| Some(count * 2))
+            .take_while(|&count| count < encoded.sites.len())
+            .chain(std::iter::once(encoded.sites.len()));
+        for count in counts {
+            let path = dir.join(format!("preview_{count:05}.png"));
+            let result = save_decoded(&encoded.truncated(count), decode.scale, &path, decode.force);
+            exit_on_err(result, "save preview");
+            eprintln!("Saved preview of {count} sites to {}", path.display());
+        }
+    }
+
+    let result = save_decoded(&encoded, decode.scale, &decode.output, decode.force);
+    exit_on_err(result, "save diagram");
+    eprintln!("Saved decoded diagram to {}", decode.output.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(width: u32, height: u32, weights: bool) -> Encoded {
+        let sites: Vec<Point> = (0..20)
+            .map(|i: u8| {
+                let (x, y) = (u32::from(i) * 37 % width, u32::from(i) * 91 % height);
+                (x, y, [i, i * 7, 255 - i])
+            })
+            .collect();
+        Encoded {
+            width,
+            height,
+            weights: weights.then(|| (0..20_u8).map(|i| f32::from(i) * -1.5).collect()),
+            sites,
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        // Small and huge images store positions in 16 and 32 bits
+        for original in [
+            encoded(160, 120, false),
+            encoded(160, 120, true),
+            encoded(100_000, 3, true),
+        ] {
+            let bytes = original.to_bytes();
+            assert_eq!(Encoded::from_bytes(&bytes), Ok(original));
+        }
+    }
+
+    #[test]
+    fn damaged_files_are_refused() {
+        let bytes = encoded(160, 120, true).to_bytes();
+        assert!(Encoded::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Encoded::from_bytes(&bytes[1..]).is_err());
+        let mut empty = bytes.clone();
+        empty[5..9].copy_from_slice(&0_u32.to_le_bytes());
+        assert!(Encoded::from_bytes(&empty).is_err());
+        let mut outside = encoded(160, 120, false);
+        outside.sites[3].0 = 160;
+        assert!(Encoded::from_bytes(&outside.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn unweighted_sites_decode_to_their_nearest_pixels() {
+        let encoded = encoded(40, 30, false);
+        let labels = encoded.labels(1);
+        for (y, x) in [(0, 0), (29, 39), (12, 17)] {
+            let nearest = (0..encoded.sites.len())
+                .min_by_key(|&i| {
+                    let (sx, sy, _) = encoded.sites[i];
+                    sx.abs_diff(x).pow(2) + sy.abs_diff(y).pow(2)
+                })
+                .unwrap();
+            let (sx, sy, _) = encoded.sites[labels[(y * 40 + x) as usize] as usize];
+            let (nx, ny, _) = encoded.sites[nearest];
+            assert_eq!(
+                sx.abs_diff(x).pow(2) + sy.abs_diff(y).pow(2),
+                nx.abs_diff(x).pow(2) + ny.abs_diff(y).pow(2)
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Color helpers shared by the rendering stages.
+
+use std::str::FromStr;
+
+/// Rounds and clamps a channel value into the `u8` range.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn to_u8(value: f64) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// Relative luminance (Rec. 709 coefficients) of an sRGB color, in `0..=255`.
+#[must_use]
+pub fn luminance(color: [u8; 3]) -> f64 {
+    let [r, g, b] = color.map(f64::from);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Euclidean distance between two colors in RGB space.
+#[must_use]
+pub fn distance(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let [r, g, b] = [0, 1, 2].map(|c| f64::from(a[c]) - f64::from(b[c]));
+    (r * r + g * g + b * b).sqrt()
+}
+
+/// Shifts `color` so that its luminance lands on the nearest of `levels`
+/// evenly spaced tonal steps.
+#[must_use]
+pub fn posterize(color: [u8; 3], levels: u8) -> [u8; 3] {
+    let lum = luminance(color);
+    let step = 255.0 / f64::from(levels.max(2) - 1);
+    let target = (lum / step).round() * step;
+    color.map(|c| to_u8(f64::from(c) + target - lum))
+}
+
+/// Parses a `#rrggbb` (or `rrggbb`) hex color.
+///
+/// # Errors
+///
+/// Returns an error message if `s` is not a six-digit hex color.
+pub fn parse_hex(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("invalid color '{s}' (expected #rrggbb)"));
+    }
+    let mut color = [0; 3];
+    for (c, channel) in color.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[c * 2..c * 2 + 2], 16)
+            .map_err(|_| format!("invalid color '{s}' (expected #rrggbb)"))?;
+    }
+    Ok(color)
+}
+
+/// Alpha-blends `over` onto `base` with opacity `alpha` in `0..=1`.
+#[must_use]
+pub fn blend(base: [u8; 3], over: [u8; 3], alpha: f64) -> [u8; 3] {
+    std::array::from_fn(|c| to_u8(f64::from(base[c]) * (1.0 - alpha) + f64::from(over[c]) * alpha))
+}
+
+/// Converts a color from HSV, with all components in `0..=1`.
+#[must_use]
+pub fn from_hsv(hue: f64, saturation: f64, value: f64) -> [u8; 3] {
+    let channel = |n: f64| {
+        let k = (n + hue.rem_euclid(1.0) * 6.0) % 6.0;
+        value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    [channel(5.0), channel(3.0), channel(1.0)].map(|c| to_u8(c * 255.0))
+}
+
+/// Color at `t` in `0..=1` along evenly spaced `stops`, interpolating linearly.
+#[must_use]
+pub fn ramp_at(stops: &[[u8; 3]], t: f64) -> [u8; 3] {
+    match stops {
+        [] => [0; 3],
+        [only] => *only,
+        _ => {
+            #[allow(clippy::cast_precision_loss)]
+            let pos = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let idx = (pos.floor() as usize).min(stops.len() - 2);
+            #[allow(clippy::cast_precision_loss)]
+            let frac = pos - idx as f64;
+            blend(stops[idx], stops[idx + 1], frac)
+        }
+    }
+}
+
+/// A gradient through two or more colors, from dark to light.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ramp(pub Vec<[u8; 3]>);
+
+impl Ramp {
+    /// Maps a color's luminance onto the ramp.
+    #[must_use]
+    pub fn recolor(&self, color: [u8; 3]) -> [u8; 3] {
+        ramp_at(&self.0, luminance(color) / 255.0)
+    }
+}
+
+impl FromStr for Ramp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stops = s
+            .split(',')
+            .map(|stop| parse_hex(stop.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if stops.len() < 2 {
+            return Err(format!("ramp '{s}' needs at least two colors"));
+        }
+        Ok(Self(stops))
+    }
+}
@@ -0,0 +1,287 @@
+//! Schemes for choosing the base color of each cell.
+
+use std::str::FromStr;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::cells::CellStats;
+use crate::color::{from_hsv, ramp_at};
+
+/// Samples of the viridis colormap at even steps.
+const VIRIDIS: [[u8; 3]; 9] = [
+    [0x44, 0x01, 0x54],
+    [0x47, 0x2d, 0x7b],
+    [0x3b, 0x52, 0x8b],
+    [0x2c, 0x72, 0x8e],
+    [0x21, 0x91, 0x8c],
+    [0x28, 0xae, 0x80],
+    [0x5e, 0xc9, 0x62],
+    [0xad, 0xdc, 0x30],
+    [0xfd, 0xe7, 0x25],
+];
+
+/// Samples of the magma colormap at even steps.
+const MAGMA: [[u8; 3]; 9] = [
+    [0x00, 0x00, 0x04],
+    [0x1c, 0x10, 0x44],
+    [0x4f, 0x12, 0x7b],
+    [0x81, 0x25, 0x81],
+    [0xb5, 0x36, 0x7a],
+    [0xe5, 0x50, 0x64],
+    [0xfb, 0x87, 0x61],
+    [0xfe, 0xc2, 0x87],
+    [0xfc, 0xfd, 0xbf],
+];
+
+/// The Okabe–Ito palette, distinguishable under common color vision deficiencies.
+const OKABE_ITO: [[u8; 3]; 8] = [
+    [0xe6, 0x9f, 0x00],
+    [0x56, 0xb4, 0xe9],
+    [0x00, 0x9e, 0x73],
+    [0xf0, 0xe4, 0x42],
+    [0x00, 0x72, 0xb2],
+    [0xd5, 0x5e, 0x00],
+    [0xcc, 0x79, 0xa7],
+    [0x00, 0x00, 0x00],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellColor {
+    /// The color of the pixel each point was sampled from
+    Seed,
+    /// The average color of the cell's source pixels
+    Average,
+    /// Distinct random hues, ignoring the image
+    Random { seed: u64 },
+    /// Colorblind-safe colors, with neighbouring cells always differing
+    RandomSafe { seed: u64 },
+    /// A geometric property of each cell, through a colormap
+    Map {
+        property: CellProperty,
+        colormap: Colormap,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellProperty {
+    /// Number of pixels in the cell
+    Area,
+    /// Elongation of the cell
+    Eccentricity,
+    /// Closeness of the cell's centroid to the image center
+    Centrality,
+}
+
+impl FromStr for CellProperty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "area" => Ok(Self::Area),
+            "eccentricity" => Ok(Self::Eccentricity),
+            "centrality" => Ok(Self::Centrality),
+            _ => Err(format!(
+                "unknown cell property '{s}' (expected area, eccentricity or centrality)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    Viridis,
+    Magma,
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viridis" => Ok(Self::Viridis),
+            "magma" => Ok(Self::Magma),
+            _ => Err(format!(
+                "unknown colormap '{s}' (expected viridis or magma)"
+            )),
+        }
+    }
+}
+
+impl Colormap {
+    /// Color at `t` in `0..=1`, interpolating linearly between samples.
+    #[must_use]
+    pub fn at(self, t: f64) -> [u8; 3] {
+        match self {
+            Self::Viridis => ramp_at(&VIRIDIS, t),
+            Self::Magma => ramp_at(&MAGMA, t),
+        }
+    }
+}
+
+impl FromStr for CellColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let seed = || {
+            arg.map_or(Ok(0), |arg| {
+                arg.parse()
+                    .map_err(|_| format!("invalid seed '{arg}' in cell color '{s}'"))
+            })
+        };
+        match name {
+            "seed" if arg.is_none() => Ok(Self::Seed),
+            "average" if arg.is_none() => Ok(Self::Average),
+            "random" => Ok(Self::Random { seed: seed()? }),
+            "random-safe" => Ok(Self::RandomSafe { seed: seed()? }),
+            "map" => {
+                let arg = arg.ok_or_else(|| format!("missing property in cell color '{s}'"))?;
+                let (property, colormap) = match arg.split_once(':') {
+                    Some((property, colormap)) => (property, colormap.parse()?),
+                    None => (arg, Colormap::Viridis),
+                };
+                Ok(Self::Map {
+                    property: property.parse()?,
+                    colormap,
+                })
+            }
+            _ => Err(format!(
+                "unknown cell color '{s}' (expected seed, average, random[:seed], \
+                 random-safe[:seed] or map:<property>[:<colormap>])"
+            )),
+        }
+    }
+}
+
+/// `cells` distinct hues spaced by the golden angle, in shuffled order.
+#[must_use]
+pub fn random_colors(cells: usize, rng: &mut impl Rng) -> Vec<[u8; 3]> {
+    const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+    let mut colors: Vec<[u8; 3]> = (0..cells)
+        .map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let i = i as f64;
+            // Cycle lightness and saturation too, so hues that come around
+            // again stay tellable apart
+            let band = (i * GOLDEN_RATIO_CONJUGATE).floor() % 3.0;
+            from_hsv(
+                i * GOLDEN_RATIO_CONJUGATE,
+                0.85 - band * 0.2,
+                0.95 - band * 0.15,
+            )
+        })
+        .collect();
+    colors.shuffle(rng);
+    colors
+}
+
+/// Greedily colors the adjacency graph with the Okabe–Ito palette so that
+/// neighbouring cells differ wherever the palette allows.
+#[must_use]
+pub fn safe_colors(neighbours: &[Vec<u32>], rng: &mut impl Rng) -> Vec<[u8; 3]> {
+    let mut order: Vec<usize> = (0..neighbours.len()).collect();
+    order.shuffle(rng);
+    // Most-constrained cells first
+    order.sort_by_key(|&cell| std::cmp::Reverse(neighbours[cell].len()));
+    let mut palette_order: Vec<usize> = (0..OKABE_ITO.len()).collect();
+    let mut assigned: Vec<Option<usize>> = vec![None; neighbours.len()];
+    for cell in order {
+        palette_order.shuffle(rng);
+        let taken = |entry: &usize| {
+            neighbours[cell]
+                .iter()
+                .any(|&n| assigned[n as usize] == Some(*entry))
+        };
+        let entry = palette_order
+            .iter()
+            .copied()
+            .find(|entry| !taken(entry))
+            .unwrap_or(palette_order[0]);
+        assigned[cell] = Some(entry);
+    }
+    assigned
+        .into_iter()
+        .map(|entry| OKABE_ITO[entry.unwrap_or(0)])
+        .collect()
+}
+
+/// Colors cells by `property`, normalized over all cells and mapped through
+/// `colormap`. Empty cells take the low end of the map.
+#[must_use]
+pub fn property_colors(
+    stats: &[CellStats],
+    property: CellProperty,
+    colormap: Colormap,
+    width: u32,
+    height: u32,
+) -> Vec<[u8; 3]> {
+    let (cx, cy) = (f64::from(width) / 2.0, f64::from(height) / 2.0);
+    let max_dist = cx.hypot(cy);
+    let values: Vec<Option<f64>> = stats
+        .iter()
+        .map(|stats| match property {
+            #[allow(clippy::cast_precision_loss)]
+            CellProperty::Area => (stats.count > 0).then_some(stats.count as f64),
+            CellProperty::Eccentricity => stats.eccentricity(),
+            CellProperty::Centrality => stats
+                .centroid()
+                .map(|(x, y)| 1.0 - (x - cx).hypot(y - cy) / max_dist),
+        })
+        .collect();
+    let (min, max) = values
+        .iter()
+        .flatten()
+        .fold((f64::MAX, f64::MIN), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+    let range = (max - min).max(f64::EPSILON);
+    values
+        .into_iter()
+        .map(|value| colormap.at(value.map_or(0.0, |v| (v - min) / range)))
+        .collect()
+}
+
+/// Remaps each channel of the cell colors so that the rendered image, with
+/// every cell weighted by its pixel count, has the same histogram as `img`.
+pub fn match_histogram(colors: &mut [[u8; 3]], stats: &[CellStats], img: &image::RgbImage) {
+    for c in 0..3 {
+        let mut source = [0u64; 256];
+        for pixel in img.pixels() {
+            source[usize::from(pixel.0[c])] += 1;
+        }
+        let mut output = [0u64; 256];
+        for (color, stats) in colors.iter().zip(stats) {
+            output[usize::from(color[c])] += stats.count;
+        }
+        let source_cdf = cumulative(&source);
+        let output_cdf = cumulative(&output);
+        let mut mapping = [0u8; 256];
+        let mut level = 0;
+        for (value, target) in mapping.iter_mut().zip(output_cdf) {
+            while level < 255 && source_cdf[level] < target {
+                level += 1;
+            }
+            *value = u8::try_from(level).unwrap_or(u8::MAX);
+        }
+        for color in colors.iter_mut() {
+            color[c] = mapping[usize::from(color[c])];
+        }
+    }
+}
+
+/// Normalized cumulative distribution of a histogram.
+fn cumulative(histogram: &[u64; 256]) -> [f64; 256] {
+    #[allow(clippy::cast_precision_loss)]
+    let total = histogram.iter().sum::<u64>().max(1) as f64;
+    let mut running = 0;
+    histogram.map(|count| {
+        running += count;
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = running as f64 / total;
+        fraction
+    })
+}
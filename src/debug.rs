@@ -0,0 +1,132 @@
+//! The intermediate maps of a render as captioned images, for `--debug-dir`.
+
+use std::path::{Path, PathBuf};
+
+use crate::plane::ImagePlane;
+use crate::{Args, Diagram, exit_on_err, font, metric, output};
+
+type Point = (u32, u32, [u8; 3]);
+
+/// Files written to the debug directory, in pipeline order.
+const FILES: [&str; 5] = [
+    "1-density.png",
+    "2-gradient.png",
+    "3-blurred.png",
+    "4-sites.png",
+    "5-margin.png",
+];
+
+/// Scale of caption glyphs, and the padding around them.
+const CAPTION_SCALE: u32 = 2;
+const CAPTION_PADDING: u32 = 4;
+
+/// Every path `--debug-dir` may write into `dir`.
+pub fn paths(dir: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    FILES.iter().map(|file| dir.join(file))
+}
+
+/// `img` below a black strip reading `caption`.
+fn captioned(img: &image::RgbImage, caption: &str) -> image::RgbImage {
+    let strip = font::GLYPH_HEIGHT * CAPTION_SCALE + 2 * CAPTION_PADDING;
+    let width = img
+        .width()
+        .max(font::text_width(caption, CAPTION_SCALE) + 2 * CAPTION_PADDING);
+    let mut out = image::RgbImage::new(width, img.height() + strip);
+    image::imageops::replace(&mut out, img, 0, i64::from(strip));
+    let padding = i64::from(CAPTION_PADDING);
+    font::draw_text(&mut out, padding, padding, caption, CAPTION_SCALE, [255; 3]);
+    out
+}
+
+/// `values` as a grayscale image scaled so the largest is white.
+fn heatmap(values: &[f64], width: u32, height: u32) -> image::RgbImage {
+    let max = values.iter().copied().fold(f64::MIN_POSITIVE, f64::max);
+    image::RgbImage::from_fn(width, height, |x, y| {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let level = (values[(y * width + x) as usize] / max * 255.0).round() as u8;
+        image::Rgb([level; 3])
+    })
+}
+
+/// Draws a square of side `2 * radius + 1` around `(x, y)`, clipped.
+fn mark(img: &mut image::RgbImage, (x, y): (u32, u32), radius: u32, color: [u8; 3]) {
+    let (width, height) = img.dimensions();
+    for my in y.saturating_sub(radius)..=(y + radius).min(height - 1) {
+        for mx in x.saturating_sub(radius)..=(x + radius).min(width - 1) {
+            img.put_pixel(mx, my, image::Rgb(color));
+        }
+    }
+}
+
+/// The image darkened, with a line from each initial site to where
+/// relaxation moved it, initial sites in red and relaxed ones in green.
+fn sites(img: &image::RgbImage, initial: &[Point], relaxed: &[Point]) -> image::RgbImage {
+    let mut out = img.clone();
+    for pixel in out.pixels_mut() {
+        pixel.0 = pixel.0.map(|c| c / 3);
+    }
+    if initial.len() == relaxed.len() {
+        for (&(x0, y0, _), &(x1, y1, _)) in initial.iter().zip(relaxed) {
+            let steps = x0.abs_diff(x1).max(y0.abs_diff(y1));
+            for step in 0..=steps {
+                let t = f64::from(step) / f64::from(steps.max(1));
+                let along = |a: u32, b: u32| f64::from(a) + (f64::from(b) - f64::from(a)) * t;
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let (x, y) = (along(x0, x1).round() as u32, along(y0, y1).round() as u32);
+                out.put_pixel(x, y, image::Rgb([160; 3]));
+            }
+        }
+    }
+    for &(x, y, _) in initial {
+        mark(&mut out, (x, y), 1, [255, 64, 64]);
+    }
+    for &(x, y, _) in relaxed {
+        mark(&mut out, (x, y), 1, [64, 255, 64]);
+    }
+    out
+}
+
+/// Writes the sampling density, gradient magnitude, blurred input, initial
+/// and relaxed sites, and score margin of a render into `dir`. The margin
+/// map is skipped when the diagram has none, as with `--levels`.
+pub fn write(
+    dir: &Path,
+    img: &ImagePlane,
+    initial: &[Point],
+    relaxed: &[Point],
+    voronoi: &Diagram,
+    args: &Args,
+) {
+    exit_on_err(std::fs::create_dir_all(dir), "create debug directory");
+    let (width, height) = img.dimensions();
+    let metric = metric::Metric::new(args.projection, width, height);
+    let density: Vec<f64> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| crate::selection_weight(args, &metric, x, y, width, height))
+        .collect();
+    let mut images = vec![
+        captioned(&heatmap(&density, width, height), "sampling density"),
+        captioned(
+            &heatmap(img.gradient_magnitudes(), width, height),
+            "gradient",
+        ),
+        captioned(img.blurred(), &format!("blur {}", args.blur)),
+        captioned(
+            &sites(img, initial, relaxed),
+            &format!("sites - relaxed {}", args.relax),
+        ),
+    ];
+    if let Some(confidence) = &voronoi.confidence {
+        let (width, height) = voronoi.image.dimensions();
+        let margins: Vec<f64> = confidence.iter().map(|&m| f64::from(m)).collect();
+        images.push(captioned(&heatmap(&margins, width, height), "score margin"));
+    }
+    for (image, file) in images.iter().zip(FILES) {
+        let path = dir.join(file);
+        exit_on_err(
+            output::save(&path, args.force, |path| image.save(path)),
+            "save debug image",
+        );
+    }
+    eprintln!("Saved {} debug images to {}", images.len(), dir.display());
+}
@@ -0,0 +1,190 @@
+//! Euclidean distance transforms over label maps.
+
+/// Squared distance standing in for "no feature"; finite so the envelope
+/// intersections stay well-defined.
+const FAR: f64 = 1e20;
+
+/// Scratch buffers for [`transform_line`], sized for the longest line.
+struct LineScratch {
+    /// Locations of the parabolas in the lower envelope
+    vertices: Vec<usize>,
+    /// Boundaries between consecutive parabolas
+    bounds: Vec<f64>,
+    output: Vec<f64>,
+}
+
+impl LineScratch {
+    fn new(len: usize) -> Self {
+        Self {
+            vertices: vec![0; len],
+            bounds: vec![0.0; len + 1],
+            output: vec![0.0; len],
+        }
+    }
+}
+
+/// Exact squared distance transform of a sampled function along one line
+/// (Felzenszwalb & Huttenlocher), written back into `line`.
+#[allow(clippy::cast_precision_loss)]
+fn transform_line(line: &mut [f64], scratch: &mut LineScratch) {
+    let len = line.len();
+    if len == 0 {
+        return;
+    }
+    let LineScratch {
+        vertices,
+        bounds,
+        output,
+    } = scratch;
+    let sq = |q: usize| (q * q) as f64;
+    let mut top = 0;
+    vertices[0] = 0;
+    bounds[0] = f64::NEG_INFINITY;
+    bounds[1] = f64::INFINITY;
+    for q in 1..len {
+        loop {
+            let vertex = vertices[top];
+            let intersection =
+                ((line[q] + sq(q)) - (line[vertex] + sq(vertex))) / (2.0 * (q - vertex) as f64);
+            if intersection <= bounds[top] && top > 0 {
+                top -= 1;
+            } else {
+                top += 1;
+                vertices[top] = q;
+                bounds[top] = intersection;
+                bounds[top + 1] = f64::INFINITY;
+                break;
+            }
+        }
+    }
+    top = 0;
+    for (q, out) in output.iter_mut().enumerate().take(len) {
+        while bounds[top + 1] < q as f64 {
+            top += 1;
+        }
+        let vertex = vertices[top];
+        *out = sq(q.abs_diff(vertex)) + line[vertex];
+    }
+    line.copy_from_slice(&output[..len]);
+}
+
+/// Euclidean distance from every pixel to the nearest pixel where `is_feature`
+/// holds, in row-major order.
+#[must_use]
+pub fn distance_transform(
+    width: u32,
+    height: u32,
+    is_feature: impl Fn(u32, u32) -> bool,
+) -> Vec<f32> {
+    let (w, h) = (width as usize, height as usize);
+    let mut grid: Vec<f64> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| if is_feature(x, y) { 0.0 } else { FAR })
+        .collect();
+    let mut scratch = LineScratch::new(w.max(h));
+    let mut column = vec![0.0; h];
+    for x in 0..w {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = grid[y * w + x];
+        }
+        transform_line(&mut column, &mut scratch);
+        for (y, &value) in column.iter().enumerate() {
+            grid[y * w + x] = value;
+        }
+    }
+    for row in grid.chunks_mut(w.max(1)) {
+        transform_line(row, &mut scratch);
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    grid.into_iter().map(|sq| sq.sqrt() as f32).collect()
+}
+
+/// Whether the pixel at `(x, y)` lies on a cell boundary, i.e. its right or
+/// bottom neighbour belongs to another cell. Checking one side only keeps
+/// shared edges one pixel wide.
+#[must_use]
+pub fn is_boundary(labels: &[u32], width: u32, height: u32, x: u32, y: u32) -> bool {
+    let idx = (y * width + x) as usize;
+    (x + 1 < width && labels[idx + 1] != labels[idx])
+        || (y + 1 < height && labels[idx + width as usize] != labels[idx])
+}
+
+/// Distance from every pixel to the nearest cell boundary.
+#[must_use]
+pub fn boundary_distance(labels: &[u32], width: u32, height: u32) -> Vec<f32> {
+    distance_transform(width, height, |x, y| {
+        is_boundary(labels, width, height, x, y)
+    })
+}
+
+/// Distance from every pixel to the nearest of `sites`.
+#[must_use]
+pub fn site_distance(sites: &[(u32, u32, [u8; 3])], width: u32, height: u32) -> Vec<f32> {
+    let mut is_site = vec![false; (width * height) as usize];
+    for &(x, y, _) in sites {
+        is_site[(y * width + x) as usize] = true;
+    }
+    distance_transform(width, height, |x, y| is_site[(y * width + x) as usize])
+}
+
+/// Difference between the distances from every pixel to its second-nearest
+/// and nearest of `sites`: zero along cell edges and rising towards the
+/// sites, the edge field of Worley noise. Sites sharing a position count once.
+#[must_use]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn edge_distance(sites: &[(u32, u32, [u8; 3])], width: u32, height: u32) -> Vec<f32> {
+    let mut positions: Vec<(u32, u32)> = sites.iter().map(|&(x, y, _)| (x, y)).collect();
+    positions.sort_unstable();
+    positions.dedup();
+    if positions.len() < 2 {
+        return vec![0.0; (width * height) as usize];
+    }
+    // Bucket the sites so each pixel only looks at those around it
+    let bucket =
+        ((f64::from(width) * f64::from(height) / positions.len() as f64).sqrt() as u32).max(1);
+    let (columns, rows) = (width.div_ceil(bucket), height.div_ceil(bucket));
+    let mut buckets = vec![Vec::new(); (columns * rows) as usize];
+    for &(x, y) in &positions {
+        buckets[(y / bucket * columns + x / bucket) as usize].push((x, y));
+    }
+    let mut field = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let (bx, by) = (i64::from(x / bucket), i64::from(y / bucket));
+            let (mut f1, mut f2) = (f64::INFINITY, f64::INFINITY);
+            for ring in 0.. {
+                for ny in by - ring..=by + ring {
+                    for nx in bx - ring..=bx + ring {
+                        let on_ring = (ny - by).abs() == ring || (nx - bx).abs() == ring;
+                        if !on_ring
+                            || nx < 0
+                            || ny < 0
+                            || nx >= i64::from(columns)
+                            || ny >= i64::from(rows)
+                        {
+                            continue;
+                        }
+                        for &(sx, sy) in &buckets[(ny as u32 * columns + nx as u32) as usize] {
+                            let d = f64::from(sx.abs_diff(x)).hypot(f64::from(sy.abs_diff(y)));
+                            if d < f1 {
+                                (f1, f2) = (d, f1);
+                            } else if d < f2 {
+                                f2 = d;
+                            }
+                        }
+                    }
+                }
+                // Sites beyond this ring are at least this far away
+                if f2 <= (ring * i64::from(bucket)) as f64 {
+                    break;
+                }
+            }
+            field.push((f2 - f1) as f32);
+        }
+    }
+    field
+}
@@ -0,0 +1,90 @@
+//! Image gradients, and snapping sites onto the edges they outline.
+
+use crate::color;
+use crate::plane::ImagePlane;
+
+type Point = (u32, u32, [u8; 3]);
+
+/// Pixels whose gradient is at least this fraction of the image's strongest
+/// count as edges worth snapping to.
+const STRONG_EDGE: f64 = 0.25;
+
+/// Sobel gradient `(gx, gy)` of the luminance at every pixel, row by row.
+/// Pixels past the border repeat the nearest one.
+#[must_use]
+pub fn gradients(img: &image::RgbImage) -> Vec<(f64, f64)> {
+    let (width, height) = img.dimensions();
+    let luma: Vec<f64> = img.pixels().map(|p| color::luminance(p.0)).collect();
+    let at = |x: i64, y: i64| {
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let (x, y) = (
+            x.clamp(0, i64::from(width) - 1) as usize,
+            y.clamp(0, i64::from(height) - 1) as usize,
+        );
+        luma[y * width as usize + x]
+    };
+    let mut gradients = Vec::with_capacity(luma.len());
+    for y in 0..i64::from(height) {
+        for x in 0..i64::from(width) {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            gradients.push((gx, gy));
+        }
+    }
+    gradients
+}
+
+/// Sobel gradient magnitude of the luminance at every pixel, row by row.
+#[must_use]
+pub fn gradient_map(img: &image::RgbImage) -> Vec<f64> {
+    gradients(img)
+        .into_iter()
+        .map(|(gx, gy)| gx.hypot(gy))
+        .collect()
+}
+
+/// Whether each pixel, row by row, lies on a strong edge.
+#[must_use]
+pub fn strong_edges(img: &ImagePlane) -> Vec<bool> {
+    let gradients = img.gradient_magnitudes();
+    let threshold = STRONG_EDGE * gradients.iter().copied().fold(0.0, f64::max);
+    gradients
+        .iter()
+        .map(|&gradient| threshold > 0.0 && gradient >= threshold)
+        .collect()
+}
+
+/// Moves each point to the nearest strong edge pixel within `radius`, taking
+/// the image color there. Points with no strong edge in reach stay put.
+#[must_use]
+pub fn snap(img: &ImagePlane, points: &[Point], radius: u32) -> Vec<Point> {
+    let (width, height) = img.dimensions();
+    let strong = strong_edges(img);
+    let reach = u64::from(radius) * u64::from(radius);
+    points
+        .iter()
+        .map(|&point| {
+            let (px, py, _) = point;
+            let xs = px.saturating_sub(radius)..=(px + radius).min(width - 1);
+            let nearest = (py.saturating_sub(radius)..=(py + radius).min(height - 1))
+                .flat_map(|y| xs.clone().map(move |x| (x, y)))
+                .filter(|&(x, y)| strong[(y * width + x) as usize])
+                .map(|(x, y)| {
+                    let (dx, dy) = (u64::from(x.abs_diff(px)), u64::from(y.abs_diff(py)));
+                    (dx * dx + dy * dy, x, y)
+                })
+                .filter(|&(dist, _, _)| dist <= reach)
+                .min();
+            match nearest {
+                Some((_, x, y)) => (x, y, img.get_pixel(x, y).0),
+                None => point,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,394 @@
+//! Hand edits to the sites of a diagram, applied one at a time from a file
+//! or as they are typed on standard input. There is no window of its own:
+//! `--interactive` saves the output after each edit for an image viewer to
+//! show.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::incremental::{Editor, Rect};
+use crate::project::{Project, load_project, project_args};
+use crate::{
+    Args, Pipeline, drawing_options, exit_on_err, export, for_image, load_image, output,
+    reject_options, sample_points_print_progress, seeded_rng, side_output_options,
+};
+
+type Point = (u32, u32, [u8; 3]);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct EditArgs {
+    #[command(flatten)]
+    pub args: Args,
+
+    /// File of edits to the sampled sites, one per line: "add X Y",
+    /// "move SITE X Y", "remove SITE", "drag X Y X2 Y2" (move the site
+    /// whose cell holds X Y) or "delete X Y", with sites numbered from 0
+    #[arg(long, required_unless_present = "interactive")]
+    pub edits: Option<PathBuf>,
+
+    /// Read edits from standard input as they are typed, saving the output
+    /// after each one so an image viewer watching it shows the changes live
+    #[arg(long, conflicts_with = "edits")]
+    pub interactive: bool,
+
+    /// Also write the edited sites and their colors to this JSON file, which
+    /// `rasterize --points` reads back
+    #[arg(long)]
+    pub export_points: Option<PathBuf>,
+
+    /// Project file (conventionally .voronoi) keeping this command line and
+    /// every edit; edits already in it are replayed first, and it is saved
+    /// again after each new edit, undo and redo
+    #[arg(long)]
+    pub session: Option<PathBuf>,
+}
+
+/// A change to the site set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Add(u32, u32),
+    Move(usize, u32, u32),
+    Remove(usize),
+    /// Moves the site whose cell holds the first position to the second
+    Drag(u32, u32, u32, u32),
+    /// Removes the site whose cell holds the position
+    Delete(u32, u32),
+}
+
+impl FromStr for Edit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let number = |word: &str| {
+            word.parse::<u32>()
+                .map_err(|err| format!("invalid number \"{word}\": {err}"))
+        };
+        let site = |word: &str| {
+            word.parse::<usize>()
+                .map_err(|err| format!("invalid site \"{word}\": {err}"))
+        };
+        match words.as_slice() {
+            ["add", x, y] => Ok(Self::Add(number(x)?, number(y)?)),
+            ["move", i, x, y] => Ok(Self::Move(site(i)?, number(x)?, number(y)?)),
+            ["remove", i] => Ok(Self::Remove(site(i)?)),
+            ["drag", x, y, to_x, to_y] => Ok(Self::Drag(
+                number(x)?,
+                number(y)?,
+                number(to_x)?,
+                number(to_y)?,
+            )),
+            ["delete", x, y] => Ok(Self::Delete(number(x)?, number(y)?)),
+            _ => Err(format!(
+                "expected \"add X Y\", \"move SITE X Y\", \"remove SITE\", \
+                 \"drag X Y X2 Y2\" or \"delete X Y\", got \"{s}\""
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Add(x, y) => write!(f, "add {x} {y}"),
+            Self::Move(site, x, y) => write!(f, "move {site} {x} {y}"),
+            Self::Remove(site) => write!(f, "remove {site}"),
+            Self::Drag(x, y, to_x, to_y) => write!(f, "drag {x} {y} {to_x} {to_y}"),
+            Self::Delete(x, y) => write!(f, "delete {x} {y}"),
+        }
+    }
+}
+
+impl Edit {
+    /// Applies the edit, checking it against the editor's image and sites.
+    /// Returns the rectangle that was re-rendered.
+    pub fn apply(self, editor: &mut Editor) -> Result<Option<Rect>, String> {
+        let (width, height) = editor.image().dimensions();
+        let sites = editor.points().len();
+        let check_position = |x: u32, y: u32| {
+            if x < width && y < height {
+                Ok(())
+            } else {
+                Err(format!("({x}, {y}) is outside the {width}x{height} image"))
+            }
+        };
+        let check_site = |site: usize| {
+            if site < sites {
+                Ok(())
+            } else {
+                Err(format!("no site {site}; there are {sites}"))
+            }
+        };
+        match self {
+            Self::Add(x, y) => {
+                check_position(x, y)?;
+                Ok(editor.add(x, y))
+            }
+            Self::Move(site, x, y) => {
+                check_site(site)?;
+                check_position(x, y)?;
+                Ok(editor.move_site(site, x, y))
+            }
+            Self::Remove(site) => {
+                check_site(site)?;
+                if sites < 2 {
+                    return Err("cannot remove the last site".to_string());
+                }
+                Ok(editor.remove(site))
+            }
+            Self::Drag(x, y, to_x, to_y) => {
+                check_position(x, y)?;
+                Self::Move(editor.site_at(x, y), to_x, to_y).apply(editor)
+            }
+            Self::Delete(x, y) => {
+                check_position(x, y)?;
+                Self::Remove(editor.site_at(x, y)).apply(editor)
+            }
+        }
+    }
+}
+
+fn parse_edits(text: &str) -> Result<Vec<Edit>, String> {
+    (1..)
+        .zip(text.lines())
+        .map(|(number, line)| (number, line.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(number, line)| line.parse().map_err(|err| format!("line {number}: {err}")))
+        .collect()
+}
+
+/// Describes what an edit redrew.
+fn report(number: usize, redrawn: Option<Rect>) {
+    match redrawn {
+        Some((x0, y0, x1, y1)) => println!(
+            "Edit {number}: redrew {}x{} pixels at ({x0}, {y0})",
+            x1 - x0 + 1,
+            y1 - y0 + 1
+        ),
+        None => println!("Edit {number}: nothing to redraw"),
+    }
+}
+
+/// An editor together with the sites it started from and the edits made
+/// since, which undo and redo step through.
+struct Session<'a> {
+    img: image::RgbImage,
+    initial: Vec<Point>,
+    args: &'a Args,
+    editor: Editor<'a>,
+    project: Project,
+    /// Undone edits, the most recently undone last
+    undone: Vec<Edit>,
+}
+
+impl<'a> Session<'a> {
+    fn new(
+        img: image::RgbImage,
+        initial: Vec<Point>,
+        args: &'a Args,
+        command: Vec<String>,
+    ) -> Self {
+        Self {
+            editor: Editor::new(img.clone(), initial.clone(), args),
+            img,
+            initial,
+            args,
+            project: Project {
+                command,
+                edits: Vec::new(),
+            },
+            undone: Vec::new(),
+        }
+    }
+
+    fn apply(&mut self, edit: Edit) -> Result<Option<Rect>, String> {
+        let redrawn = edit.apply(&mut self.editor)?;
+        self.project.edits.push(edit);
+        self.undone.clear();
+        Ok(redrawn)
+    }
+
+    /// Takes back the last edit. Edits are not inverted: the diagram is
+    /// rebuilt from the initial sites with the remaining edits replayed.
+    fn undo(&mut self) -> Result<(), String> {
+        let edit = self.project.edits.pop().ok_or("nothing to undo")?;
+        self.undone.push(edit);
+        self.editor = Editor::new(self.img.clone(), self.initial.clone(), self.args);
+        for edit in &self.project.edits {
+            edit.apply(&mut self.editor)?;
+        }
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<Option<Rect>, String> {
+        let edit = self.undone.pop().ok_or("nothing to redo")?;
+        let redrawn = edit.apply(&mut self.editor)?;
+        self.project.edits.push(edit);
+        Ok(redrawn)
+    }
+
+    fn save(&self, force: bool) -> Result<(), String> {
+        output::save(&self.args.output, force, |path| {
+            self.editor.image().save(path)
+        })
+    }
+
+    fn save_project(&self, path: Option<&PathBuf>) {
+        if let Some(path) = path {
+            exit_on_err(
+                output::save(path, true, |path| {
+                    std::fs::write(path, self.project.to_string())
+                }),
+                "save project",
+            );
+        }
+    }
+}
+
+/// Applies edits typed on standard input until it ends or "quit" is typed,
+/// saving the output and project after each one. Mistakes are reported and
+/// skipped.
+fn interactive(session: &mut Session, project: Option<&PathBuf>) {
+    exit_on_err(session.save(session.args.force), "save diagram");
+    eprintln!(
+        "Editing {}; type add, move, remove, drag or delete, undo or redo, or quit",
+        session.args.output.display()
+    );
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        eprint!("> ");
+        let _ = std::io::stderr().flush();
+        let Some(Ok(line)) = lines.next() else {
+            eprintln!();
+            break;
+        };
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let result = match line {
+            "" => continue,
+            "quit" | "exit" => break,
+            "undo" => session.undo().map(|()| {
+                println!("Undid edit {}", session.project.edits.len() + 1);
+            }),
+            "redo" => session
+                .redo()
+                .map(|redrawn| report(session.project.edits.len(), redrawn)),
+            _ => line
+                .parse()
+                .and_then(|edit| session.apply(edit))
+                .map(|redrawn| report(session.project.edits.len(), redrawn)),
+        };
+        match result {
+            Ok(()) => {
+                // The output is ours to overwrite from here on
+                exit_on_err(session.save(true), "save diagram");
+                session.save_project(project);
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+}
+
+/// Exits if `args` asks for anything the [`Editor`] does not do: it only
+/// samples sites, scores pixels against them and fills the cells flat, so
+/// the steps between sampling and drawing, styles and side outputs of a
+/// plain render have no place in it.
+fn reject_unsupported_options(args: &Args) {
+    let mut options = vec![
+        ("--like", args.like.is_some()),
+        ("--pipeline", args.pipeline != Pipeline::Rgb),
+        ("--ensemble", args.ensemble.is_some()),
+        ("--passes", args.passes > 1),
+        ("--viewport", args.viewport.is_some()),
+        ("--shard", args.shard.is_some()),
+        ("--proxy-scale", args.proxy_scale < 1.0),
+        ("--cache-dir", args.cache_dir.is_some()),
+        ("--snap-to-edges", args.snap_to_edges.is_some()),
+        ("--text-safety", args.text_safety.is_some()),
+        ("--reject-outliers", args.reject_outliers.is_some()),
+        ("--site-colors", args.site_colors > 1),
+        ("--relax", args.relax > 0),
+        ("--optimize", args.optimize.is_some()),
+        ("--coarse-to-fine", args.coarse_to_fine.is_some()),
+        ("--boundary-dither", args.boundary_dither.is_some()),
+        ("--warp", args.warp.is_some()),
+        ("--duotone", args.duotone.is_some()),
+        ("--match-histogram", args.match_histogram),
+        ("--posterize", args.posterize.is_some()),
+        ("--smooth-boundaries", args.smooth_boundaries.is_some()),
+        ("--debug-dir", args.debug_dir.is_some()),
+    ];
+    options.extend(drawing_options(args));
+    options.extend(side_output_options(args));
+    reject_options("edit", &options);
+}
+
+pub fn run(edit: &EditArgs) {
+    reject_unsupported_options(&edit.args);
+    let mut args = edit.args.clone();
+    for path in std::iter::once(&args.output).chain(&edit.export_points) {
+        exit_on_err(output::check_clobber(path, args.force), "write output");
+    }
+    let edits = edit.edits.as_ref().map(|path| {
+        let text = exit_on_err(std::fs::read_to_string(path), "read edits");
+        exit_on_err(parse_edits(&text), "parse edits")
+    });
+    let resumed = edit
+        .session
+        .as_ref()
+        .filter(|path| path.exists())
+        .map(|path| exit_on_err(load_project(path), "read project"));
+
+    // Replaying edits needs the same sites, so the project pins the seed
+    let mut command: Vec<String> = std::env::args().collect();
+    if args.seed.is_none() {
+        let recorded = resumed
+            .as_ref()
+            .and_then(|project| exit_on_err(project_args(project), "read project").seed);
+        let seed = recorded.unwrap_or_else(|| rand::rng().random());
+        args.seed = Some(seed);
+        command.extend(["--seed".to_string(), seed.to_string()]);
+    }
+    let img = load_image(&args.input, &args);
+    let args = for_image(&args, &img);
+    let mut rng = seeded_rng(&args);
+    let points = sample_points_print_progress(&img, &args, args.points, &mut rng);
+    let points = args.seed_color.apply(&img, &points);
+    let mut session = Session::new(img, points, &args, command);
+    if let Some(resumed) = resumed {
+        println!("Replaying {} edits", resumed.edits.len());
+        for (number, edit) in (1..).zip(resumed.edits) {
+            exit_on_err(session.apply(edit), &format!("replay edit {number}"));
+        }
+    }
+
+    let project = edit.session.as_ref();
+    if let Some(edits) = edits {
+        for edit in edits {
+            let number = session.project.edits.len() + 1;
+            let redrawn = exit_on_err(session.apply(edit), &format!("apply edit {number}"));
+            report(number, redrawn);
+        }
+        exit_on_err(session.save(args.force), "save diagram");
+        session.save_project(project);
+    } else {
+        interactive(&mut session, project);
+    }
+    eprintln!("Saved voronoi diagram to {}", args.output.display());
+    if let Some(path) = project {
+        eprintln!("Saved project to {}", path.display());
+    }
+
+    if let Some(path) = &edit.export_points {
+        let points = session.editor.points();
+        exit_on_err(
+            output::save(path, args.force, |path| {
+                std::fs::write(path, export::points_json(points))
+            }),
+            "save points",
+        );
+        eprintln!("Saved {} points to {}", points.len(), path.display());
+    }
+}
@@ -0,0 +1,173 @@
+//! Shading effects applied to rendered cells.
+
+use std::f64::consts::FRAC_PI_2;
+use std::str::FromStr;
+
+use crate::cells::CellStats;
+use crate::color::{blend, luminance, to_u8};
+use crate::distance;
+
+/// How far, in pixels, a papercut layer casts its shadow down and right.
+const SHADOW_OFFSET: u32 = 4;
+const SHADOW_ALPHA: f64 = 0.45;
+const HIGHLIGHT_ALPHA: f64 = 0.35;
+
+/// Stacking order of cells in the papercut style: lighter cells sit on top.
+#[must_use]
+pub fn papercut_layers(colors: &[[u8; 3]]) -> Vec<u32> {
+    let mut order: Vec<usize> = (0..colors.len()).collect();
+    order.sort_by(|&a, &b| luminance(colors[a]).total_cmp(&luminance(colors[b])));
+    let mut layers = vec![0; colors.len()];
+    for (layer, idx) in (0..).zip(order) {
+        layers[idx] = layer;
+    }
+    layers
+}
+
+/// Shades `color` at `(x, y)` as a sheet of cut paper: cells on higher
+/// layers cast a soft shadow onto lower ones, and each sheet's exposed top-left
+/// edge catches the light.
+#[must_use]
+pub fn papercut(
+    color: [u8; 3],
+    labels: &[u32],
+    layers: &[u32],
+    width: u32,
+    x: u32,
+    y: u32,
+) -> [u8; 3] {
+    let label_at = |x: u32, y: u32| labels[(y * width + x) as usize] as usize;
+    let layer = layers[label_at(x, y)];
+
+    let mut color = color;
+    if let Some(distance) =
+        (1..=SHADOW_OFFSET.min(x).min(y)).find(|&d| layers[label_at(x - d, y - d)] > layer)
+    {
+        let falloff = f64::from(distance - 1) / f64::from(SHADOW_OFFSET);
+        color = blend(color, [0, 0, 0], SHADOW_ALPHA * (1.0 - falloff));
+    }
+
+    let lit_edge = [(1, 1), (1, 0), (0, 1)]
+        .into_iter()
+        .filter(|&(dx, dy)| x >= dx && y >= dy)
+        .any(|(dx, dy)| layers[label_at(x - dx, y - dy)] < layer);
+    if lit_edge {
+        color = blend(color, [255, 255, 255], HIGHLIGHT_ALPHA);
+    }
+    color
+}
+
+/// Direction of a distant light, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    /// Clockwise from the top of the image
+    pub azimuth: f64,
+    /// Above the image plane
+    pub elevation: f64,
+}
+
+impl Light {
+    /// Unit vector pointing towards the light, with y pointing down the image.
+    #[must_use]
+    pub fn direction(&self) -> [f64; 3] {
+        let (az, el) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        [az.sin() * el.cos(), -az.cos() * el.cos(), el.sin()]
+    }
+}
+
+impl FromStr for Light {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid light '{s}' (expected azimuth,elevation in degrees)");
+        let (azimuth, elevation) = s.split_once(',').ok_or_else(err)?;
+        Ok(Self {
+            azimuth: azimuth.trim().parse().map_err(|_| err())?,
+            elevation: elevation.trim().parse().map_err(|_| err())?,
+        })
+    }
+}
+
+/// Sharpness of specular highlights, as the Blinn-Phong exponent.
+const SHININESS: i32 = 32;
+
+/// Height of each tile's flat top: `bevel`, plus up to `relief` more for the
+/// brightest cells when tiles are raised by their average luminance.
+#[must_use]
+pub fn tile_peaks(stats: &[CellStats], bevel: f32, relief: Option<f32>) -> Vec<f64> {
+    stats
+        .iter()
+        .map(|stats| {
+            let lift = relief.map_or(0.0, |relief| {
+                let level = stats.average().map_or(0.0, luminance) / 255.0;
+                f64::from(relief) * level
+            });
+            f64::from(bevel) + lift
+        })
+        .collect()
+}
+
+/// Surface normals of cells raised into tiles whose edges slope down over
+/// `bevel` pixels towards the cell boundary, each cell rising to its height
+/// in `peaks`.
+#[must_use]
+pub fn bevel_normals(
+    labels: &[u32],
+    width: u32,
+    height: u32,
+    bevel: f32,
+    peaks: &[f64],
+) -> Vec<[f64; 3]> {
+    let heights: Vec<f64> = distance::boundary_distance(labels, width, height)
+        .into_iter()
+        .zip(labels)
+        .map(|(dist, &label)| {
+            // Quarter sine profile: steep at the edge, flat on top
+            let t = f64::from((dist / bevel).min(1.0));
+            (t * FRAC_PI_2).sin() * peaks[label as usize]
+        })
+        .collect();
+    let (w, h) = (width as usize, height as usize);
+    let mut normals = Vec::with_capacity(heights.len());
+    for y in 0..h {
+        for x in 0..w {
+            let at = |x: usize, y: usize| heights[y * w + x];
+            let dx = (at((x + 1).min(w - 1), y) - at(x.saturating_sub(1), y)) / 2.0;
+            let dy = (at(x, (y + 1).min(h - 1)) - at(x, y.saturating_sub(1))) / 2.0;
+            let len = (dx * dx + dy * dy + 1.0).sqrt();
+            normals.push([-dx / len, -dy / len, 1.0 / len]);
+        }
+    }
+    normals
+}
+
+/// Lambert-shades `color` for a surface `normal`, leaving flat areas
+/// unchanged, then adds a Blinn-Phong highlight of strength `specular` as
+/// seen from straight above.
+#[must_use]
+pub fn shade(color: [u8; 3], normal: [f64; 3], light: &Light, specular: Option<f64>) -> [u8; 3] {
+    let dir = light.direction();
+    let dot = |a: [f64; 3], b: [f64; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let lambert = dot(normal, dir).max(0.0);
+    let factor = lambert / dir[2].max(1e-3);
+    let highlight = specular.map_or(0.0, |strength| {
+        let half = [dir[0], dir[1], dir[2] + 1.0];
+        let len = dot(half, half).sqrt().max(1e-9);
+        let facing = (dot(normal, half) / len).max(0.0);
+        // Flat tops reflect the same highlight everywhere, so only the part
+        // above what a flat surface gets shows
+        let flat = (half[2] / len).max(0.0);
+        strength * (facing.powi(SHININESS) - flat.powi(SHININESS)).max(0.0) * 255.0
+    });
+    color.map(|c| to_u8(f64::from(c) * factor + highlight))
+}
+
+/// Encodes normals as a tangent-space normal map (OpenGL convention, y up).
+#[must_use]
+pub fn normal_map(normals: &[[f64; 3]], width: u32, height: u32) -> image::RgbImage {
+    let encode = |v: f64| to_u8((v * 0.5 + 0.5) * 255.0);
+    image::RgbImage::from_fn(width, height, |x, y| {
+        let [nx, ny, nz] = normals[(y * width + x) as usize];
+        image::Rgb([encode(nx), encode(-ny), encode(nz)])
+    })
+}
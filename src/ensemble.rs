@@ -0,0 +1,75 @@
+//! Several diagrams of one image from different seeds blended into one, for
+//! `--ensemble`: cell edges that move from seed to seed soften into washes.
+
+use crate::{Args, Diagram, build_diagram, timings};
+
+type Point = (u32, u32, [u8; 3]);
+
+/// How the ensemble members are blended per pixel.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnsembleMode {
+    /// The average of the members, softest
+    Mean,
+    /// The per-channel median of the members, keeping edges most members
+    /// agree on
+    Median,
+}
+
+/// Renders `members` diagrams of `img` in parallel, the first with the seed
+/// of `args` and each next one with the seed after, and blends their images.
+/// Returns the first diagram carrying the blended image, so outputs drawn
+/// from cells use its labels and colors, and its points.
+pub fn build(
+    img: &image::RgbImage,
+    args: &Args,
+    members: u32,
+    mode: EnsembleMode,
+    timings: &mut timings::Timings,
+) -> (Diagram, Vec<Point>) {
+    let seed = args.seed.unwrap_or_default();
+    println!("Rendering {members} ensemble members in parallel");
+    let mut diagrams: Vec<(Diagram, Vec<Point>)> = timings.time("ensemble", || {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..members)
+                .map(|member| {
+                    let mut args = args.clone();
+                    args.seed = Some(seed.wrapping_add(u64::from(member)));
+                    scope.spawn(move || build_diagram(img, &args, &mut timings::Timings::default()))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("ensemble member panicked"))
+                .collect()
+        })
+    });
+    let images: Vec<&image::RgbImage> =
+        diagrams.iter().map(|(diagram, _)| &diagram.image).collect();
+    let blended = blend(&images, mode);
+    let (mut first, points) = diagrams.swap_remove(0);
+    first.image = blended;
+    (first, points)
+}
+
+/// The per-pixel blend of same-sized `images` by `mode`.
+fn blend(images: &[&image::RgbImage], mode: EnsembleMode) -> image::RgbImage {
+    let (width, height) = images[0].dimensions();
+    let count = images.len();
+    let mut values = Vec::with_capacity(count);
+    image::RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb(std::array::from_fn(|channel| {
+            values.clear();
+            values.extend(images.iter().map(|image| image.get_pixel(x, y).0[channel]));
+            match mode {
+                EnsembleMode::Mean => {
+                    let sum: usize = values.iter().map(|&v| usize::from(v)).sum();
+                    u8::try_from((sum + count / 2) / count).unwrap_or(u8::MAX)
+                }
+                EnsembleMode::Median => {
+                    values.sort_unstable();
+                    values[count / 2]
+                }
+            }
+        }))
+    })
+}
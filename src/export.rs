@@ -0,0 +1,630 @@
+//! Machine-readable exports of a diagram alongside the raster.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use image::Pixel;
+
+use crate::{output, polygon};
+
+/// The outline of every cell as closed rings of vertices, traced along its
+/// pixel boundary and indexed by label. Only collinear vertices are dropped,
+/// so neighbouring cells still meet exactly.
+#[must_use]
+pub fn cell_outlines(
+    labels: &[u32],
+    width: u32,
+    height: u32,
+    cells: usize,
+) -> Vec<Vec<Vec<(f64, f64)>>> {
+    polygon::cell_edges(labels, width, height, cells)
+        .iter()
+        .map(|edges| {
+            polygon::chain(edges)
+                .iter()
+                .map(|chain| polygon::simplify(chain, 0.0))
+                .collect()
+        })
+        .collect()
+}
+
+/// SVG with every cell as a filled path traced along its pixel boundary.
+#[must_use]
+pub fn svg(labels: &[u32], colors: &[[u8; 3]], width: u32, height: u32) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" shape-rendering=\"crispEdges\">\n"
+    );
+    for (rings, [r, g, b]) in cell_outlines(labels, width, height, colors.len())
+        .iter()
+        .zip(colors)
+    {
+        if rings.is_empty() {
+            continue;
+        }
+        let _ = writeln!(
+            svg,
+            "<path fill=\"#{r:02x}{g:02x}{b:02x}\" fill-rule=\"evenodd\" d=\"{}\"/>",
+            ring_path(rings)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes each ring of a cell outline as SVG path data.
+fn ring_path(rings: &[Vec<(f64, f64)>]) -> String {
+    let mut path = String::new();
+    for points in rings {
+        let _ = write!(path, "M{},{}", points[0].0, points[0].1);
+        for (x, y) in &points[1..] {
+            let _ = write!(path, " L{x},{y}");
+        }
+        path.push('Z');
+    }
+    path
+}
+
+/// SVG laid out for editing in Inkscape or Illustrator: a "Fills" layer with
+/// every cell as a path named after it, a "Boundaries" layer with the edges
+/// between cells as strokes, and a "Sites" layer with a dot on each point.
+/// `swatches` are declared as document colors.
+#[must_use]
+pub fn layered_svg(
+    labels: &[u32],
+    colors: &[[u8; 3]],
+    points: &[(u32, u32, [u8; 3])],
+    (width, height): (u32, u32),
+    swatches: &[[u8; 3]],
+) -> String {
+    let hex = |[r, g, b]: [u8; 3]| format!("#{r:02x}{g:02x}{b:02x}");
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+         xmlns:inkscape=\"http://www.inkscape.org/namespaces/inkscape\" \
+         width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n<defs>\n"
+    );
+    // Inkscape lists solid single-stop gradients as swatches
+    for (i, &color) in swatches.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            "<linearGradient id=\"swatch-{i}\" inkscape:swatch=\"solid\" inkscape:label=\"{0}\">\
+             <stop offset=\"0\" style=\"stop-color:{0};stop-opacity:1\"/></linearGradient>",
+            hex(color)
+        );
+    }
+    svg.push_str("</defs>\n");
+
+    svg.push_str(
+        "<g id=\"fills\" inkscape:groupmode=\"layer\" inkscape:label=\"Fills\" \
+         shape-rendering=\"crispEdges\">\n",
+    );
+    for (label, (rings, &color)) in cell_outlines(labels, width, height, colors.len())
+        .iter()
+        .zip(colors)
+        .enumerate()
+    {
+        if !rings.is_empty() {
+            let _ = writeln!(
+                svg,
+                "<path id=\"cell-{label}\" inkscape:label=\"Cell {label}\" fill=\"{}\" \
+                 fill-rule=\"evenodd\" d=\"{}\"/>",
+                hex(color),
+                ring_path(rings)
+            );
+        }
+    }
+    svg.push_str("</g>\n");
+
+    svg.push_str(
+        "<g id=\"boundaries\" inkscape:groupmode=\"layer\" inkscape:label=\"Boundaries\" \
+         fill=\"none\" stroke=\"#000000\" stroke-width=\"1\" stroke-linejoin=\"round\">\n",
+    );
+    let mut edges: Vec<_> = polygon::shared_edges(labels, width, height)
+        .into_iter()
+        .collect();
+    // Hash map order varies between runs; keep the file reproducible
+    edges.sort_unstable_by_key(|&(pair, _)| pair);
+    for ((a, b), segments) in edges {
+        let mut path = String::new();
+        for chain in polygon::chain(&segments) {
+            let points = polygon::simplify(&chain, 0.0);
+            let _ = write!(path, "M{},{}", points[0].0, points[0].1);
+            for (x, y) in &points[1..] {
+                let _ = write!(path, " L{x},{y}");
+            }
+        }
+        let _ = writeln!(svg, "<path id=\"edge-{a}-{b}\" d=\"{path}\"/>");
+    }
+    svg.push_str("</g>\n");
+
+    svg.push_str(
+        "<g id=\"sites\" inkscape:groupmode=\"layer\" inkscape:label=\"Sites\" fill=\"#000000\">\n",
+    );
+    for (label, (x, y, _)) in points.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            "<circle id=\"site-{label}\" cx=\"{}\" cy=\"{}\" r=\"1.5\"/>",
+            f64::from(*x) + 0.5,
+            f64::from(*y) + 0.5
+        );
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// One SVG per palette entry for screen printing or vinyl cutting, each
+/// holding only the cells whose color is nearest that entry, merged into
+/// one shape. Entries no cell maps to get no SVG.
+#[must_use]
+pub fn separations(
+    labels: &[u32],
+    colors: &[[u8; 3]],
+    palette: &crate::palette::Palette,
+    width: u32,
+    height: u32,
+) -> Vec<(usize, String)> {
+    let entries: Vec<u32> = colors
+        .iter()
+        .map(|&color| {
+            let nearest = palette.nearest(color.map(f64::from));
+            let entry = palette.0.iter().position(|&c| c == nearest);
+            u32::try_from(entry.unwrap_or_default()).unwrap_or_default()
+        })
+        .collect();
+    let plates: Vec<u32> = labels
+        .iter()
+        .map(|&label| entries[label as usize])
+        .collect();
+    cell_outlines(&plates, width, height, palette.0.len())
+        .iter()
+        .zip(&palette.0)
+        .enumerate()
+        .filter(|(_, (rings, _))| !rings.is_empty())
+        .map(|(entry, (rings, [r, g, b]))| {
+            let svg = format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+                 viewBox=\"0 0 {width} {height}\">\n\
+                 <path fill=\"#{r:02x}{g:02x}{b:02x}\" fill-rule=\"evenodd\" d=\"{}\"/>\n</svg>\n",
+                ring_path(rings)
+            );
+            (entry, svg)
+        })
+        .collect()
+}
+
+/// File name of the separation for palette entry `entry` of `color`.
+#[must_use]
+pub fn separation_name(entry: usize, [r, g, b]: [u8; 3]) -> String {
+    format!("separation_{entry:02}_{r:02x}{g:02x}{b:02x}.svg")
+}
+
+/// The label map as an RGB image, each pixel holding its label in 24 bits
+/// (red is the high byte).
+#[must_use]
+pub fn label_map(labels: &[u32], width: u32, height: u32) -> image::RgbImage {
+    image::RgbImage::from_fn(width, height, |x, y| {
+        let [_, r, g, b] = labels[(y * width + x) as usize].to_be_bytes();
+        image::Rgb([r, g, b])
+    })
+}
+
+/// A soft matte of the cell boundaries: white on them, fading smoothly to
+/// black `band` pixels away.
+#[must_use]
+pub fn boundary_matte(labels: &[u32], width: u32, height: u32, band: f32) -> image::GrayImage {
+    let distances = crate::distance::boundary_distance(labels, width, height);
+    image::GrayImage::from_fn(width, height, |x, y| {
+        let t = (distances[(y * width + x) as usize] / band).clamp(0.0, 1.0);
+        let fade = 1.0 - t * t * (3.0 - 2.0 * t);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        image::Luma([(fade * 255.0).round() as u8])
+    })
+}
+
+/// Each cell of `image` cropped to its bounding box, with the pixels of other
+/// cells transparent, and the crop's top-left corner. Empty cells are `None`.
+#[must_use]
+pub fn cell_cutouts(
+    image: &image::RgbImage,
+    labels: &[u32],
+    cells: usize,
+) -> Vec<Option<(u32, u32, image::RgbaImage)>> {
+    let width = image.width();
+    let mut bounds: Vec<Option<(u32, u32, u32, u32)>> = vec![None; cells];
+    for (i, &label) in (0..).zip(labels) {
+        let (x, y) = (i % width, i / width);
+        let bound = bounds[label as usize].get_or_insert((x, y, x, y));
+        *bound = (
+            bound.0.min(x),
+            bound.1.min(y),
+            bound.2.max(x),
+            bound.3.max(y),
+        );
+    }
+    (0..)
+        .zip(bounds)
+        .map(|(label, bound)| {
+            let (x0, y0, x1, y1) = bound?;
+            let cutout = image::RgbaImage::from_fn(x1 - x0 + 1, y1 - y0 + 1, |x, y| {
+                let (x, y) = (x0 + x, y0 + y);
+                let mut pixel = image.get_pixel(x, y).to_rgba();
+                if labels[(y * width + x) as usize] != label {
+                    pixel.0[3] = 0;
+                }
+                pixel
+            });
+            Some((x0, y0, cutout))
+        })
+        .collect()
+}
+
+/// File name of the cutout of cell `label` in a `save_cells` directory.
+fn cell_file(label: usize) -> String {
+    format!("cell_{label:05}.png")
+}
+
+/// The files in `dir` a `save_cells` export would write over: its index
+/// and any cutouts, whichever cells they were of.
+#[must_use]
+pub fn cell_export_paths(dir: &Path) -> Vec<PathBuf> {
+    let cutouts = std::fs::read_dir(dir).into_iter().flatten().flatten();
+    let cutouts = cutouts.map(|entry| entry.path()).filter(|path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("cell_")?.strip_suffix(".png"))
+            .is_some_and(|number| number.len() == 5 && number.bytes().all(|b| b.is_ascii_digit()))
+    });
+    std::iter::once(dir.join("index.json"))
+        .chain(cutouts)
+        .collect()
+}
+
+/// Writes every cell of `image` as a cropped PNG into `dir`, with an
+/// `index.json` listing each file and where it sits in the image.
+pub fn save_cells(
+    dir: &Path,
+    image: &image::RgbImage,
+    labels: &[u32],
+    cells: usize,
+    force: bool,
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    let mut entries = Vec::new();
+    for (label, cutout) in cell_cutouts(image, labels, cells).into_iter().enumerate() {
+        let Some((x, y, cutout)) = cutout else {
+            continue;
+        };
+        let file = cell_file(label);
+        output::save(&dir.join(&file), force, |path| cutout.save(path))?;
+        entries.push(format!(
+            "  {{\"cell\": {label}, \"file\": \"{file}\", \"x\": {x}, \"y\": {y}, \"width\": {}, \"height\": {}}}",
+            cutout.width(),
+            cutout.height()
+        ));
+    }
+    let index = format!("[\n{}\n]\n", entries.join(",\n"));
+    output::save(&dir.join("index.json"), force, |path| {
+        std::fs::write(path, index)
+    })
+}
+
+/// Gap between packed sprites, so texture filtering never bleeds a
+/// neighbour's pixels into a sprite's edge.
+const SPRITE_PADDING: u32 = 2;
+
+/// Places rectangles of `sizes` on shelves of a sheet about as wide as it is
+/// tall, tallest first. Returns each rectangle's top-left corner and the size
+/// of the sheet.
+fn pack(sizes: &[(u32, u32)]) -> (Vec<(u32, u32)>, (u32, u32)) {
+    let area: u64 = sizes
+        .iter()
+        .map(|&(w, h)| u64::from(w + SPRITE_PADDING) * u64::from(h + SPRITE_PADDING))
+        .sum();
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    let square = (area as f64).sqrt().ceil() as u32;
+    let sheet_width = sizes
+        .iter()
+        .map(|&(w, _)| w + SPRITE_PADDING)
+        .fold(square, u32::max);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for i in order {
+        let (w, h) = sizes[i];
+        if x + w + SPRITE_PADDING > sheet_width {
+            (x, y, shelf_height) = (0, y + shelf_height, 0);
+        }
+        positions[i] = (x, y);
+        x += w + SPRITE_PADDING;
+        shelf_height = shelf_height.max(h + SPRITE_PADDING);
+    }
+    (positions, (sheet_width, (y + shelf_height).max(1)))
+}
+
+/// Packs every cell of `image` into one sprite sheet saved to `path`, with a
+/// JSON atlas next to it in the widely supported `TexturePacker` hash layout:
+/// each frame's rectangle in the sheet, where it sits in the source image,
+/// and its pivot at the cell's centroid as a fraction of the frame from its
+/// top-left corner.
+pub fn save_sprite_sheet(
+    path: &Path,
+    image: &image::RgbImage,
+    labels: &[u32],
+    stats: &[crate::cells::CellStats],
+    force: bool,
+) -> Result<(), String> {
+    let cutouts: Vec<(usize, (u32, u32, image::RgbaImage))> =
+        cell_cutouts(image, labels, stats.len())
+            .into_iter()
+            .enumerate()
+            .filter_map(|(label, cutout)| Some((label, cutout?)))
+            .collect();
+    let sizes: Vec<(u32, u32)> = cutouts
+        .iter()
+        .map(|(_, (_, _, cutout))| cutout.dimensions())
+        .collect();
+    let (positions, (sheet_width, sheet_height)) = pack(&sizes);
+
+    let mut sheet = image::RgbaImage::new(sheet_width, sheet_height);
+    let mut frames = Vec::new();
+    for ((label, (x, y, cutout)), &(sheet_x, sheet_y)) in cutouts.iter().zip(&positions) {
+        image::imageops::replace(&mut sheet, cutout, i64::from(sheet_x), i64::from(sheet_y));
+        let (w, h) = cutout.dimensions();
+        let (cx, cy) = stats[*label]
+            .centroid()
+            .unwrap_or((f64::from(*x), f64::from(*y)));
+        let pivot = (
+            (cx + 0.5 - f64::from(*x)) / f64::from(w),
+            (cy + 0.5 - f64::from(*y)) / f64::from(h),
+        );
+        frames.push(format!(
+            "    \"cell_{label:05}\": {{\"frame\": {{\"x\": {sheet_x}, \"y\": {sheet_y}, \"w\": {w}, \"h\": {h}}}, \
+             \"rotated\": false, \"trimmed\": true, \
+             \"spriteSourceSize\": {{\"x\": {x}, \"y\": {y}, \"w\": {w}, \"h\": {h}}}, \
+             \"sourceSize\": {{\"w\": {}, \"h\": {}}}, \
+             \"pivot\": {{\"x\": {:.4}, \"y\": {:.4}}}}}",
+            image.width(),
+            image.height(),
+            pivot.0,
+            pivot.1
+        ));
+    }
+    output::save(path, force, |path| sheet.save(path))?;
+
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let atlas = format!(
+        "{{\n  \"frames\": {{\n{}\n  }},\n  \"meta\": {{\"image\": \"{name}\", \"format\": \"RGBA8888\", \
+         \"size\": {{\"w\": {sheet_width}, \"h\": {sheet_height}}}, \"scale\": \"1\"}}\n}}\n",
+        frames.join(",\n")
+    );
+    output::save(&path.with_extension("json"), force, |path| {
+        std::fs::write(path, atlas)
+    })
+}
+
+/// How a distance field's values are stored.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldScale {
+    /// Scaled so the largest distance is 1, or full white in integer formats
+    Normalized,
+    /// Distances in pixels, clamped to the format's range in integer formats
+    Raw,
+}
+
+/// A distance field as a floating-point image for `.exr` outputs or a 16-bit
+/// grayscale image otherwise, chosen by the extension of `path`.
+pub fn save_distance_field(
+    path: &Path,
+    distances: &[f32],
+    width: u32,
+    height: u32,
+    scale: FieldScale,
+) -> image::ImageResult<()> {
+    let max = distances
+        .iter()
+        .copied()
+        .fold(0.0, f32::max)
+        .max(f32::MIN_POSITIVE);
+    let value = |x: u32, y: u32| {
+        let distance = distances[(y * width + x) as usize];
+        match scale {
+            FieldScale::Normalized => distance / max,
+            FieldScale::Raw => distance,
+        }
+    };
+    let is_exr = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+    if is_exr {
+        // OpenEXR output needs color channels, so the field is repeated in each
+        image::Rgb32FImage::from_fn(width, height, |x, y| image::Rgb([value(x, y); 3])).save(path)
+    } else {
+        let full = match scale {
+            FieldScale::Normalized => f32::from(u16::MAX),
+            FieldScale::Raw => 1.0,
+        };
+        image::ImageBuffer::<image::Luma<u16>, _>::from_fn(width, height, |x, y| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            image::Luma([(value(x, y) * full).round().min(f32::from(u16::MAX)) as u16])
+        })
+        .save(path)
+    }
+}
+
+/// A map of values from 0 to 1 as a floating-point image for `.exr` outputs
+/// or a 16-bit grayscale image with 1 as white otherwise.
+pub fn save_unit_map(
+    path: &Path,
+    values: &[f32],
+    width: u32,
+    height: u32,
+) -> image::ImageResult<()> {
+    let value = |x: u32, y: u32| values[(y * width + x) as usize].clamp(0.0, 1.0);
+    let is_exr = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+    if is_exr {
+        image::Rgb32FImage::from_fn(width, height, |x, y| image::Rgb([value(x, y); 3])).save(path)
+    } else {
+        image::ImageBuffer::<image::Luma<u16>, _>::from_fn(width, height, |x, y| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            image::Luma([(value(x, y) * f32::from(u16::MAX)).round() as u16])
+        })
+        .save(path)
+    }
+}
+
+/// The sampled points and their colors as a JSON array.
+#[must_use]
+pub fn points_json(points: &[(u32, u32, [u8; 3])]) -> String {
+    let mut json = String::from("[\n");
+    for (i, (x, y, [r, g, b])) in points.iter().enumerate() {
+        let separator = if i + 1 < points.len() { "," } else { "" };
+        let _ = writeln!(
+            json,
+            "  {{\"x\": {x}, \"y\": {y}, \"color\": \"#{r:02x}{g:02x}{b:02x}\"}}{separator}"
+        );
+    }
+    json.push_str("]\n");
+    json
+}
+
+/// A flat JSON object: its keys and values in order, strings unescaped and
+/// numbers, booleans and nulls as written.
+#[derive(Debug, Clone, Default)]
+pub struct JsonObject(Vec<(String, String)>);
+
+impl JsonObject {
+    /// The value of `key`, if the object has it.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads JSON a character at a time.
+struct JsonReader<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl JsonReader<'_> {
+    /// The next character after any whitespace, without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(char::is_ascii_whitespace).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.chars.next();
+                Ok(())
+            }
+            Some(c) => Err(format!("expected '{expected}', found '{c}'")),
+            None => Err(format!("expected '{expected}', found the end")),
+        }
+    }
+
+    /// A quoted string, with its escapes resolved.
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next().ok_or("unterminated string")? {
+                '"' => return Ok(string),
+                '\\' => string.push(match self.chars.next().ok_or("unterminated string")? {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let hex: String = self.chars.by_ref().take(4).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == 4)
+                            .ok_or_else(|| format!("invalid escape \"\\u{hex}\""))
+                            .map(|code| char::from_u32(code).unwrap_or('\u{fffd}'))?
+                    }
+                    c @ ('"' | '\\' | '/') => c,
+                    c => return Err(format!("invalid escape \"\\{c}\"")),
+                }),
+                c => string.push(c),
+            }
+        }
+    }
+
+    /// A string, or a number, boolean or null as written.
+    fn value(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') => self.string(),
+            Some('{' | '[') => Err("nested objects and arrays are not supported".to_string()),
+            _ => {
+                let mut value = String::new();
+                while let Some(c) = self
+                    .chars
+                    .next_if(|&c| !matches!(c, ',' | '}' | ']') && !c.is_ascii_whitespace())
+                {
+                    value.push(c);
+                }
+                if value.is_empty() {
+                    return Err("missing value".to_string());
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonObject, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        if self.peek() == Some('}') {
+            self.chars.next();
+            return Ok(JsonObject(fields));
+        }
+        loop {
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            match self.peek() {
+                Some(',') => self.chars.next(),
+                _ => break self.expect('}').map(|()| JsonObject(fields)),
+            };
+        }
+    }
+}
+
+/// The objects of a JSON array of flat objects, `[{"key": value, ...}, ...]`.
+pub fn json_objects(data: &str) -> Result<Vec<JsonObject>, String> {
+    let mut reader = JsonReader {
+        chars: data.chars().peekable(),
+    };
+    reader.expect('[')?;
+    let mut objects = Vec::new();
+    if reader.peek() == Some(']') {
+        reader.chars.next();
+    } else {
+        loop {
+            objects.push(reader.object()?);
+            match reader.peek() {
+                Some(',') => reader.chars.next(),
+                _ => break reader.expect(']')?,
+            };
+        }
+    }
+    match reader.peek() {
+        None => Ok(objects),
+        Some(c) => Err(format!("unexpected '{c}' after the array")),
+    }
+}
@@ -0,0 +1,219 @@
+//! Extrusion of cells into heightmaps and printable meshes.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::cells::CellStats;
+use crate::color::luminance;
+
+/// Thickness of the solid floor under every cell, in pixels.
+const BASE_HEIGHT: f32 = 1.0;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtrudeBy {
+    /// Darker cells stand taller, as in a lithophane
+    Luminance,
+    /// Larger cells stand taller
+    Area,
+}
+
+/// Height of each cell in `0..=1`.
+#[must_use]
+pub fn cell_heights(colors: &[[u8; 3]], stats: &[CellStats], by: ExtrudeBy) -> Vec<f32> {
+    match by {
+        #[allow(clippy::cast_possible_truncation)]
+        ExtrudeBy::Luminance => colors
+            .iter()
+            .map(|&color| (1.0 - luminance(color) / 255.0) as f32)
+            .collect(),
+        ExtrudeBy::Area => {
+            let max = stats.iter().map(|s| s.count).max().unwrap_or(0).max(1);
+            #[allow(clippy::cast_precision_loss)]
+            stats.iter().map(|s| s.count as f32 / max as f32).collect()
+        }
+    }
+}
+
+/// Renders cell heights as a 16-bit grayscale heightmap.
+#[must_use]
+pub fn heightmap(
+    labels: &[u32],
+    heights: &[f32],
+    width: u32,
+    height: u32,
+) -> image::ImageBuffer<image::Luma<u16>, Vec<u16>> {
+    image::ImageBuffer::from_fn(width, height, |x, y| {
+        let h = heights[labels[(y * width + x) as usize] as usize];
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        image::Luma([(h * f32::from(u16::MAX)).round() as u16])
+    })
+}
+
+type Triangle = [[f32; 3]; 3];
+
+/// Builds a closed terraced mesh where each pixel is a column standing
+/// `BASE_HEIGHT + height * max_height` tall. The image's top row ends up at
+/// the far (+y) side so the print reads correctly from above.
+#[must_use]
+// Equal heights come from identical cell values, so exact comparison is intended
+#[allow(clippy::float_cmp)]
+pub fn mesh(
+    labels: &[u32],
+    heights: &[f32],
+    width: u32,
+    height: u32,
+    max_height: f32,
+) -> Vec<Triangle> {
+    let (w, h) = (width as usize, height as usize);
+    let column =
+        |x: usize, y: usize| BASE_HEIGHT + heights[labels[y * w + x] as usize] * max_height;
+    #[allow(clippy::cast_precision_loss)]
+    let (fw, fh) = (w as f32, h as f32);
+    #[allow(clippy::cast_precision_loss)]
+    let coord = |v: usize| v as f32;
+    let mut triangles = Vec::new();
+
+    for y in 0..h {
+        let (y_lo, y_hi) = (fh - coord(y) - 1.0, fh - coord(y));
+        // Top faces, one quad per run of equal height
+        let mut start = 0;
+        for x in 1..=w {
+            if x == w || column(x, y) != column(start, y) {
+                let z = column(start, y);
+                let (x0, x1) = (coord(start), coord(x));
+                push_quad(
+                    &mut triangles,
+                    [[x0, y_lo, z], [x1, y_lo, z], [x1, y_hi, z], [x0, y_hi, z]],
+                    [0.0, 0.0, 1.0],
+                );
+                start = x;
+            }
+        }
+        // Floor
+        push_quad(
+            &mut triangles,
+            [
+                [0.0, y_lo, 0.0],
+                [fw, y_lo, 0.0],
+                [fw, y_hi, 0.0],
+                [0.0, y_hi, 0.0],
+            ],
+            [0.0, 0.0, -1.0],
+        );
+        // Walls between horizontally adjacent columns and at the sides
+        for x in 0..=w {
+            let left = if x > 0 { column(x - 1, y) } else { 0.0 };
+            let right = if x < w { column(x, y) } else { 0.0 };
+            if left != right {
+                let xe = coord(x);
+                let normal = if left > right { 1.0 } else { -1.0 };
+                push_quad(
+                    &mut triangles,
+                    [
+                        [xe, y_lo, left],
+                        [xe, y_hi, left],
+                        [xe, y_hi, right],
+                        [xe, y_lo, right],
+                    ],
+                    [normal, 0.0, 0.0],
+                );
+            }
+        }
+    }
+    // Walls between vertically adjacent columns and at the top and bottom
+    for y in 0..=h {
+        let ye = fh - coord(y);
+        for x in 0..w {
+            let above = if y > 0 { column(x, y - 1) } else { 0.0 };
+            let below = if y < h { column(x, y) } else { 0.0 };
+            if above != below {
+                let (x0, x1) = (coord(x), coord(x + 1));
+                let normal = if below > above { 1.0 } else { -1.0 };
+                push_quad(
+                    &mut triangles,
+                    [
+                        [x0, ye, above],
+                        [x1, ye, above],
+                        [x1, ye, below],
+                        [x0, ye, below],
+                    ],
+                    [0.0, normal, 0.0],
+                );
+            }
+        }
+    }
+    triangles
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Adds a planar quad as two triangles wound to face `normal`.
+fn push_quad(triangles: &mut Vec<Triangle>, mut quad: [[f32; 3]; 4], normal: [f32; 3]) {
+    let facing = cross(sub(quad[1], quad[0]), sub(quad[2], quad[0]));
+    if facing.iter().zip(normal).map(|(a, b)| a * b).sum::<f32>() < 0.0 {
+        quad.reverse();
+    }
+    triangles.push([quad[0], quad[1], quad[2]]);
+    triangles.push([quad[0], quad[2], quad[3]]);
+}
+
+/// Writes triangles as a binary STL file.
+///
+/// # Errors
+///
+/// Returns any I/O error from writing `path`.
+pub fn write_stl(path: &Path, triangles: &[Triangle]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&[0; 80])?;
+    let count = u32::try_from(triangles.len())
+        .map_err(|_| io::Error::other("too many triangles for STL"))?;
+    out.write_all(&count.to_le_bytes())?;
+    for triangle in triangles {
+        let normal = cross(sub(triangle[1], triangle[0]), sub(triangle[2], triangle[0]));
+        let len = normal
+            .iter()
+            .map(|v| v * v)
+            .sum::<f32>()
+            .sqrt()
+            .max(f32::EPSILON);
+        for value in normal
+            .map(|v| v / len)
+            .iter()
+            .chain(triangle.as_flattened())
+        {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        out.write_all(&[0; 2])?;
+    }
+    out.flush()
+}
+
+/// Writes triangles as a Wavefront OBJ file.
+///
+/// # Errors
+///
+/// Returns any I/O error from writing `path`.
+pub fn write_obj(path: &Path, triangles: &[Triangle]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for [x, y, z] in triangles.as_flattened() {
+        writeln!(out, "v {x} {y} {z}")?;
+    }
+    for i in 0..triangles.len() {
+        let base = i * 3;
+        writeln!(out, "f {} {} {}", base + 1, base + 2, base + 3)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,159 @@
+use rand::prelude::*;
+
+use crate::color::ramp_at;
+use crate::noise::Simplex;
+use crate::palette::Palette;
+use crate::timings::Timings;
+use crate::{Args, Pipeline, exit_on_err, reject_options, render_image};
+
+#[derive(clap::Args, Debug, Clone)]
+// There's no input image; the positional argument left is the output
+#[command(mut_arg("input", |arg| arg.required(false).hide(true).default_value("-")))]
+pub struct GenerateArgs {
+    #[command(flatten)]
+    pub args: Args,
+
+    /// Size of the generated image, e.g. "1920x1080"
+    #[arg(long, default_value = "1920x1080", value_parser = parse_size)]
+    pub size: (u32, u32),
+
+    /// Procedural color field the cells are sampled from
+    #[arg(long, value_enum, default_value_t = Field::Noise)]
+    pub field: Field,
+
+    /// Number of noise octaves layered on top of each other
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub octaves: u32,
+
+    /// Noise features across the longer side of the image in the first octave
+    #[arg(long, default_value_t = 3.0)]
+    pub frequency: f64,
+
+    /// Frequency multiplier from one octave to the next
+    #[arg(long, default_value_t = 2.0)]
+    pub lacunarity: f64,
+
+    /// Amplitude multiplier from one octave to the next
+    #[arg(long, default_value_t = 0.5)]
+    pub gain: f64,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// A linear gradient across the palette at a random angle
+    Gradient,
+    /// Fractal (fBm) simplex noise mapped onto the palette
+    Noise,
+    /// Ridged noise, folding fBm into sharp creases
+    Ridged,
+}
+
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got \"{s}\""))?;
+    let parse = |value: &str| match value.trim().parse::<u32>() {
+        Ok(0) => Err("size must be positive".to_string()),
+        Ok(value) => Ok(value),
+        Err(err) => Err(format!("invalid size \"{value}\": {err}")),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+/// Fractal Brownian motion: `octaves` layers of noise in `[-1, 1]`, each at
+/// `lacunarity` times the frequency and `gain` times the amplitude of the last.
+fn fbm(noise: &Simplex, x: f64, y: f64, generate: &GenerateArgs, ridged: bool) -> f64 {
+    let mut value = 0.0;
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    for octave in 0..generate.octaves {
+        // Shift octaves apart so their lattices don't line up at the origin
+        let offset = 17.0 * f64::from(octave);
+        let mut layer = noise.at(x * frequency + offset, y * frequency - offset);
+        if ridged {
+            layer = 1.0 - 2.0 * layer.abs();
+        }
+        value += amplitude * layer;
+        total += amplitude;
+        amplitude *= generate.gain;
+        frequency *= generate.lacunarity;
+    }
+    value / total
+}
+
+/// Paints the chosen field over the output size with colors from `stops`.
+fn color_field(generate: &GenerateArgs, stops: &[[u8; 3]], seed: u64) -> image::RgbImage {
+    let mut rng = generate.args.rng.seeded(seed);
+    let (width, height) = generate.size;
+    let (w, h) = (f64::from(width), f64::from(height));
+    match generate.field {
+        Field::Gradient => {
+            let angle = rng.random_range(0.0..std::f64::consts::TAU);
+            let (dx, dy) = (angle.cos(), angle.sin());
+            // Project the corners so the ramp spans the whole image
+            let extent = (w * dx.abs() + h * dy.abs()).max(1.0);
+            let start = (w * dx).min(0.0) + (h * dy).min(0.0);
+            image::RgbImage::from_fn(width, height, |x, y| {
+                let t = (f64::from(x) * dx + f64::from(y) * dy - start) / extent;
+                image::Rgb(ramp_at(stops, t))
+            })
+        }
+        Field::Noise | Field::Ridged => {
+            let noise = Simplex::new(&mut rng);
+            let scale = generate.frequency / w.max(h);
+            let ridged = generate.field == Field::Ridged;
+            image::RgbImage::from_fn(width, height, |x, y| {
+                let value = fbm(
+                    &noise,
+                    f64::from(x) * scale,
+                    f64::from(y) * scale,
+                    generate,
+                    ridged,
+                );
+                image::Rgb(ramp_at(stops, f64::midpoint(value, 1.0)))
+            })
+        }
+    }
+}
+
+/// Exits if `args` asks for anything that reads the input image, which
+/// `generate` paints itself.
+fn reject_input_options(args: &Args) {
+    reject_options(
+        "generate",
+        &[
+            ("--copy-metadata", args.copy_metadata),
+            ("--like", args.like.is_some()),
+            ("--pipeline", args.pipeline != Pipeline::Rgb),
+            ("--channel-weights", !args.channel_weights.is_empty()),
+            ("--channel-map", args.channel_map.is_some()),
+        ],
+    );
+}
+
+/// Renders the `generate` subcommand.
+pub fn run(generate: &GenerateArgs) {
+    reject_input_options(&generate.args);
+    let (width, height) = generate.size;
+    exit_on_err(
+        crate::check_pixels(width, height, generate.args.max_pixels),
+        "check size",
+    );
+    let mut args = generate.args.clone();
+    let seed = *args.seed.get_or_insert_with(|| rand::rng().random());
+    // The palette colors the field rather than quantizing the result
+    let stops = args
+        .palette
+        .take()
+        .unwrap_or_else(|| "gameboy".parse::<Palette>().unwrap())
+        .0;
+
+    let mut timings = Timings::default();
+    let img = timings.time("color field", || color_field(generate, &stops, seed));
+    let img = match args.symmetry {
+        Some(symmetry) => symmetry.fold(&img),
+        None => img,
+    };
+    render_image(&img, &args, timings);
+}
@@ -0,0 +1,265 @@
+//! A single-channel pipeline for grayscale inputs, which never expands them
+//! to RGB: points, scoring and output all stay in luma.
+
+use image::GrayImage;
+use image::imageops::fast_blur;
+use rand::prelude::*;
+
+use crate::colorize::CellColor;
+use crate::{
+    Args, cells, channel_pipeline_options, distance_fn, exit_on_err, kernel, labels, metadata,
+    metric, output, reject_options, seeded_rng, timings, weight_at,
+};
+
+/// Sites and pixels are scored as RGB grays, one level repeated, so they
+/// share the RGB pipeline's scores without the image being expanded.
+type Point = (u32, u32, [u8; 3]);
+
+/// Exits if `args` asks for anything the gray pipeline does not do.
+fn reject_unsupported_options(args: &Args) {
+    let mut options = channel_pipeline_options(args);
+    options.extend([
+        ("--post", args.post.is_some()),
+        ("--caption", args.caption.is_some()),
+        ("--watermark", args.watermark.is_some()),
+        ("--channel-weights", !args.channel_weights.is_empty()),
+        ("--channel-map", args.channel_map.is_some()),
+    ]);
+    reject_options("the gray pipeline", &options);
+}
+
+fn load(args: &Args) -> GrayImage {
+    let img = exit_on_err(
+        crate::open_image(&args.input, args.max_pixels),
+        "open image",
+    );
+    if img.color().has_color() {
+        eprintln!("The gray pipeline needs a grayscale input image");
+        std::process::exit(1);
+    }
+    img.into_luma8()
+}
+
+fn sample(img: &GrayImage, args: &Args) -> Vec<Point> {
+    let (width, height) = img.dimensions();
+    let draw = crate::position_sampler(args, width, height);
+    let mut rng = seeded_rng(args);
+    (0..args.points)
+        .map(|_| {
+            let (x, y) = draw(&mut rng);
+            (x, y, [img.get_pixel(x, y).0[0]; 3])
+        })
+        .collect()
+}
+
+fn assign_labels(
+    img: &GrayImage,
+    points: &[Point],
+    args: &Args,
+    metric: &metric::Metric,
+) -> Vec<u32> {
+    let blurred = fast_blur(img, args.blur);
+    let (width, height) = blurred.dimensions();
+    let max_color_dist = 255.0 * 3.0;
+    let max_pos_dist = metric.max_pos_dist();
+    let score_fn = distance_fn(args);
+    // Scoring never looks at the RGB image it is handed
+    let no_img = image::RgbImage::new(0, 0);
+    let label_of = |x: u32, y: u32| {
+        let level = blurred.get_pixel(x, y).0[0];
+        let weight = weight_at(args, (x, y), (width, height), || f64::from(level));
+        let pixel = (x, y, [level; 3]);
+        kernel::nearest(points, |point| {
+            score_fn(
+                &pixel,
+                point,
+                &no_img,
+                metric,
+                weight,
+                max_color_dist,
+                max_pos_dist,
+            )
+        })
+        .label
+    };
+    let progress = |y: u32| eprint!("\rCalculating voronoi diagram... {y} / {height} rows");
+    let labels = labels::by_rows(width, height, label_of, progress);
+    eprintln!("\rCalculating voronoi diagram... {height} / {height} rows");
+    labels
+}
+
+/// Renders a grayscale input to a grayscale diagram. Cells take their seed
+/// level with `--cell-color seed` and their average level otherwise.
+pub fn render(args: &Args) {
+    reject_unsupported_options(args);
+    crate::check_outputs(args);
+    let mut args = args.clone();
+    args.seed.get_or_insert_with(|| rand::rng().random());
+    let mut timings = timings::Timings::default();
+    let img = timings.time("load", || load(&args));
+    let (width, height) = img.dimensions();
+    let mut args = crate::with_density(&args, (width, height));
+    crate::fit_points(&mut args, (width, height));
+    println!("Image dimensions: {width}x{height}");
+    println!("Points: {}", args.points);
+    let metric = metric::Metric::new(args.projection, width, height);
+
+    let points = timings.time("sampling", || sample(&img, &args));
+    let labels = timings.time("assignment", || {
+        assign_labels(&img, &points, &args, &metric)
+    });
+    let levels = cell_levels(&img, &labels, &points, &args);
+
+    let encode = std::time::Instant::now();
+    let diagram = draw(&labels, &levels, &points, (width, height), &args);
+    let save_result = output::save(&args.output, args.force, |path| {
+        if crate::is_png(path) {
+            metadata::save_png(path, &diagram, metadata::text_chunks(&args), None)
+        } else {
+            diagram.save(path)
+        }
+    });
+    exit_on_err(save_result, "save image");
+    eprintln!("Saved voronoi diagram to {}", args.output.display());
+    timings.add("encode", encode.elapsed());
+    if args.timings {
+        timings.print();
+    }
+}
+
+/// Each cell's seed level with `--cell-color seed`, otherwise its average.
+fn cell_levels(img: &GrayImage, labels: &[u32], points: &[Point], args: &Args) -> Vec<u8> {
+    let seeds = points.iter().map(|&(_, _, [level, ..])| level);
+    if args.cell_color == CellColor::Seed {
+        return seeds.collect();
+    }
+    let (width, height) = img.dimensions();
+    let level = |x, y| [img.get_pixel(x, y).0[0]; 3];
+    let stats = cells::cell_stats_of(width, height, level, labels, points.len());
+    Iterator::zip(stats.iter(), seeds)
+        .map(|(stats, seed)| stats.average().map_or(seed, |[level, ..]| level))
+        .collect()
+}
+
+/// The cells of `labels` in their `levels`, with the sites inverted within
+/// `--point-radius`.
+fn draw(
+    labels: &[u32],
+    levels: &[u8],
+    points: &[Point],
+    (width, height): (u32, u32),
+    args: &Args,
+) -> GrayImage {
+    GrayImage::from_fn(width, height, |x, y| {
+        let label = labels[(y * width + x) as usize] as usize;
+        let (px, py, _) = points[label];
+        let on_point = args.point_radius.is_some_and(|radius| {
+            let (dx, dy) = (x.abs_diff(px), y.abs_diff(py));
+            dx * dx + dy * dy < radius * (radius - 1)
+        });
+        let level = levels[label];
+        image::Luma([if on_point { u8::MAX - level } else { level }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImagePlane, max_dists, score_fn_for, testing};
+
+    fn gray(width: u32, height: u32) -> GrayImage {
+        image::imageops::grayscale(&testing::image(width, height))
+    }
+
+    fn points(img: &GrayImage) -> Vec<Point> {
+        (0..12)
+            .map(|i| {
+                let (x, y) = (2 + i * 17 % 50, 1 + i * 23 % 36);
+                (x, y, [img.get_pixel(x, y).0[0]; 3])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn labels_match_the_rgb_pipeline_on_the_same_grays() {
+        let img = gray(52, 38);
+        let points = points(&img);
+        let rgb = image::DynamicImage::ImageLuma8(img.clone()).into_rgb8();
+        for options in [
+            &[][..],
+            &["--balance", "0.5"],
+            &["--weight-ramp", "radial:1..40"],
+        ] {
+            let args = testing::args(options);
+            let metric = metric::Metric::new(args.projection, 52, 38);
+            let (max_color_dist, max_pos_dist) = max_dists(&rgb, &args);
+            let plane = ImagePlane::new(&rgb, args.blur);
+            let expected = crate::assign_labels(
+                &plane,
+                &points,
+                max_color_dist,
+                max_pos_dist,
+                &score_fn_for(&plane, &args),
+                &args,
+            );
+            assert_eq!(
+                assign_labels(&img, &points, &args, &metric),
+                expected,
+                "{options:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cells_take_their_seed_or_average_level() {
+        let img = gray(52, 38);
+        let points = points(&img);
+        let args = testing::args(&[]);
+        let metric = metric::Metric::new(args.projection, 52, 38);
+        let labels = assign_labels(&img, &points, &args, &metric);
+
+        let seeds = cell_levels(
+            &img,
+            &labels,
+            &points,
+            &testing::args(&["--cell-color", "seed"]),
+        );
+        assert!(Iterator::eq(seeds.iter(), points.iter().map(|p| &p.2[0])));
+
+        let averages = cell_levels(
+            &img,
+            &labels,
+            &points,
+            &testing::args(&["--cell-color", "average"]),
+        );
+        for (cell, &average) in averages.iter().enumerate() {
+            let levels: Vec<u32> = Iterator::zip(img.pixels(), &labels)
+                .filter(|&(_, &label)| label as usize == cell)
+                .map(|(pixel, _)| u32::from(pixel.0[0]))
+                .collect();
+            let count = u32::try_from(levels.len()).unwrap();
+            let sum: u32 = levels.iter().sum();
+            assert_eq!(u32::from(average), (sum + count / 2) / count, "cell {cell}");
+        }
+    }
+
+    #[test]
+    fn point_radius_inverts_the_sites() {
+        let img = gray(52, 38);
+        let points = points(&img);
+        let labels = vec![0; 52 * 38];
+        let levels = vec![40; points.len()];
+        let plain = draw(&labels, &levels, &points, (52, 38), &testing::args(&[]));
+        let marked = draw(
+            &labels,
+            &levels,
+            &points,
+            (52, 38),
+            &testing::args(&["--point-radius", "2"]),
+        );
+        let (x, y, _) = points[0];
+        assert_eq!(plain.get_pixel(x, y).0, [40]);
+        assert_eq!(marked.get_pixel(x, y).0, [215]);
+        assert_eq!(marked.get_pixel(x + 3, y).0, [40]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Greedy point placement for `--sampler greedy`: every new site goes
+//! where the picture drawn from the sites so far is most wrong.
+//!
+//! The running picture colors each pixel like its nearest site, so a new
+//! site only changes the pixels of its own cell. Those are found by flood
+//! fill from the site, since a Voronoi cell is convex, and the largest
+//! error is kept per block of pixels so finding the next site costs a scan
+//! of the blocks rather than of the image.
+
+use image::imageops::fast_blur;
+
+use crate::color::to_u8;
+
+type Point = (u32, u32, [u8; 3]);
+
+/// Edge length of the blocks whose largest error is tracked.
+const BLOCK: u32 = 32;
+
+/// Squared RGB distance between two colors.
+fn error(a: [u8; 3], b: [u8; 3]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| (f64::from(a) - f64::from(b)).powi(2))
+        .sum()
+}
+
+/// The running picture of a `width` by `height` target.
+struct Approximation<'a> {
+    target: &'a image::RgbImage,
+    width: u32,
+    /// Squared distance from each pixel to its nearest site so far
+    distances: Vec<f64>,
+    /// Error of each pixel's color in the picture
+    errors: Vec<f64>,
+    /// Index of the pixel with the largest error in each block
+    block_worst: Vec<usize>,
+    blocks_across: u32,
+}
+
+impl<'a> Approximation<'a> {
+    /// The picture before any site: every pixel the mean color of `target`.
+    fn new(target: &'a image::RgbImage) -> Self {
+        let (width, height) = target.dimensions();
+        let mut sums = [0.0; 3];
+        for pixel in target.pixels() {
+            for (sum, c) in sums.iter_mut().zip(pixel.0) {
+                *sum += f64::from(c);
+            }
+        }
+        let pixels = f64::from(width) * f64::from(height);
+        let mean = sums.map(|sum| to_u8(sum / pixels));
+        let blocks_across = width.div_ceil(BLOCK);
+        let mut approximation = Self {
+            target,
+            width,
+            distances: vec![f64::INFINITY; (width * height) as usize],
+            errors: target.pixels().map(|pixel| error(pixel.0, mean)).collect(),
+            block_worst: vec![0; (blocks_across * height.div_ceil(BLOCK)) as usize],
+            blocks_across,
+        };
+        for block in 0..approximation.block_worst.len() {
+            approximation.update_block(block);
+        }
+        approximation
+    }
+
+    fn block_of(&self, pixel: usize) -> usize {
+        let width = self.width as usize;
+        let across = self.blocks_across as usize;
+        let block = BLOCK as usize;
+        (pixel / width / block) * across + (pixel % width) / block
+    }
+
+    /// Finds the worst pixel of block `block` again.
+    #[allow(clippy::cast_possible_truncation)]
+    fn update_block(&mut self, block: usize) {
+        let (bx, by) = (
+            block as u32 % self.blocks_across,
+            block as u32 / self.blocks_across,
+        );
+        let (x0, y0) = (bx * BLOCK, by * BLOCK);
+        let (x1, y1) = (
+            (x0 + BLOCK).min(self.width),
+            (y0 + BLOCK).min(self.target.height()),
+        );
+        let mut worst = (y0 * self.width + x0) as usize;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let pixel = (y * self.width + x) as usize;
+                if self.errors[pixel] > self.errors[worst] {
+                    worst = pixel;
+                }
+            }
+        }
+        self.block_worst[block] = worst;
+    }
+
+    /// The pixel the picture gets most wrong.
+    fn worst(&self) -> usize {
+        self.block_worst
+            .iter()
+            .copied()
+            .max_by(|&a, &b| self.errors[a].total_cmp(&self.errors[b]))
+            .expect("an image has at least one block")
+    }
+
+    /// Adds a site at pixel `site`, recoloring the pixels nearer to it
+    /// than to any site before.
+    fn add_site(&mut self, site: usize) {
+        let width = self.width as usize;
+        let height = self.target.height() as usize;
+        let (sx, sy) = (site % width, site / width);
+        let color = self.target.as_raw()[site * 3..site * 3 + 3]
+            .try_into()
+            .expect("three channels");
+        #[allow(clippy::cast_precision_loss)]
+        let distance = |pixel: usize| {
+            let (x, y) = ((pixel % width) as f64, (pixel / width) as f64);
+            (x - sx as f64).powi(2) + (y - sy as f64).powi(2)
+        };
+        let mut touched = vec![false; self.block_worst.len()];
+        let mut stack = vec![site];
+        self.distances[site] = -1.0;
+        while let Some(pixel) = stack.pop() {
+            self.distances[pixel] = distance(pixel);
+            let offset = pixel * 3;
+            let target = self.target.as_raw()[offset..offset + 3]
+                .try_into()
+                .expect("three channels");
+            self.errors[pixel] = error(target, color);
+            let block = self.block_of(pixel);
+            touched[block] = true;
+            let (x, y) = (pixel % width, pixel / width);
+            let sides = [
+                (x > 0).then(|| pixel - 1),
+                (x + 1 < width).then_some(pixel + 1),
+                (y > 0).then(|| pixel - width),
+                (y + 1 < height).then_some(pixel + width),
+            ];
+            for next in sides.into_iter().flatten() {
+                if distance(next) < self.distances[next] {
+                    // Marks the pixel as queued until it is visited
+                    self.distances[next] = -1.0;
+                    stack.push(next);
+                }
+            }
+        }
+        for (block, touched) in touched.into_iter().enumerate() {
+            if touched {
+                self.update_block(block);
+            }
+        }
+    }
+}
+
+/// Places `count` points one at a time, each at the pixel of `img` blurred
+/// by `blur` that the nearest-site picture of the points before gets most
+/// wrong.
+#[must_use]
+pub fn sample(img: &image::RgbImage, blur: f32, count: usize, print_progress: bool) -> Vec<Point> {
+    let target = if blur > 0.0 {
+        fast_blur(img, blur)
+    } else {
+        img.clone()
+    };
+    let mut approximation = Approximation::new(&target);
+    let width = img.width() as usize;
+    let mut points = Vec::with_capacity(count);
+    for i in 0..count {
+        if print_progress && i % 64 == 0 {
+            eprint!("\rPlacing points greedily... {i} / {count}");
+        }
+        let pixel = approximation.worst();
+        approximation.add_site(pixel);
+        #[allow(clippy::cast_possible_truncation)]
+        let (x, y) = ((pixel % width) as u32, (pixel / width) as u32);
+        points.push((x, y, img.get_pixel(x, y).0));
+    }
+    if print_progress {
+        eprintln!("\rPlacing points greedily... {count} / {count}");
+    }
+    points
+}
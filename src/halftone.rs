@@ -0,0 +1,70 @@
+//! Print-style halftoning: a rotated grid of dots per ink, sized by how much
+//! of that ink the image needs there.
+
+use image::imageops::fast_blur;
+
+/// Screen angle in degrees and color of each ink, in the customary angles
+/// that keep the grids from beating against each other.
+const INKS: [(f64, [u8; 3]); 4] = [
+    (15.0, [0, 255, 255]),
+    (75.0, [255, 0, 255]),
+    (0.0, [255, 255, 0]),
+    (45.0, [0, 0, 0]),
+];
+
+/// Cyan, magenta, yellow and black coverage in `0..=1` of an RGB color.
+fn cmyk(rgb: [f64; 3]) -> [f64; 4] {
+    let black = 1.0 - rgb.into_iter().fold(0.0, f64::max);
+    if black >= 1.0 {
+        return [0.0, 0.0, 0.0, 1.0];
+    }
+    let [cyan, magenta, yellow] =
+        rgb.map(|value| ((1.0 - value - black) / (1.0 - black)).clamp(0.0, 1.0));
+    [cyan, magenta, yellow, black]
+}
+
+/// `img` as overlapping dot screens with about `points` dots per ink, each
+/// dot covering as much of its grid cell as the ink covers there.
+#[must_use]
+pub fn render(img: &image::RgbImage, points: usize) -> image::RgbImage {
+    let (width, height) = img.dimensions();
+    #[allow(clippy::cast_precision_loss)]
+    let spacing = (f64::from(width) * f64::from(height) / points.max(1) as f64)
+        .sqrt()
+        .max(2.0);
+    // Each dot takes its ink from the image averaged over about its cell
+    #[allow(clippy::cast_possible_truncation)]
+    let smoothed = fast_blur(img, (spacing / 2.0) as f32);
+    let coverage = |x: f64, y: f64| {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (x, y) = (
+            x.clamp(0.0, f64::from(width - 1)) as u32,
+            y.clamp(0.0, f64::from(height - 1)) as u32,
+        );
+        cmyk(smoothed.get_pixel(x, y).0.map(|v| f64::from(v) / 255.0))
+    };
+
+    let mut out = image::RgbImage::from_pixel(width, height, image::Rgb([255; 3]));
+    for (ink, &(angle, color)) in INKS.iter().enumerate() {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        for (x, y, pixel) in out.enumerate_pixels_mut() {
+            let (px, py) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+            // Nearest grid site, found in the screen's rotated frame
+            let (u, v) = (px * cos + py * sin, py * cos - px * sin);
+            let (gu, gv) = (
+                (u / spacing).round() * spacing,
+                (v / spacing).round() * spacing,
+            );
+            let (sx, sy) = (gu * cos - gv * sin, gu * sin + gv * cos);
+            let radius = spacing * (coverage(sx, sy)[ink] / std::f64::consts::PI).sqrt();
+            let distance = (u - gu).hypot(v - gv);
+            // Antialias the rim of the dot over a pixel
+            let alpha = (radius - distance + 0.5).clamp(0.0, 1.0);
+            for (channel, ink) in pixel.0.iter_mut().zip(color) {
+                let filter = 1.0 - alpha * (1.0 - f64::from(ink) / 255.0);
+                *channel = crate::color::to_u8(f64::from(*channel) * filter);
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,147 @@
+//! Cells within cells: finer diagrams grown inside each cell of a coarser one.
+
+use rand::Rng;
+
+use crate::plane::ImagePlane;
+use crate::{Args, Diagram, ScoreFn, cells, color, color_weight, distance, metric};
+
+type Point = (u32, u32, [u8; 3]);
+
+/// How strongly the boundaries of the coarsest level are drawn; each finer
+/// level is drawn at half the strength of the one above it.
+const OUTLINE_STRENGTH: f64 = 0.8;
+
+/// Splits every cell of `labels` into up to `--level-points` finer cells,
+/// grown from points picked among its own pixels and competing only with
+/// each other. Cells no more detailed than `--level-detail` stay whole.
+#[allow(clippy::too_many_arguments)]
+fn split(
+    blurred: &image::RgbImage,
+    labels: &[u32],
+    points: &[Point],
+    stats: &[cells::CellStats],
+    max_dists: (f64, f64),
+    score_fn: &ScoreFn,
+    args: &Args,
+    rng: &mut impl Rng,
+) -> (Vec<u32>, Vec<Point>) {
+    let width = blurred.width();
+    let metric = metric::Metric::new(args.projection, width, blurred.height());
+    let mut members: Vec<Vec<u32>> = vec![Vec::new(); points.len()];
+    for (i, &label) in (0..).zip(labels) {
+        members[label as usize].push(i);
+    }
+
+    let mut children = Vec::new();
+    let mut split_labels = vec![0; labels.len()];
+    for ((pixels, &parent), stats) in members.iter().zip(points).zip(stats) {
+        let first = u32::try_from(children.len()).unwrap_or(u32::MAX);
+        let detailed = args
+            .level_detail
+            .is_none_or(|threshold| stats.color_variance().is_some_and(|v| v > threshold));
+        if !detailed || pixels.len() < 2 {
+            children.push(parent);
+            for &i in pixels {
+                split_labels[i as usize] = first;
+            }
+            continue;
+        }
+        let own: Vec<Point> = (0..args.level_points)
+            .map(|_| {
+                let i = pixels[rng.random_range(0..pixels.len())];
+                let (x, y) = (i % width, i / width);
+                (x, y, blurred.get_pixel(x, y).0)
+            })
+            .collect();
+        for &i in pixels {
+            let (x, y) = (i % width, i / width);
+            let pixel = (x, y, blurred.get_pixel(x, y).0);
+            let weight = color_weight(args, blurred, x, y);
+            let scores = own.iter().map(|child| {
+                score_fn(
+                    &pixel,
+                    child,
+                    blurred,
+                    &metric,
+                    weight,
+                    max_dists.0,
+                    max_dists.1,
+                )
+            });
+            let nearest = (0..)
+                .zip(scores)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(0, |(child, _)| child);
+            split_labels[i as usize] = first + nearest;
+        }
+        children.extend(own);
+    }
+    (split_labels, children)
+}
+
+/// Subdivides `top` down to `--levels` levels and renders the finest cells
+/// with the boundaries of every level drawn over them, coarser ones darker.
+/// Returns the finest diagram and its points.
+pub fn subdivide(
+    img: &ImagePlane,
+    top: Diagram,
+    mut points: Vec<Point>,
+    max_dists: (f64, f64),
+    score_fn: &ScoreFn,
+    args: &Args,
+    rng: &mut impl Rng,
+) -> (Diagram, Vec<Point>) {
+    let (width, height) = img.dimensions();
+    let mut timings = top.timings;
+    let blurred = timings.time("blur", || img.blurred());
+    let mut levels = vec![top.labels];
+    let mut stats = top.stats;
+    let subdivision = std::time::Instant::now();
+    for level in 2..=args.levels {
+        eprint!("\rSubdividing cells... level {level} / {}", args.levels);
+        let (labels, children) = split(
+            blurred,
+            levels.last().unwrap_or(&Vec::new()),
+            &points,
+            &stats,
+            max_dists,
+            score_fn,
+            args,
+            rng,
+        );
+        stats = cells::cell_stats(img, &labels, children.len());
+        points = children;
+        levels.push(labels);
+    }
+    eprintln!("\rSubdividing cells... level {0} / {0}", args.levels);
+    timings.add("subdivision", subdivision.elapsed());
+
+    let labels = levels.pop().unwrap_or_default();
+    let colors = args.colorizer.colors(img, &labels, &stats, &points, args);
+    let mut image = image::RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb(colors[labels[(y * width + x) as usize] as usize])
+    });
+    // Coarser boundaries go on top, so where levels share an edge it stays dark
+    let outlines = std::iter::once(&labels).chain(levels.iter().rev());
+    let strengths = (0..=levels.len())
+        .rev()
+        .map(|depth| OUTLINE_STRENGTH * 0.5_f64.powi(i32::try_from(depth).unwrap_or(i32::MAX)));
+    for (level, strength) in outlines.zip(strengths) {
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if distance::is_boundary(level, width, height, x, y) {
+                pixel.0 = color::blend(pixel.0, [0; 3], strength);
+            }
+        }
+    }
+    (
+        Diagram {
+            image,
+            labels,
+            confidence: None,
+            colors,
+            stats,
+            timings,
+        },
+        points,
+    )
+}
@@ -0,0 +1,228 @@
+//! Sites ranked by how much of the image each one carries, so that the
+//! first k of them make about the best k-site picture there is: the `.vor`
+//! site order, `animate --reveal` and `--points auto`.
+//!
+//! Ranking runs backwards: starting from every site, it keeps removing the
+//! one whose pixels lose the least color accuracy going to their nearest
+//! remaining neighbour, and the last site standing ranks first.
+
+use crate::{Args, treemap};
+
+/// Share of the error reduction its largest candidate diagram achieves that
+/// `--points auto` asks of the count it picks.
+const AUTO_SHARE: f64 = 0.9;
+
+/// Largest edge of the preview `--points auto` ranks candidates on, and
+/// pixels per candidate site.
+const AUTO_PREVIEW_SIZE: u32 = 256;
+const AUTO_PIXELS_PER_SITE: usize = 24;
+
+/// Sites in order of importance, with the error of each prefix.
+#[derive(Debug, Clone)]
+pub struct Ranking {
+    /// Site indices, most important first
+    pub order: Vec<usize>,
+    /// Squared RGB error, summed over the pixels, of drawing each pixel in
+    /// the color of its nearest site among the first `k + 1` of `order`
+    pub errors: Vec<f64>,
+}
+
+/// Squared RGB distance between two colors.
+fn error(a: [u8; 3], b: [u8; 3]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| (f64::from(a) - f64::from(b)).powi(2))
+        .sum()
+}
+
+/// A diagram losing one site at a time.
+struct Shrinking<'a> {
+    img: &'a image::RgbImage,
+    positions: &'a [(f64, f64)],
+    colors: &'a [[u8; 3]],
+    labels: Vec<u32>,
+    /// Pixel indices of every site's cell
+    cells: Vec<Vec<usize>>,
+}
+
+impl Shrinking<'_> {
+    /// Sites whose cells touch the cell of `site`.
+    fn neighbours(&self, site: usize) -> Vec<usize> {
+        let (width, height) = (self.img.width() as usize, self.img.height() as usize);
+        let mut neighbours = Vec::new();
+        for &pixel in &self.cells[site] {
+            let (x, y) = (pixel % width, pixel / width);
+            let sides = [
+                (x > 0).then(|| pixel - 1),
+                (x + 1 < width).then_some(pixel + 1),
+                (y > 0).then(|| pixel - width),
+                (y + 1 < height).then_some(pixel + width),
+            ];
+            for other in sides.into_iter().flatten() {
+                let label = self.labels[other] as usize;
+                if label != site && !neighbours.contains(&label) {
+                    neighbours.push(label);
+                }
+            }
+        }
+        neighbours
+    }
+
+    /// The nearest of `candidates` to pixel `pixel`.
+    fn nearest(&self, pixel: usize, candidates: &[usize]) -> usize {
+        let width = self.img.width() as usize;
+        #[allow(clippy::cast_precision_loss)]
+        let (px, py) = ((pixel % width) as f64 + 0.5, (pixel / width) as f64 + 0.5);
+        let distance = |site: &usize| {
+            let (sx, sy) = self.positions[*site];
+            (px - sx).powi(2) + (py - sy).powi(2)
+        };
+        *candidates
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .expect("a site being removed has a neighbour")
+    }
+
+    /// Color of pixel `pixel` of the image.
+    fn pixel(&self, pixel: usize) -> [u8; 3] {
+        let width = self.img.width() as usize;
+        #[allow(clippy::cast_possible_truncation)]
+        self.img
+            .get_pixel((pixel % width) as u32, (pixel / width) as u32)
+            .0
+    }
+
+    /// How much the error grows if `site` goes: nothing for a site without
+    /// pixels, and infinity for the last site with any.
+    fn removal_cost(&self, site: usize) -> f64 {
+        if self.cells[site].is_empty() {
+            return 0.0;
+        }
+        let neighbours = self.neighbours(site);
+        if neighbours.is_empty() {
+            return f64::INFINITY;
+        }
+        self.cells[site]
+            .iter()
+            .map(|&pixel| {
+                let color = self.pixel(pixel);
+                let heir = self.nearest(pixel, &neighbours);
+                error(color, self.colors[heir]) - error(color, self.colors[site])
+            })
+            .sum()
+    }
+
+    /// Hands the pixels of `site` to their nearest neighbours, returning
+    /// the neighbours.
+    fn remove(&mut self, site: usize) -> Vec<usize> {
+        let neighbours = self.neighbours(site);
+        for pixel in std::mem::take(&mut self.cells[site]) {
+            let heir = self.nearest(pixel, &neighbours);
+            self.labels[pixel] = u32::try_from(heir).unwrap_or(u32::MAX);
+            self.cells[heir].push(pixel);
+        }
+        neighbours
+    }
+}
+
+/// Ranks the sites at `positions`, drawn in `colors`, by how much leaving
+/// each out of the diagram of `img` would cost.
+#[must_use]
+pub fn rank(img: &image::RgbImage, positions: &[(f64, f64)], colors: &[[u8; 3]]) -> Ranking {
+    let (width, height) = img.dimensions();
+    let labels = treemap::power_labels(width, height, positions, &vec![0.0; positions.len()]);
+    let mut cells = vec![Vec::new(); positions.len()];
+    for (pixel, &label) in labels.iter().enumerate() {
+        cells[label as usize].push(pixel);
+    }
+    let mut total: f64 = labels
+        .iter()
+        .zip(img.pixels())
+        .map(|(&label, pixel)| error(pixel.0, colors[label as usize]))
+        .sum();
+    let mut diagram = Shrinking {
+        img,
+        positions,
+        colors,
+        labels,
+        cells,
+    };
+
+    let mut costs: Vec<f64> = (0..positions.len())
+        .map(|site| diagram.removal_cost(site))
+        .collect();
+    let mut alive = vec![true; positions.len()];
+    let mut order = Vec::with_capacity(positions.len());
+    let mut errors = Vec::with_capacity(positions.len());
+    for removed in 0..positions.len() {
+        eprint!("\rRanking sites... {removed} / {}", positions.len());
+        let site = (0..positions.len())
+            .filter(|&site| alive[site])
+            .min_by(|&a, &b| costs[a].total_cmp(&costs[b]))
+            .expect("a site is left");
+        order.push(site);
+        errors.push(total);
+        alive[site] = false;
+        if removed + 1 == positions.len() {
+            break;
+        }
+        total += costs[site];
+        for neighbour in diagram.remove(site) {
+            costs[neighbour] = diagram.removal_cost(neighbour);
+        }
+    }
+    eprintln!("\rRanking sites... Done");
+    order.reverse();
+    errors.reverse();
+    Ranking { order, errors }
+}
+
+/// How many points to use for `--points auto`: on a small preview of
+/// `img`, the fewest of a generous set of ranked candidates that get
+/// most of the way to the candidates' accuracy, scaled up to the size of
+/// `img`.
+#[must_use]
+pub fn auto_points(img: &image::RgbImage, args: &Args) -> usize {
+    let (width, height) = img.dimensions();
+    let preview = if width.max(height) > AUTO_PREVIEW_SIZE {
+        let shrink = f64::from(AUTO_PREVIEW_SIZE) / f64::from(width.max(height));
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let size = |side: u32| ((f64::from(side) * shrink).round() as u32).max(1);
+        image::imageops::thumbnail(img, size(width), size(height))
+    } else {
+        img.clone()
+    };
+    let pixels = |img: &image::RgbImage| img.width() as usize * img.height() as usize;
+    let budget = (pixels(&preview) / AUTO_PIXELS_PER_SITE).max(1);
+    let seed = args.seed.unwrap_or_default();
+    let sites = crate::sample_points(&preview, args, budget, &mut args.rng.seeded(seed));
+    let positions: Vec<(f64, f64)> = sites
+        .iter()
+        .map(|&(x, y, _)| (f64::from(x) + 0.5, f64::from(y) + 0.5))
+        .collect();
+    let colors: Vec<[u8; 3]> = sites.iter().map(|&(_, _, color)| color).collect();
+    let ranking = rank(&preview, &positions, &colors);
+
+    let (first, last) = (ranking.errors[0], ranking.errors[budget - 1]);
+    let enough = last + (1.0 - AUTO_SHARE) * (first - last);
+    let count = 1 + ranking
+        .errors
+        .iter()
+        .position(|&error| error <= enough)
+        .unwrap_or(budget - 1);
+    #[allow(clippy::cast_precision_loss)]
+    let scale = pixels(img) as f64 / pixels(&preview) as f64;
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    let points = ((count as f64 * scale).round() as usize).max(1);
+    println!(
+        "Auto points: {points}, as the first {count} of {budget} ranked sites on a {}x{} preview reach {:.0}% of their error reduction",
+        preview.width(),
+        preview.height(),
+        AUTO_SHARE * 100.0
+    );
+    points
+}
@@ -0,0 +1,333 @@
+//! Diagrams that can be edited one site at a time, re-rendering only the
+//! pixels an edit can affect.
+
+use image::imageops::fast_blur;
+
+use crate::plane::ImagePlane;
+use crate::{Args, ScoreFn, Source, cells, color_weight, kernel, max_dists, metric, score_fn_for};
+
+type Point = (u32, u32, [u8; 3]);
+
+/// An inclusive pixel rectangle `(x0, y0, x1, y1)`.
+pub type Rect = (u32, u32, u32, u32);
+
+/// The smallest rectangle holding `rect` and `(x, y)`.
+fn grow(rect: Option<Rect>, x: u32, y: u32) -> Rect {
+    rect.map_or((x, y, x, y), |(x0, y0, x1, y1)| {
+        (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+    })
+}
+
+/// The smallest rectangle holding both `a` and `b`.
+#[must_use]
+pub fn union(a: Option<Rect>, b: Option<Rect>) -> Option<Rect> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))),
+        _ => a.or(b),
+    }
+}
+
+/// A diagram kept up to date as sites are added, moved and removed.
+///
+/// Besides the label of every pixel it remembers the pixel's score against
+/// its owner and a bounding box around each site's pixels (its influence
+/// region), so an added or moved site only has to be scored against every
+/// pixel once, and only the pixels of a removed or moved site need all sites
+/// scored again. Cells are drawn flat; styles and effects are left to a full
+/// render of the final points.
+pub struct Editor<'a> {
+    img: image::RgbImage,
+    scored: image::RgbImage,
+    filled: image::RgbImage,
+    metric: metric::Metric,
+    max_dists: (f64, f64),
+    score_fn: Box<ScoreFn>,
+    args: &'a Args,
+    points: Vec<Point>,
+    labels: Vec<u32>,
+    /// Score of each pixel against the site owning it
+    scores: Vec<f64>,
+    /// Bounding box of each site's pixels; never smaller than the region,
+    /// though it may be larger after the site loses pixels
+    bounds: Vec<Option<Rect>>,
+    stats: Vec<cells::CellStats>,
+    colors: Vec<[u8; 3]>,
+    image: image::RgbImage,
+}
+
+impl<'a> Editor<'a> {
+    /// Assigns every pixel of `img` to its best site among `points`.
+    pub fn new(img: image::RgbImage, points: Vec<Point>, args: &'a Args) -> Self {
+        let (width, height) = img.dimensions();
+        let blurred = [args.score_on, args.fill_from]
+            .contains(&Source::Blurred)
+            .then(|| fast_blur(&img, args.blur));
+        let source = |source| match (source, &blurred) {
+            (Source::Blurred, Some(blurred)) => blurred.clone(),
+            _ => img.clone(),
+        };
+        let (scored, filled) = (source(args.score_on), source(args.fill_from));
+        let pixels = (width * height) as usize;
+        let sites = points.len();
+        let mut editor = Self {
+            metric: metric::Metric::new(args.projection, width, height),
+            max_dists: max_dists(&img, args),
+            score_fn: score_fn_for(&ImagePlane::new(&img, args.blur), args),
+            img,
+            scored,
+            filled,
+            args,
+            points,
+            labels: vec![0; pixels],
+            scores: vec![f64::INFINITY; pixels],
+            bounds: vec![None; sites],
+            stats: vec![cells::CellStats::default(); sites],
+            colors: Vec::new(),
+            image: image::RgbImage::new(width, height),
+        };
+        // One pass over every site, so each site's bounds only ever grow
+        // around the pixels it ends up with
+        for index in 0..pixels {
+            let nearest = kernel::nearest(0..sites, |site| editor.score(index, site));
+            if nearest.score < f64::MAX {
+                editor.relabel(index, nearest.label as usize, nearest.score);
+            }
+        }
+        editor.refresh(Some((0, 0, width - 1, height - 1)), true);
+        editor
+    }
+
+    #[must_use]
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// The site whose cell holds pixel `(x, y)`.
+    #[must_use]
+    pub fn site_at(&self, x: u32, y: u32) -> usize {
+        self.labels[(y * self.img.width() + x) as usize] as usize
+    }
+
+    /// The diagram as it stands after the last edit.
+    #[must_use]
+    pub fn image(&self) -> &image::RgbImage {
+        &self.image
+    }
+
+    /// Coordinates of the pixel at `index` in row-major order.
+    #[allow(clippy::cast_possible_truncation)]
+    fn position(&self, index: usize) -> (u32, u32) {
+        let width = self.img.width();
+        (index as u32 % width, index as u32 / width)
+    }
+
+    fn score(&self, index: usize, site: usize) -> f64 {
+        let (x, y) = self.position(index);
+        (self.score_fn)(
+            &(x, y, self.scored.get_pixel(x, y).0),
+            &self.points[site],
+            &self.img,
+            &self.metric,
+            color_weight(self.args, &self.scored, x, y),
+            self.max_dists.0,
+            self.max_dists.1,
+        )
+    }
+
+    /// Hands pixel `index` to `site`, which scores `score` on it.
+    fn relabel(&mut self, index: usize, site: usize, score: f64) {
+        let (x, y) = self.position(index);
+        let pixel = self.filled.get_pixel(x, y).0;
+        let old = self.labels[index] as usize;
+        if self.scores[index].is_finite() {
+            self.stats[old].remove(x, y, pixel);
+        }
+        self.stats[site].add(x, y, pixel);
+        self.labels[index] = u32::try_from(site).unwrap_or(u32::MAX);
+        self.scores[index] = score;
+        self.bounds[site] = Some(grow(self.bounds[site], x, y));
+    }
+
+    /// Gives `site` every pixel it scores better on than the pixel's owner.
+    /// Returns the rectangle around the pixels that changed hands.
+    fn claim(&mut self, site: usize) -> Option<Rect> {
+        let mut changed = None;
+        for index in 0..self.labels.len() {
+            let score = self.score(index, site);
+            if score < self.scores[index] {
+                self.relabel(index, site, score);
+                let (x, y) = self.position(index);
+                changed = Some(grow(changed, x, y));
+            }
+        }
+        changed
+    }
+
+    /// Hands each pixel of `site` to its best site, skipping `site` itself
+    /// when `exclude` is set. Returns the rectangle around those pixels.
+    fn release(&mut self, site: usize, exclude: bool) -> Option<Rect> {
+        let width = self.img.width();
+        let (x0, y0, x1, y1) = self.bounds[site]?;
+        let mut changed = None;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let index = (y * width + x) as usize;
+                if self.labels[index] as usize != site {
+                    continue;
+                }
+                let best = (0..self.points.len())
+                    .filter(|&other| !exclude || other != site)
+                    .map(|other| (other, self.score(index, other)))
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                if let Some((other, score)) = best {
+                    self.relabel(index, other, score);
+                }
+                changed = Some(grow(changed, x, y));
+            }
+        }
+        if exclude {
+            self.bounds[site] = None;
+        }
+        changed
+    }
+
+    /// The site at `(x, y)` with its color picked the way sampling does.
+    fn site(&self, x: u32, y: u32) -> Point {
+        let point = (x, y, self.scored.get_pixel(x, y).0);
+        self.args.seed_color.apply(&self.img, &[point])[0]
+    }
+
+    /// Adds a site at `(x, y)`. Returns the rectangle that was re-rendered.
+    pub fn add(&mut self, x: u32, y: u32) -> Option<Rect> {
+        self.points.push(self.site(x, y));
+        self.bounds.push(None);
+        self.stats.push(cells::CellStats::default());
+        let changed = self.claim(self.points.len() - 1);
+        self.refresh(changed, false)
+    }
+
+    /// Moves site `site` to `(x, y)`. Returns the rectangle that was
+    /// re-rendered.
+    pub fn move_site(&mut self, site: usize, x: u32, y: u32) -> Option<Rect> {
+        self.set_site(site, self.site(x, y))
+    }
+
+    /// Replaces site `site` with `point`, position and color as given.
+    /// Returns the rectangle that was re-rendered.
+    pub fn set_site(&mut self, site: usize, point: Point) -> Option<Rect> {
+        self.points[site] = point;
+        let released = self.release(site, false);
+        let claimed = self.claim(site);
+        self.refresh(union(released, claimed), false)
+    }
+
+    /// Removes site `site`; the last site takes over its index. Returns the
+    /// rectangle that was re-rendered.
+    pub fn remove(&mut self, site: usize) -> Option<Rect> {
+        if self.points.len() < 2 {
+            return None;
+        }
+        let changed = self.release(site, true);
+        let last = self.points.len() - 1;
+        if site != last
+            && let Some((x0, y0, x1, y1)) = self.bounds[last]
+        {
+            let width = self.img.width();
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    let index = (y * width + x) as usize;
+                    if self.labels[index] as usize == last {
+                        self.labels[index] = u32::try_from(site).unwrap_or(u32::MAX);
+                    }
+                }
+            }
+        }
+        self.points.swap_remove(site);
+        self.bounds.swap_remove(site);
+        self.stats.swap_remove(site);
+        // Colors picked by index may all shift; refresh redraws those cells
+        self.refresh(changed, false)
+    }
+
+    /// Picks the cell colors again and redraws the pixels in `changed` plus
+    /// the cells whose color changed. Returns the rectangle redrawn.
+    fn refresh(&mut self, changed: Option<Rect>, all: bool) -> Option<Rect> {
+        let colors = self.args.colorizer.colors(
+            &self.filled,
+            &self.labels,
+            &self.stats,
+            &self.points,
+            self.args,
+        );
+        let mut dirty = changed;
+        for (site, color) in colors.iter().enumerate() {
+            if all || self.colors.get(site) != Some(color) {
+                dirty = union(dirty, self.bounds[site]);
+            }
+        }
+        self.colors = colors;
+        let (x0, y0, x1, y1) = dirty?;
+        let width = self.img.width();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let label = self.labels[(y * width + x) as usize] as usize;
+                self.image.put_pixel(x, y, image::Rgb(self.colors[label]));
+            }
+        }
+        dirty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// `img` rendered from scratch with `points`, to hold edits against.
+    fn full_render(img: &image::RgbImage, points: &[Point], args: &Args) -> image::RgbImage {
+        let (max_color_dist, max_pos_dist) = max_dists(img, args);
+        let plane = ImagePlane::new(img, args.blur);
+        let score_fn = score_fn_for(&plane, args);
+        crate::generate_voronoi(
+            &plane,
+            points,
+            max_color_dist,
+            max_pos_dist,
+            &score_fn,
+            args,
+        )
+        .image
+    }
+
+    fn editor<'a>(img: &image::RgbImage, args: &'a Args) -> Editor<'a> {
+        let points = (0..10)
+            .map(|i| {
+                let (x, y) = (3 + i * 13 % 60, 2 + i * 29 % 44);
+                (x, y, img.get_pixel(x, y).0)
+            })
+            .collect();
+        Editor::new(img.clone(), points, args)
+    }
+
+    #[test]
+    fn edits_match_a_full_render() {
+        let (img, args) = (testing::image(64, 48), testing::args(&["--seed", "1"]));
+        let mut editor = editor(&img, &args);
+        assert_eq!(*editor.image(), full_render(&img, editor.points(), &args));
+        editor.move_site(0, 40, 30);
+        assert_eq!(*editor.image(), full_render(&img, editor.points(), &args));
+        editor.remove(3);
+        assert_eq!(*editor.image(), full_render(&img, editor.points(), &args));
+        editor.add(10, 40);
+        assert_eq!(*editor.image(), full_render(&img, editor.points(), &args));
+    }
+
+    #[test]
+    fn early_sites_redraw_only_near_their_cells() {
+        let (img, args) = (testing::image(64, 48), testing::args(&["--seed", "1"]));
+        let mut editor = editor(&img, &args);
+        let (x, y, _) = editor.points()[0];
+        let (x0, y0, x1, y1) = editor.move_site(0, x + 1, y).expect("the move redraws");
+        assert!((x1 - x0 + 1) * (y1 - y0 + 1) < 64 * 48 / 2);
+    }
+}
@@ -0,0 +1,103 @@
+//! Jigsaw puzzles cut along cell boundaries.
+
+use std::fmt::Write;
+
+use rand::Rng;
+
+use crate::cells::CellStats;
+use crate::polygon;
+
+/// Edges shorter than this many pixels are cut straight, without a tab.
+const MIN_TAB_EDGE: f64 = 12.0;
+
+/// Appends a tab to `path` along the segment from `a` to `b`, bulging to the
+/// left of the direction of travel when `side` is 1 and to the right when -1.
+fn push_tab(path: &mut String, a: (f64, f64), b: (f64, f64), side: f64) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = dx.hypot(dy);
+    let (ux, uy) = (dx / len, dy / len);
+    let (nx, ny) = (uy * side, -ux * side);
+    let width = (len * 0.4).min(40.0);
+    let height = width * 0.8;
+    let mid = (f64::midpoint(a.0, b.0), f64::midpoint(a.1, b.1));
+    // Local (along, across) coordinates in units of width and height
+    let at = |u: f64, v: f64| {
+        (
+            mid.0 + ux * u * width + nx * v * height,
+            mid.1 + uy * u * width + ny * v * height,
+        )
+    };
+    let knob = [
+        ("L", [(-0.15, 0.0)].as_slice()),
+        ("C", &[(-0.05, 0.0), (-0.25, 0.35), (-0.25, 0.6)]),
+        ("C", &[(-0.25, 1.0), (0.25, 1.0), (0.25, 0.6)]),
+        ("C", &[(0.25, 0.35), (0.05, 0.0), (0.15, 0.0)]),
+    ];
+    for (command, points) in knob {
+        let _ = write!(path, " {command}");
+        for &(u, v) in points {
+            let (x, y) = at(u, v);
+            let _ = write!(path, " {x:.2},{y:.2}");
+        }
+    }
+}
+
+/// SVG path data for one shared edge, with a tab in its longest segment.
+fn edge_path(points: &[(f64, f64)], rng: &mut impl Rng) -> String {
+    let mut path = format!("M {:.2},{:.2}", points[0].0, points[0].1);
+    let longest = (1..points.len()).max_by(|&i, &j| {
+        let len = |k: usize| (points[k].0 - points[k - 1].0).hypot(points[k].1 - points[k - 1].1);
+        len(i).total_cmp(&len(j))
+    });
+    for i in 1..points.len() {
+        let (a, b) = (points[i - 1], points[i]);
+        if Some(i) == longest && (b.0 - a.0).hypot(b.1 - a.1) >= MIN_TAB_EDGE {
+            let side = if rng.random() { 1.0 } else { -1.0 };
+            push_tab(&mut path, a, b, side);
+        }
+        let _ = write!(path, " L {:.2},{:.2}", b.0, b.1);
+    }
+    path
+}
+
+/// Builds an SVG of puzzle cuts along every shared cell boundary, plus a
+/// separate engraving layer numbering each piece at its centroid.
+#[must_use]
+pub fn svg(
+    labels: &[u32],
+    stats: &[CellStats],
+    width: u32,
+    height: u32,
+    rng: &mut impl Rng,
+) -> String {
+    let mut edges: Vec<_> = polygon::shared_edges(labels, width, height)
+        .into_iter()
+        .collect();
+    // Hash map order varies between runs; keep the tab sides reproducible
+    edges.sort_unstable_by_key(|&(pair, _)| pair);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">\n<g id=\"cut\" fill=\"none\" stroke=\"#ff0000\" \
+         stroke-width=\"0.5\">\n<rect x=\"0\" y=\"0\" width=\"{width}\" height=\"{height}\"/>\n"
+    );
+    for (_, segments) in edges {
+        for chain in polygon::chain(&segments) {
+            let points = polygon::simplify(&chain, 1.0);
+            let _ = writeln!(svg, "<path d=\"{}\"/>", edge_path(&points, rng));
+        }
+    }
+    svg.push_str("</g>\n<g id=\"numbers\" fill=\"#0000ff\" font-family=\"sans-serif\" font-size=\"8\" text-anchor=\"middle\">\n");
+    for (piece, stats) in (1..).zip(stats) {
+        if let Some((x, y)) = stats.centroid() {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\">{piece}</text>",
+                x + 0.5,
+                y + 3.5
+            );
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
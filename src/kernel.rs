@@ -0,0 +1,113 @@
+//! The distance and assignment math at the heart of every diagram, written
+//! against `core` alone so it could be lifted into a `no_std` build for
+//! microcontrollers drawing low-resolution diagrams on small displays.
+//!
+//! Nothing builds this module without `std`, so staying within `core` is a
+//! convention. The lints below only catch `std::` paths where `core` or
+//! `alloc` would do; float methods such as `powf`, `sqrt` and `sin` come
+//! from `std` without a path, so keep them out by hand and leave scores and
+//! projections that need them with their callers.
+
+#![deny(clippy::std_instead_of_core, clippy::std_instead_of_alloc)]
+
+/// Divides the color term of the legacy score, so `--weight` values of a few
+/// units give color a noticeable but not overwhelming say.
+pub const COLOR_WEIGHT_MULT: f64 = 10000.0;
+
+/// Squared distance between pixels `a` and `b` on the image plane.
+#[must_use]
+pub fn planar_dist(a: (u32, u32), b: (u32, u32)) -> f64 {
+    f64::from(a.0.abs_diff(b.0).pow(2)) + f64::from(a.1.abs_diff(b.1).pow(2))
+}
+
+/// Summed channel differences between colors `a` and `b`.
+#[must_use]
+pub fn color_dist<const N: usize>(a: &[u8; N], b: &[u8; N]) -> f64 {
+    Iterator::zip(a.iter(), b.iter())
+        .map(|(c1, c2)| f64::from(c1.abs_diff(*c2)))
+        .sum()
+}
+
+/// Channel differences between colors `a` and `b`, each scaled by its
+/// entry in `weights` and summed.
+#[must_use]
+pub fn weighted_color_dist<const N: usize>(a: &[u8; N], b: &[u8; N], weights: &[f64; N]) -> f64 {
+    (0..N)
+        .map(|channel| f64::from(a[channel].abs_diff(b[channel])) * weights[channel])
+        .sum()
+}
+
+/// The legacy score given the position distance `pos_dist` between a pixel
+/// of color `color` and a point of color `pcolor`: the position distance,
+/// plus the color distance normalized by `max_color_dist` and scaled by
+/// `color_weight / COLOR_WEIGHT_MULT`. With a zero weight it is the raw
+/// position distance.
+#[must_use]
+pub fn weighted_score<const N: usize>(
+    pos_dist: f64,
+    color: [u8; N],
+    pcolor: [u8; N],
+    color_weight: f64,
+    max_color_dist: f64,
+    max_pos_dist: f64,
+) -> f64 {
+    if let 0.0 = color_weight {
+        pos_dist
+    } else {
+        mixed_score(
+            pos_dist,
+            color_dist(&color, &pcolor),
+            color_weight,
+            max_color_dist,
+            max_pos_dist,
+        )
+    }
+}
+
+/// The legacy score from an already measured `color_dist`, for callers
+/// measuring color their own way.
+#[must_use]
+pub fn mixed_score(
+    pos_dist: f64,
+    color_dist: f64,
+    color_weight: f64,
+    max_color_dist: f64,
+    max_pos_dist: f64,
+) -> f64 {
+    if let 0.0 = color_weight {
+        pos_dist
+    } else {
+        pos_dist / max_pos_dist + color_dist / max_color_dist * color_weight / COLOR_WEIGHT_MULT
+    }
+}
+
+/// The two best-scoring points for a pixel.
+#[derive(Debug, Clone, Copy)]
+pub struct Nearest {
+    pub label: u32,
+    pub score: f64,
+    /// The runner-up, scoring `f64::MAX` if there was only one point
+    pub second_label: u32,
+    pub second_score: f64,
+}
+
+/// Scores every one of `points` with `score`, lowest best, keeping the best
+/// two. Ties go to the earlier point.
+pub fn nearest<P>(points: impl IntoIterator<Item = P>, mut score: impl FnMut(P) -> f64) -> Nearest {
+    let mut nearest = Nearest {
+        label: 0,
+        score: f64::MAX,
+        second_label: 0,
+        second_score: f64::MAX,
+    };
+    for (label, point) in (0..).zip(points) {
+        let s = score(point);
+        if s < nearest.score {
+            (nearest.second_score, nearest.second_label) = (nearest.score, nearest.label);
+            (nearest.score, nearest.label) = (s, label);
+        } else if s < nearest.second_score {
+            (nearest.second_score, nearest.second_label) = (s, label);
+        }
+    }
+    nearest
+}
@@ -0,0 +1,255 @@
+//! Transformations of label maps.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{distance, schedule};
+
+/// Builds a label map by calling `label_of` on every pixel, a row at a time
+/// spread over the available threads. `progress` is called with the number
+/// of rows done.
+pub fn by_rows(
+    width: u32,
+    height: u32,
+    label_of: impl Fn(u32, u32) -> u32 + Sync,
+    progress: impl Fn(u32) + Sync,
+) -> Vec<u32> {
+    let rows = schedule::run(
+        &vec![u64::from(width); height as usize],
+        |y| {
+            let y = u32::try_from(y).expect("row index fits the height");
+            (0..width).map(|x| label_of(x, y)).collect::<Vec<_>>()
+        },
+        |done| progress(u32::try_from(done).expect("row count fits the height")),
+    );
+    rows.concat()
+}
+
+/// Builds a label map by calling `label_of` only on a grid every `block`
+/// pixels, filling blocks whose four corners agree with that label and
+/// resolving the rest pixel by pixel. `progress` is called with the share
+/// of the image resolved so far, in rows.
+///
+/// The corners are what the blocks will cost: a block they agree on is
+/// only filled, while one they disagree on needs `label_of` for each of
+/// its pixels. Those blocks are spread over the available threads, largest
+/// first, so images with their cell boundaries bunched in one part keep
+/// every thread busy.
+pub fn coarse_to_fine(
+    width: u32,
+    height: u32,
+    block: u32,
+    label_of: impl Fn(u32, u32) -> u32 + Sync,
+    progress: impl Fn(u32) + Sync,
+) -> Vec<u32> {
+    // Corner coordinates, ending on the last pixel; a one pixel wide image
+    // still gets a (degenerate) block
+    let grid = |size: u32| {
+        let mut grid: Vec<u32> = (0..size).step_by(block as usize).collect();
+        if grid.len() == 1 || grid.last() != Some(&(size - 1)) {
+            grid.push(size - 1);
+        }
+        grid
+    };
+    let (grid_x, grid_y) = (grid(width), grid(height));
+    let corners = schedule::run(
+        &vec![grid_x.len() as u64; grid_y.len()],
+        |j| {
+            let y = grid_y[j];
+            grid_x.iter().map(|&x| label_of(x, y)).collect::<Vec<_>>()
+        },
+        |_| {},
+    );
+
+    // Blocks as (x0, x_end, y0, y_end), the last in each direction also
+    // covering the far edge, with their label if the corners agree
+    let mut uniform = Vec::new();
+    let mut mixed = Vec::new();
+    for (j, rows) in grid_y.windows(2).enumerate() {
+        let y_end = if j + 2 == grid_y.len() {
+            height
+        } else {
+            rows[1]
+        };
+        for (i, columns) in grid_x.windows(2).enumerate() {
+            let x_end = if i + 2 == grid_x.len() {
+                width
+            } else {
+                columns[1]
+            };
+            let bounds = (columns[0], x_end, rows[0], y_end);
+            let labels = [
+                corners[j][i],
+                corners[j][i + 1],
+                corners[j + 1][i],
+                corners[j + 1][i + 1],
+            ];
+            if labels.iter().all(|&label| label == labels[0]) {
+                uniform.push((bounds, labels[0]));
+            } else {
+                mixed.push(bounds);
+            }
+        }
+    }
+
+    let mut labels = vec![0; (width * height) as usize];
+    for ((x0, x_end, y0, y_end), label) in uniform {
+        for y in y0..y_end {
+            labels[(y * width + x0) as usize..(y * width + x_end) as usize].fill(label);
+        }
+    }
+    let costs: Vec<u64> = mixed
+        .iter()
+        .map(|&(x0, x_end, y0, y_end)| u64::from(x_end - x0) * u64::from(y_end - y0))
+        .collect();
+    let total: u64 = costs.iter().sum();
+    let resolved = AtomicU64::new(0);
+    let label_of = &label_of;
+    let tiles = schedule::run(
+        &costs,
+        |tile| {
+            let (x0, x_end, y0, y_end) = mixed[tile];
+            let pixels: Vec<u32> = (y0..y_end)
+                .flat_map(|y| (x0..x_end).map(move |x| label_of(x, y)))
+                .collect();
+            let done = resolved.fetch_add(costs[tile], Ordering::Relaxed) + costs[tile];
+            progress(u32::try_from(u64::from(height) * done / total).unwrap_or(height));
+            pixels
+        },
+        |_| {},
+    );
+    for ((x0, x_end, y0, y_end), pixels) in mixed.into_iter().zip(tiles) {
+        let tile_width = (x_end - x0) as usize;
+        for (y, row) in (y0..y_end).zip(pixels.chunks(tile_width)) {
+            labels[(y * width + x0) as usize..][..tile_width].copy_from_slice(row);
+        }
+    }
+    labels
+}
+
+/// Smooths cell boundaries by blurring each cell's mask with a Gaussian of
+/// standard deviation `sigma` and giving every pixel to the cell with the
+/// strongest blurred mask.
+#[must_use]
+pub fn smooth_labels(labels: &[u32], width: u32, height: u32, sigma: f32) -> Vec<u32> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let radius = (sigma * 2.0).ceil().max(1.0) as usize;
+    #[allow(clippy::cast_precision_loss)]
+    let kernel: Vec<f32> = (0..=radius * 2)
+        .map(|i| {
+            let offset = i as f32 - radius as f32;
+            (-offset * offset / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let boundary_dist = distance::boundary_distance(labels, width, height);
+    let (w, h) = (width as usize, height as usize);
+    let mut smoothed = labels.to_vec();
+    let mut votes: Vec<(u32, f32)> = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            let idx = y * w + x;
+            // The window only sees one cell this far from any boundary
+            #[allow(clippy::cast_precision_loss)]
+            if boundary_dist[idx] > (radius + 1) as f32 {
+                continue;
+            }
+            votes.clear();
+            for ky in y.saturating_sub(radius)..=(y + radius).min(h - 1) {
+                let wy = kernel[ky + radius - y];
+                for kx in x.saturating_sub(radius)..=(x + radius).min(w - 1) {
+                    let weight = wy * kernel[kx + radius - x];
+                    let label = labels[ky * w + kx];
+                    match votes.iter_mut().find(|(l, _)| *l == label) {
+                        Some((_, total)) => *total += weight,
+                        None => votes.push((label, weight)),
+                    }
+                }
+            }
+            if let Some(&(label, _)) = votes.iter().max_by(|a, b| a.1.total_cmp(&b.1)) {
+                smoothed[idx] = label;
+            }
+        }
+    }
+    smoothed
+}
+
+/// Sorted lists of the cells touching each of `cells` cells, where two cells
+/// touch if any of their pixels are 4-neighbours.
+#[must_use]
+pub fn adjacency(labels: &[u32], width: u32, cells: usize) -> Vec<Vec<u32>> {
+    let mut neighbours = vec![Vec::new(); cells];
+    let w = width as usize;
+    let mut link = |a: u32, b: u32| {
+        if a != b {
+            neighbours[a as usize].push(b);
+            neighbours[b as usize].push(a);
+        }
+    };
+    for (idx, &label) in labels.iter().enumerate() {
+        if (idx + 1) % w != 0 {
+            link(label, labels[idx + 1]);
+        }
+        if let Some(&below) = labels.get(idx + w) {
+            link(label, below);
+        }
+    }
+    for list in &mut neighbours {
+        list.sort_unstable();
+        list.dedup();
+    }
+    neighbours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImagePlane, kernel, max_dists, score_fn_for, testing};
+
+    /// Cells nearest by position alone are convex, so a block whose corners
+    /// share a cell lies wholly inside it and skipping it loses nothing.
+    #[test]
+    fn coarse_to_fine_matches_by_rows_on_convex_cells() {
+        let sites = [(3, 4), (60, 10), (25, 40), (90, 55), (48, 70), (10, 66)];
+        let nearest = |x: u32, y: u32| {
+            kernel::nearest(&sites, |&(sx, sy): &(u32, u32)| {
+                f64::from(x.abs_diff(sx).pow(2) + y.abs_diff(sy).pow(2))
+            })
+            .label
+        };
+        for (width, height) in [(97, 73), (1, 73), (97, 1), (64, 64)] {
+            let expected = by_rows(width, height, nearest, |_| {});
+            for block in [2, 5, 8, 16, 200] {
+                let labels = coarse_to_fine(width, height, block, nearest, |_| {});
+                assert_eq!(labels, expected, "{width}x{height} in blocks of {block}");
+            }
+        }
+    }
+
+    #[test]
+    fn coarse_to_fine_renders_match_the_plain_render() {
+        let img = testing::image(97, 73);
+        let points: Vec<_> = (0..12_u32)
+            .map(|i| {
+                let (x, y) = (i * 37 % 97, i * 23 % 73);
+                (x, y, img.get_pixel(x, y).0)
+            })
+            .collect();
+        let labels = |options: &[&str]| {
+            let args = testing::args(options);
+            let (max_color_dist, max_pos_dist) = max_dists(&img, &args);
+            let plane = ImagePlane::new(&img, args.blur);
+            crate::assign_labels(
+                &plane,
+                &points,
+                max_color_dist,
+                max_pos_dist,
+                &score_fn_for(&plane, &args),
+                &args,
+            )
+        };
+        let expected = labels(&["--distance", "spatial"]);
+        for block in ["2", "6", "16"] {
+            let coarse = labels(&["--distance", "spatial", "--coarse-to-fine", block]);
+            assert_eq!(coarse, expected, "blocks of {block}");
+        }
+    }
+}
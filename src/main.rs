@@ -1,13 +1,123 @@
-use clap::Parser;
-use image::imageops::fast_blur;
+mod animate;
+mod anneal;
+mod annotate;
+mod auto;
+mod bricks;
+mod brush;
+mod cache;
+mod cells;
+mod cmyk;
+mod codec;
+mod color;
+mod colorize;
+mod debug;
+mod distance;
+mod edges;
+mod edit;
+mod effects;
+mod ensemble;
+mod export;
+mod extrude;
+mod font;
+mod generate;
+mod gray;
+mod greedy;
+mod halftone;
+mod hierarchy;
+mod importance;
+mod incremental;
+mod jigsaw;
+mod kernel;
+mod labels;
+mod legibility;
+mod metadata;
+mod metric;
+mod multicolor;
+mod multispectral;
+mod noise;
+mod outliers;
+mod output;
+mod overrides;
+mod palette;
+mod passes;
+mod pattern;
+mod pdf;
+mod plane;
+mod plugins;
+mod polygon;
+mod post;
+mod project;
+mod pyramid;
+mod rasterize;
+mod regions;
+mod relax;
+mod rng;
+mod schedule;
+mod selftest;
+mod shard;
+mod stitch;
+mod symmetry;
+mod text;
+mod timings;
+mod treemap;
+mod worley;
+
+use clap::{CommandFactory, Parser, ValueEnum};
+use colorize::CellColor;
+use plane::ImagePlane;
+use rand::Rng;
 use rand::distr::weighted::WeightedIndex;
 use rand::prelude::*;
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
 
 #[derive(Parser, Debug, Clone)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    args_override_self = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Option<Args>,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Render an animated GIF of a changing diagram
+    Animate(animate::AnimateArgs),
+    /// Render abstract art from a procedural color field instead of an image
+    Generate(generate::GenerateArgs),
+    /// Lay out weighted data items as a Voronoi treemap
+    Treemap(treemap::TreemapArgs),
+    /// Draw a diagram from a list of sites instead of an image
+    Rasterize(rasterize::RasterizeArgs),
+    /// Generate a tileable cellular noise texture
+    Worley(worley::WorleyArgs),
+    /// Render a diagram with hand edits to its sites
+    Edit(edit::EditArgs),
+    /// Render a project saved by `edit --session`
+    Render(project::RenderArgs),
+    /// Store only a diagram's sites, as a compact lossy .vor file
+    Encode(codec::EncodeArgs),
+    /// Draw the diagram stored in a .vor file
+    Decode(codec::DecodeArgs),
+    /// Check that this build reproduces the reference renders
+    Selftest(selftest::SelftestArgs),
+    /// Stack the bands rendered with --shard into one image
+    Merge(shard::MergeArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Args {
     /// Input image file path
     input: PathBuf,
@@ -15,22 +125,133 @@ pub struct Args {
     /// Output image file path
     output: PathBuf,
 
-    /// Number of points to generate
-    #[arg(short, long, default_value_t = 1000)]
+    /// Refuse images with more pixels than this, so hostile files cannot
+    /// exhaust memory: inputs are checked from the header before decoding,
+    /// and generated images before drawing
+    #[arg(long, default_value_t = DEFAULT_MAX_PIXELS, value_parser = clap::value_parser!(u64).range(1..))]
+    max_pixels: u64,
+
+    /// Copy the input's camera EXIF and XMP to PNG, JPEG and WebP outputs,
+    /// turning the image upright first instead of copying its orientation
+    #[arg(long)]
+    copy_metadata: bool,
+
+    /// Number of points to generate, or "auto" to rank a generous set of
+    /// candidates by importance and keep as many as pay their way
+    #[arg(short, long, default_value_t = 1000, value_parser = parse_points)]
     points: usize,
 
+    /// What to do when there are more points than pixels, from --points,
+    /// --density or --auto: allow them, with some sites sharing a pixel and
+    /// their cells left empty; cap them at the pixel count; or stop with an
+    /// error
+    #[arg(long, value_enum, default_value_t = PointOverflow::Allow)]
+    point_overflow: PointOverflow,
+
+    /// Render this many diagrams with consecutive seeds in parallel and blend
+    /// them per pixel, for a soft, watercolor-like result; outputs drawn
+    /// from cells use the first
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..=256))]
+    ensemble: Option<u32>,
+
+    /// Render the diagram this many times, each pass taking the previous
+    /// pass's image as its input, for compound abstractions
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=64), conflicts_with = "viewport")]
+    passes: u32,
+
+    /// TOML file with a table of options per pass, e.g. [2] then
+    /// points = 200, each applied on top of the command line for that pass
+    #[arg(long, value_name = "FILE", requires = "passes")]
+    pass_params: Option<PathBuf>,
+
+    /// How --ensemble blends its diagrams
+    #[arg(long, value_enum, default_value_t = ensemble::EnsembleMode::Mean, requires = "ensemble")]
+    ensemble_mode: ensemble::EnsembleMode,
+
+    /// Number of points per megapixel of the input instead of --points, so
+    /// cells come out the same size across inputs of different sizes
+    #[arg(long, conflicts_with = "points", value_parser = parse_density)]
+    density: Option<f64>,
+
+    /// Place the sites over the whole image but draw only this window of it,
+    /// given as "X,Y,WIDTH,HEIGHT", to preview a detail of a large render;
+    /// cells cut by the window are colored from their visible part
+    #[arg(long, conflicts_with_all = ["levels", "region_mask", "text_safety", "distance_field", "edge_field"])]
+    viewport: Option<Viewport>,
+
+    /// Place the sites over the whole image but draw only band I of N equal
+    /// bands of rows, given as "I/N", to spread a huge render over several
+    /// machines; stack the bands with the merge subcommand. Options drawn
+    /// from whole cells or their neighbours are refused, so the bands meet
+    #[arg(long, conflicts_with_all = [
+        "viewport", "ensemble", "passes", "levels", "region_mask", "text_safety",
+        "distance_field", "edge_field", "post", "caption", "watermark",
+        "smooth_boundaries", "coarse_to_fine", "match_histogram", "posterize", "duotone",
+        "detail_passthrough", "cell_gap", "bevel", "dither",
+    ])]
+    shard: Option<shard::Shard>,
+
+    /// Pick --points, --weight and --blur from the input's size, edge
+    /// density and colorfulness, printing the values picked
+    #[arg(long, conflicts_with_all = ["points", "density", "weight", "blur"])]
+    auto: bool,
+
     /// Seed for random number generator
     #[arg(long)]
     seed: Option<u64>,
 
-    /// Color distance weight
+    /// Random number generator algorithm; pin chacha12 or pcg64 to keep
+    /// seeds reproducible across versions of this tool
+    #[arg(long, value_enum, default_value_t = rng::Algorithm::Stdrng)]
+    rng: rng::Algorithm,
+
+    /// Color distance weight in the legacy score, which adds the color
+    /// distance scaled down by 10000 to the position distance; kept as the
+    /// default so earlier outputs can be reproduced, see --balance
     #[arg(short, long, default_value_t = 2.0)]
     weight: f64,
 
+    /// Score pixels by a blend of position and color distance instead, both
+    /// normalized to 0..1: 0 is position only and 1 is color only
+    #[arg(
+        long,
+        value_parser = parse_balance,
+        conflicts_with_all = ["weight", "weight_ramp", "distance"]
+    )]
+    balance: Option<f64>,
+
+    /// Exponent on the normalized position distance with --balance; above 1
+    /// lets nearby pixels stray further from the closest point
+    #[arg(long, default_value_t = 1.0, requires = "balance")]
+    position_gamma: f64,
+
+    /// Exponent on the normalized color distance with --balance; below 1
+    /// makes small color differences count for more
+    #[arg(long, default_value_t = 1.0, requires = "balance")]
+    color_gamma: f64,
+
+    /// Vary the color weight across the image in place of --weight:
+    /// "radial:CENTER..CORNERS" ramps from the middle out, and
+    /// "luminance:BLACK..WHITE" follows the brightness of each pixel
+    #[arg(long)]
+    weight_ramp: Option<WeightRamp>,
+
     /// Blur amount before processing
     #[arg(short, long, default_value_t = 3.0)]
     blur: f32,
 
+    /// Image points are sampled from and take their colors from
+    #[arg(long, value_enum, default_value_t = Source::Original)]
+    seed_from: Source,
+
+    /// Image whose pixel colors are compared with the points' colors
+    #[arg(long, value_enum, default_value_t = Source::Blurred)]
+    score_on: Source,
+
+    /// Image cell statistics, and so average and gradient fills, come from
+    #[arg(long, value_enum, default_value_t = Source::Original)]
+    fill_from: Source,
+
     /// Add circles at point locations
     #[arg(long)]
     point_radius: Option<u32>,
@@ -42,6 +263,763 @@ pub struct Args {
     /// Selection offset for weighted sampling of points
     #[arg(long, default_value_t = 0.3)]
     selection_offset: f64,
+
+    /// Sample points on a copy of the image downscaled by this factor, which
+    /// is faster and leaner; cells are still assigned at full resolution
+    #[arg(long, default_value_t = 1.0, value_parser = parse_proxy_scale)]
+    proxy_scale: f64,
+
+    /// Snap each sampled point to the nearest strong edge pixel within this
+    /// many pixels, so cell boundaries follow object contours more often
+    #[arg(long, value_name = "RADIUS")]
+    snap_to_edges: Option<u32>,
+
+    /// Keep text and logos legible by finding regions dense with sharp
+    /// edges and giving them extra points or leaving them unstylized
+    #[arg(long, value_enum)]
+    text_safety: Option<legibility::TextSafety>,
+
+    /// Render a first pass, then give points whose color is further than
+    /// this from the median of their cell that median color instead
+    #[arg(long, value_name = "DISTANCE")]
+    reject_outliers: Option<f64>,
+
+    /// Color each point takes: pixel (the pixel under it) or median:R (the
+    /// median of the unblurred pixels within R pixels, ignoring specks)
+    #[arg(long, default_value = "pixel")]
+    seed_color: SeedColor,
+
+    /// Representative colors per point, found by k-means over the pixels
+    /// around it; pixels take the closest, splitting cells along edges
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8))]
+    site_colors: u8,
+
+    /// How points are sampled: weighted, uniform or greedy, which places
+    /// each point where the diagram of those before is most wrong; slower,
+    /// but truer to the image per point
+    #[arg(long, alias = "sampling", default_value = "weighted", value_parser = plugins::parse_sampler)]
+    sampler: &'static dyn plugins::PointSampler,
+
+    /// How pixels are matched to points: color (position and color, balanced
+    /// by --weight) or spatial (position only)
+    #[arg(long, default_value = "color", value_parser = plugins::parse_distance_metric)]
+    distance: &'static dyn plugins::DistanceMetric,
+
+    /// How cells are colored: cell-color (as --cell-color and the tone
+    /// options choose) or grayscale
+    #[arg(long, default_value = "cell-color", value_parser = plugins::parse_cell_colorizer)]
+    colorizer: &'static dyn plugins::CellColorizer,
+
+    /// Cache sampled points in this directory, keyed by the image and the
+    /// sampling options, and reuse them on later runs
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Assign cells on a grid of this spacing first and only resolve blocks
+    /// whose corners disagree pixel by pixel; much faster for large cells
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..))]
+    coarse_to_fine: Option<u32>,
+
+    /// Roughen cell boundaries: where the second-best point scores within
+    /// this fraction of the best, the pixel may go to it instead, more likely
+    /// the closer the two scores are
+    #[arg(long, value_name = "EPSILON")]
+    boundary_dither: Option<f64>,
+
+    /// Wobble cell edges as if hand-drawn by displacing pixel positions with
+    /// noise before scoring: "amplitude,frequency", the amplitude in pixels
+    /// and the frequency in noise features across the image
+    #[arg(long)]
+    warp: Option<noise::Warp>,
+
+    /// Iterations of Lloyd relaxation, moving points to their cell centroids
+    #[arg(long, default_value_t = 0)]
+    relax: u32,
+
+    /// Optimize site positions and colors for fidelity after relaxation:
+    /// anneal[:ITERATIONS] perturbs one site per iteration, keeping changes
+    /// that lower the total color error; slow, for best quality
+    #[arg(long, value_name = "METHOD")]
+    optimize: Option<anneal::Optimize>,
+
+    /// Also write these next to the output, named after it: svg (cells as
+    /// vector paths), labels (label map with 24-bit labels), points (JSON)
+    /// and layers (SVG with fill, boundary and site layers and the colors as
+    /// swatches, for editing in Inkscape or Illustrator)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    also_write: Vec<AlsoWrite>,
+
+    /// Print the wall-clock time spent in each phase
+    #[arg(long)]
+    timings: bool,
+
+    /// Overwrite output files that already exist
+    #[arg(short, long)]
+    force: bool,
+
+    /// Reuse the parameters and seed recorded in a PNG rendered earlier;
+    /// options given here still take precedence
+    #[arg(long)]
+    like: Option<PathBuf>,
+
+    /// Command line the parameters came from when not the process's own
+    #[arg(skip)]
+    command_line: Vec<String>,
+
+    /// Also write an animated GIF with a frame per relaxation iteration
+    #[arg(long, requires = "relax")]
+    relax_frames: Option<PathBuf>,
+
+    /// Recolor cells by mapping their luminance onto a ramp of two or more
+    /// comma-separated colors, e.g. "#0a2540,#ffd166"
+    #[arg(long)]
+    duotone: Option<color::Ramp>,
+
+    /// Base color of each cell: seed, average, random[:seed], random-safe[:seed]
+    /// or map:area|eccentricity|centrality[:viridis|magma]
+    #[arg(long, default_value = "seed")]
+    cell_color: colorize::CellColor,
+
+    /// Adjust cell colors so the output's color histogram matches the input's
+    #[arg(long)]
+    match_histogram: bool,
+
+    /// Posterize cell colors to this many tonal steps
+    #[arg(long, value_parser = clap::value_parser!(u8).range(2..))]
+    posterize: Option<u8>,
+
+    /// Nest diagrams this many levels deep, splitting every cell into a finer
+    /// diagram of its own; boundaries are drawn darker the coarser they are
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=6))]
+    levels: u32,
+
+    /// Points each cell is split into at every level below the first
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(2..))]
+    level_points: u32,
+
+    /// Only split cells whose color variance exceeds this
+    #[arg(long, value_name = "THRESHOLD")]
+    level_detail: Option<f64>,
+
+    /// Grayscale mask whose levels pick the options each part of the image
+    /// is rendered with, from the tables of --region-params
+    #[arg(long, requires = "region_params")]
+    region_mask: Option<PathBuf>,
+
+    /// TOML file with a table per mask gray level, e.g. [255] then
+    /// points = 2000 and style = "papercut", overriding options there
+    #[arg(long, requires = "region_mask")]
+    region_params: Option<PathBuf>,
+
+    /// Keep the original pixels of cells whose color variance exceeds this,
+    /// flattening only low-detail regions
+    #[arg(long, value_name = "THRESHOLD")]
+    detail_passthrough: Option<f64>,
+
+    /// How to fill each cell: flat, gradient, or pattern:stripes|dots|crosshatch
+    #[arg(long, default_value = "flat")]
+    cell_fill: CellFill,
+
+    /// Shrink cells to leave a grout line this many pixels wide between them
+    #[arg(long)]
+    cell_gap: Option<u32>,
+
+    /// Color of the grout between cells
+    #[arg(long, value_parser = color::parse_hex, default_value = "#e0e0e0", requires = "cell_gap")]
+    grout_color: [u8; 3],
+
+    /// Smooth cell boundaries with a Gaussian of this standard deviation
+    #[arg(long, value_parser = parse_sigma)]
+    smooth_boundaries: Option<f32>,
+
+    /// Limit the output to a palette: bw, gameboy, pico8 or comma-separated colors
+    #[arg(long)]
+    palette: Option<palette::Palette>,
+
+    /// Dither within each cell when quantizing to --palette
+    #[arg(long, value_enum, requires = "palette")]
+    dither: Option<palette::Dither>,
+
+    /// Also write one SVG per palette color into this directory, each with
+    /// only the cells of that color merged into one shape, as separations
+    /// for screen printing or vinyl cutting
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "palette",
+        conflicts_with = "dither"
+    )]
+    separations: Option<PathBuf>,
+
+    /// Output format; images are encoded according to the output's extension
+    #[arg(long, value_enum, default_value_t = Format::Image)]
+    format: Format,
+
+    /// ICC output profile for `--format tiff-cmyk`, converting through it and
+    /// embedding it in the file
+    #[arg(long, value_name = "PROFILE")]
+    icc: Option<PathBuf>,
+
+    /// How much gray `--format tiff-cmyk` prints with black ink when no
+    /// `--icc` profile decides it
+    #[arg(long, value_enum, default_value_t = cmyk::BlackGeneration::Medium, conflicts_with = "icc")]
+    black_generation: cmyk::BlackGeneration,
+
+    /// Width in characters of text output formats
+    #[arg(long, default_value_t = 80)]
+    columns: u32,
+
+    /// Also write a cross-stitch chart with DMC floss colors to this path
+    #[arg(long)]
+    stitch_chart: Option<PathBuf>,
+
+    /// Number of stitches across the cross-stitch chart
+    #[arg(long, default_value_t = 80)]
+    stitch_count: u32,
+
+    /// Also write jigsaw puzzle cuts along the cell boundaries as SVG to this path
+    #[arg(long)]
+    jigsaw: Option<PathBuf>,
+
+    /// Projection used to measure distances between pixels: planar, equirect
+    /// or polar[:cx,cy]
+    #[arg(long, default_value = "planar")]
+    projection: metric::Projection,
+
+    /// Channels the diagram is worked in; gray keeps a grayscale input
+    /// single-channel from sampling to output, with seed or average levels
+    /// per cell, and applies no styling options
+    #[arg(long, value_enum, default_value_t = Pipeline::Rgb)]
+    pipeline: Pipeline,
+
+    /// How much each input channel counts toward color distance in the
+    /// multispectral pipeline, one weight per channel, e.g. 1,1,1,2 to favor
+    /// near-infrared; all 1 by default
+    #[arg(long, value_delimiter = ',')]
+    channel_weights: Vec<f64>,
+
+    /// Input channels the multispectral pipeline draws as red, green and
+    /// blue, e.g. 3,0,1 for a false-color infrared view; 0,1,2 by default
+    #[arg(long, value_name = "R,G,B")]
+    channel_map: Option<multispectral::ChannelMap>,
+
+    /// Make the diagram symmetric: mirror-x, mirror-y or rot:N
+    #[arg(long)]
+    symmetry: Option<symmetry::Symmetry>,
+
+    /// Rendering style
+    #[arg(long, value_enum, default_value_t = Style::Flat)]
+    style: Style,
+
+    /// Size in pixels of one stud for --style bricks
+    #[arg(long, default_value_t = 16)]
+    brick_size: u32,
+
+    /// Bevel cell edges over this many pixels, shaded by --light
+    #[arg(long)]
+    bevel: Option<f32>,
+
+    /// Finishing effects applied in order to the rendered image, as
+    /// name:amount pairs from grain, vignette and sharpen, e.g.
+    /// "grain:0.05,vignette:0.3,sharpen:0.5"
+    #[arg(long, value_name = "EFFECTS")]
+    post: Option<post::Effects>,
+
+    /// Write this caption along the bottom left of the output, in black or
+    /// white to stand out from the cells behind it
+    #[arg(long, value_name = "TEXT")]
+    caption: Option<String>,
+
+    /// Blend a logo into a corner of the output, as
+    /// PATH[@CORNER[:OPACITY]], e.g. "logo.png@bottom-right:0.5"; corners
+    /// are top-left, top-right, bottom-left and bottom-right
+    #[arg(long, value_name = "SPEC")]
+    watermark: Option<annotate::Watermark>,
+
+    /// Light direction for shading, as azimuth,elevation in degrees
+    #[arg(long, default_value = "315,45")]
+    light: effects::Light,
+
+    /// Raise bevelled tiles by up to this many more pixels the brighter
+    /// their cell is on average, so the light catches the image's tones
+    #[arg(long, requires = "bevel")]
+    relief: Option<f32>,
+
+    /// Add glossy highlights of this strength (0 to 1) where bevelled tiles
+    /// reflect the light
+    #[arg(long, requires = "bevel")]
+    specular: Option<f64>,
+
+    /// Also write the bevel's normal map to this path
+    #[arg(long, requires = "bevel")]
+    normal_map: Option<PathBuf>,
+
+    /// Also write a grayscale matte of the cell boundaries to this path,
+    /// white along them and feathered to black over --matte-width pixels
+    #[arg(long)]
+    boundary_matte: Option<PathBuf>,
+
+    /// Width in pixels of the band --boundary-matte fades over
+    #[arg(long, default_value_t = 4.0, requires = "boundary_matte")]
+    matte_width: f32,
+
+    /// Also write each cell as a cropped PNG with transparency into this
+    /// directory, with an index.json of where each one goes
+    #[arg(long, value_name = "DIR")]
+    export_cells: Option<PathBuf>,
+
+    /// Also pack every cell into one PNG sprite sheet at this path, with a
+    /// JSON atlas of frames and pivots beside it for game engines
+    #[arg(long)]
+    sprite_sheet: Option<PathBuf>,
+
+    /// Also write this many successively halved copies of the diagram for
+    /// zoomable viewers (3 if no count is given: 1/2, 1/4 and 1/8 size),
+    /// named after the output as "out.mip1.png" and so on; each is drawn
+    /// from a downsampled label map so cells stay crisp
+    #[arg(long, num_args = 0..=1, default_missing_value = "3",
+          value_parser = clap::value_parser!(u8).range(1..=16))]
+    pyramid: Option<u8>,
+
+    /// Also write the diagram as a Deep Zoom image for zoomable web viewers:
+    /// this .dzi descriptor and a directory of PNG tiles beside it, named
+    /// after it with "_files" appended
+    #[arg(long)]
+    dzi: Option<PathBuf>,
+
+    /// Also write each pixel's distance to the nearest point to this path,
+    /// as 32-bit floats for .exr and 16-bit grayscale otherwise
+    #[arg(long)]
+    distance_field: Option<PathBuf>,
+
+    /// Also write each pixel's distance to its second-nearest point minus
+    /// that to its nearest (the Worley F2-F1 edge field) to this path
+    #[arg(long)]
+    edge_field: Option<PathBuf>,
+
+    /// Whether the distance and edge fields are scaled to 0..1 or kept in pixels
+    #[arg(long, value_enum, default_value_t = export::FieldScale::Normalized)]
+    distance_field_scale: export::FieldScale,
+
+    /// Also write how clearly each pixel belongs to its cell to this path:
+    /// the margin between its best and second-best score relative to the
+    /// second, dark where two cells nearly tie. Written like the distance
+    /// field; pixels that --coarse-to-fine fills without scoring count as
+    /// fully confident
+    #[arg(long, conflicts_with = "levels")]
+    confidence: Option<PathBuf>,
+
+    /// Also write the sampling density, gradient magnitude, blurred input,
+    /// initial and relaxed sites, and score margin into this directory as
+    /// captioned images, for diagnosing parameters
+    #[arg(long, value_name = "DIR")]
+    debug_dir: Option<PathBuf>,
+
+    /// Also write a 16-bit heightmap of the extruded cells to this path
+    #[arg(long)]
+    heightmap: Option<PathBuf>,
+
+    /// Also write the extruded cells as a closed mesh (.stl or .obj) to this path
+    #[arg(long)]
+    mesh: Option<PathBuf>,
+
+    /// Cell property that sets the extrusion height
+    #[arg(long, value_enum, default_value_t = extrude::ExtrudeBy::Luminance)]
+    extrude_by: extrude::ExtrudeBy,
+
+    /// Height of the tallest cell in the mesh, in pixels, above a one-pixel floor
+    #[arg(long, default_value_t = 10.0)]
+    extrude_height: f32,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A raster image
+    Image,
+    /// Colored block characters for 24-bit color terminals
+    Ansi,
+    /// Plain ASCII art
+    Ascii,
+    /// A PDF of the cells as vector paths, for printing at any size; drawn
+    /// in the cell colors without styles or effects
+    Pdf,
+    /// A PDF of the raster image, keeping styles and effects
+    PdfRaster,
+    /// A CMYK TIFF of the raster image for prepress, see `--icc` and
+    /// `--black-generation`
+    TiffCmyk,
+}
+
+fn parse_proxy_scale(s: &str) -> Result<f64, String> {
+    let scale: f64 = s
+        .parse()
+        .map_err(|err| format!("invalid scale \"{s}\": {err}"))?;
+    if scale > 0.0 && scale <= 1.0 {
+        Ok(scale)
+    } else {
+        Err(format!("scale must be in (0, 1], got {scale}"))
+    }
+}
+
+/// `--points auto`, until [`for_image`] works out the count.
+const AUTO_POINTS: usize = 0;
+
+fn parse_points(s: &str) -> Result<usize, String> {
+    if s == "auto" {
+        return Ok(AUTO_POINTS);
+    }
+    let points: usize = s
+        .parse()
+        .map_err(|err| format!("invalid point count \"{s}\": {err}"))?;
+    if points >= 1 {
+        Ok(points)
+    } else {
+        Err("at least 1 point is needed".to_string())
+    }
+}
+
+fn parse_density(s: &str) -> Result<f64, String> {
+    let density: f64 = s
+        .parse()
+        .map_err(|err| format!("invalid density \"{s}\": {err}"))?;
+    if density > 0.0 && density.is_finite() {
+        Ok(density)
+    } else {
+        Err(format!("density must be positive, got {density}"))
+    }
+}
+
+fn parse_sigma(s: &str) -> Result<f32, String> {
+    let sigma: f32 = s
+        .parse()
+        .map_err(|err| format!("invalid standard deviation \"{s}\": {err}"))?;
+    if sigma > 0.0 && sigma.is_finite() {
+        Ok(sigma)
+    } else {
+        Err(format!("standard deviation must be positive, got {sigma}"))
+    }
+}
+
+fn parse_balance(s: &str) -> Result<f64, String> {
+    let balance: f64 = s
+        .parse()
+        .map_err(|err| format!("invalid balance \"{s}\": {err}"))?;
+    if (0.0..=1.0).contains(&balance) {
+        Ok(balance)
+    } else {
+        Err(format!("balance must be in [0, 1], got {balance}"))
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlsoWrite {
+    Svg,
+    Labels,
+    Points,
+    Layers,
+}
+
+impl AlsoWrite {
+    /// Where to write this next to `output`.
+    fn path(self, output: &Path) -> PathBuf {
+        output.with_extension(match self {
+            AlsoWrite::Svg => "svg",
+            AlsoWrite::Labels => "labels.png",
+            AlsoWrite::Points => "points.json",
+            AlsoWrite::Layers => "layers.svg",
+        })
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pipeline {
+    /// Three color channels, expanding grayscale inputs
+    Rgb,
+    /// A single luma channel, for grayscale inputs only
+    Gray,
+    /// Every channel of the input, up to 8, such as RGB plus near-infrared
+    /// TIFFs; see --channel-weights and --channel-map
+    Multispectral,
+}
+
+/// What to do about more points than pixels.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointOverflow {
+    Allow,
+    Cap,
+    Error,
+}
+
+/// Which version of the input image a stage works on.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The input as loaded
+    Original,
+    /// The input blurred by --blur
+    Blurred,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Cells painted side by side
+    Flat,
+    /// Layered cut paper with drop shadows and lit edges
+    Papercut,
+    /// A brick mosaic snapped to a stud grid in brick colors
+    Bricks,
+    /// Painterly strokes: cells stretched along the local flow of the image
+    Brush,
+    /// Print halftone: round dots on a rotated grid per CMYK ink, about
+    /// --points of them per ink
+    Halftone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellFill {
+    /// A single color per cell
+    Flat,
+    /// A linear color gradient fitted to the cell's source pixels
+    Gradient,
+    /// An engraving-style pattern oriented along the cell's gradient
+    Pattern(pattern::Pattern),
+}
+
+impl FromStr for CellFill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "flat" => Ok(Self::Flat),
+            None if s == "gradient" => Ok(Self::Gradient),
+            Some(("pattern", pattern)) => pattern.parse().map(Self::Pattern),
+            _ => Err(format!(
+                "unknown cell fill '{s}' (expected flat, gradient or pattern:<kind>)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedColor {
+    /// The color of the pixel under the point
+    Pixel,
+    /// The per-channel median color of the pixels within this radius
+    Median(u32),
+}
+
+impl FromStr for SeedColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "pixel" => Ok(Self::Pixel),
+            Some(("median", radius)) => radius
+                .parse()
+                .map(Self::Median)
+                .map_err(|_| format!("invalid median radius '{radius}'")),
+            _ => Err(format!(
+                "unknown seed color '{s}' (expected pixel or median:<radius>)"
+            )),
+        }
+    }
+}
+
+impl SeedColor {
+    /// `points` with their colors taken from `img` this way.
+    fn apply(
+        self,
+        img: &image::RgbImage,
+        points: &[(u32, u32, [u8; 3])],
+    ) -> Vec<(u32, u32, [u8; 3])> {
+        let Self::Median(radius) = self else {
+            return points.to_vec();
+        };
+        let (width, height) = img.dimensions();
+        let reach = u64::from(radius) * u64::from(radius);
+        points
+            .iter()
+            .map(|&(px, py, _)| {
+                let mut channels: [Vec<u8>; 3] = Default::default();
+                for y in py.saturating_sub(radius)..=(py + radius).min(height - 1) {
+                    for x in px.saturating_sub(radius)..=(px + radius).min(width - 1) {
+                        let (dx, dy) = (u64::from(x.abs_diff(px)), u64::from(y.abs_diff(py)));
+                        if dx * dx + dy * dy <= reach {
+                            for (channel, value) in channels.iter_mut().zip(img.get_pixel(x, y).0) {
+                                channel.push(value);
+                            }
+                        }
+                    }
+                }
+                let color = channels.map(|mut channel| {
+                    let mid = channel.len() / 2;
+                    *channel.select_nth_unstable(mid).1
+                });
+                (px, py, color)
+            })
+            .collect()
+    }
+}
+
+/// A window of the image: its top-left corner and size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for Viewport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|err| format!("invalid viewport value \"{value}\": {err}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [_, _, 0, _] | [_, _, _, 0] => Err(format!("viewport \"{s}\" is empty")),
+            [x, y, width, height] => Ok(Self {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!("expected \"X,Y,WIDTH,HEIGHT\", got \"{s}\"")),
+        }
+    }
+}
+
+impl Viewport {
+    /// The whole of `img`.
+    fn of(img: &image::RgbImage) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: img.width(),
+            height: img.height(),
+        }
+    }
+
+    /// Fails unless the window lies inside `img`.
+    fn check(self, img: &image::RgbImage) -> Result<(), String> {
+        let (width, height) = img.dimensions();
+        if u64::from(self.x) + u64::from(self.width) > u64::from(width)
+            || u64::from(self.y) + u64::from(self.height) > u64::from(height)
+        {
+            return Err(format!(
+                "viewport {}x{} at ({}, {}) does not fit in the {width}x{height} image",
+                self.width, self.height, self.x, self.y
+            ));
+        }
+        Ok(())
+    }
+
+    /// The window's pixels of `img`, borrowed when it is the whole image.
+    fn crop(self, img: &image::RgbImage) -> std::borrow::Cow<'_, image::RgbImage> {
+        if self == Self::of(img) {
+            std::borrow::Cow::Borrowed(img)
+        } else {
+            std::borrow::Cow::Owned(
+                image::imageops::crop_imm(img, self.x, self.y, self.width, self.height).to_image(),
+            )
+        }
+    }
+}
+
+/// A color weight that changes linearly across the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeightRamp {
+    /// From the first weight at the center to the second at the corners
+    Radial(f64, f64),
+    /// From the first weight on black pixels to the second on white ones
+    Luminance(f64, f64),
+}
+
+impl FromStr for WeightRamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, range) = s.split_once(':').ok_or_else(|| {
+            format!("expected \"radial:FROM..TO\" or \"luminance:FROM..TO\", got \"{s}\"")
+        })?;
+        let (from, to) = range
+            .split_once("..")
+            .ok_or_else(|| format!("expected a range \"FROM..TO\", got \"{range}\""))?;
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|err| format!("invalid weight \"{value}\": {err}"))
+        };
+        let (from, to) = (parse(from)?, parse(to)?);
+        match kind {
+            "radial" => Ok(Self::Radial(from, to)),
+            "luminance" => Ok(Self::Luminance(from, to)),
+            _ => Err(format!(
+                "unknown weight ramp '{kind}' (expected radial or luminance)"
+            )),
+        }
+    }
+}
+
+impl WeightRamp {
+    /// The weight at `(x, y)` in a `width`x`height` image, where the pixel
+    /// has `luminance` in `0..=255`.
+    fn at(self, (x, y): (u32, u32), (width, height): (u32, u32), luminance: f64) -> f64 {
+        let (from, to, t) = match self {
+            Self::Radial(from, to) => {
+                let dx = (f64::from(x) + 0.5) / f64::from(width) * 2.0 - 1.0;
+                let dy = (f64::from(y) + 0.5) / f64::from(height) * 2.0 - 1.0;
+                (from, to, dx.hypot(dy) / std::f64::consts::SQRT_2)
+            }
+            Self::Luminance(from, to) => (from, to, luminance / 255.0),
+        };
+        from + (to - from) * t.clamp(0.0, 1.0)
+    }
+}
+
+/// The color weight for pixel `(x, y)` of `img`: `--weight`, or the
+/// `--weight-ramp` there.
+fn color_weight(args: &Args, img: &image::RgbImage, x: u32, y: u32) -> f64 {
+    let luminance = || color::luminance(img.get_pixel(x, y).0);
+    weight_at(args, (x, y), img.dimensions(), luminance)
+}
+
+/// The color weight at `(x, y)` of an image of `dimensions`: `--weight`,
+/// or the `--weight-ramp` there for a pixel of `luminance`.
+fn weight_at(
+    args: &Args,
+    position: (u32, u32),
+    dimensions: (u32, u32),
+    luminance: impl FnOnce() -> f64,
+) -> f64 {
+    args.weight_ramp.map_or(args.weight, |ramp| {
+        ramp.at(position, dimensions, luminance())
+    })
+}
+
+/// How a single cell is painted.
+#[derive(Debug, Clone, Copy)]
+enum Fill {
+    Flat([u8; 3]),
+    Gradient(cells::LinearGradient),
+    Pattern(pattern::CellPattern),
+}
+
+impl Fill {
+    fn at(&self, x: u32, y: u32) -> [u8; 3] {
+        match self {
+            Self::Flat(color) => *color,
+            Self::Gradient(gradient) => gradient.at(x, y),
+            Self::Pattern(pattern) => pattern.at(x, y),
+        }
+    }
 }
 
 #[must_use]
@@ -64,13 +1042,14 @@ fn weight<const N: usize>(
     dist_weight - offset
 }
 
-const COLOR_WEIGHT_MULT: f64 = 10000.0;
-
+/// Legacy score: [`kernel::weighted_score`] under the metric's position
+/// distance.
 #[must_use]
 pub fn score<const N: usize>(
     &pixel: &(u32, u32, [u8; N]),
     &point: &(u32, u32, [u8; N]),
     _img: &image::RgbImage,
+    metric: &metric::Metric,
     color_weight: f64,
     max_color_dist: f64,
     max_pos_dist: f64,
@@ -78,92 +1057,497 @@ pub fn score<const N: usize>(
     let (x, y, color) = pixel;
     let (px, py, pcolor) = point;
 
-    let pos_dist = f64::from(x.abs_diff(px).pow(2)) + f64::from(y.abs_diff(py).pow(2));
+    let pos_dist = metric.pos_dist((x, y), (px, py));
+    kernel::weighted_score(
+        pos_dist,
+        color,
+        pcolor,
+        color_weight,
+        max_color_dist,
+        max_pos_dist,
+    )
+}
+
+/// Score under `--balance`: the position and color distances, each divided
+/// by its largest possible value and raised to its gamma, mixed `balance`
+/// parts color to `1 - balance` parts position.
+#[must_use]
+pub fn balanced_score<const N: usize>(
+    &(x, y, color): &(u32, u32, [u8; N]),
+    &(px, py, pcolor): &(u32, u32, [u8; N]),
+    metric: &metric::Metric,
+    balance: f64,
+    (position_gamma, color_gamma): (f64, f64),
+    max_color_dist: f64,
+    max_pos_dist: f64,
+) -> f64 {
+    balanced_mix(
+        metric.pos_dist((x, y), (px, py)),
+        kernel::color_dist(&color, &pcolor),
+        balance,
+        (position_gamma, color_gamma),
+        max_color_dist,
+        max_pos_dist,
+    )
+}
+
+/// [`balanced_score`] from already measured distances, for callers that
+/// measure color their own way.
+#[must_use]
+pub fn balanced_mix(
+    pos_dist: f64,
+    color_dist: f64,
+    balance: f64,
+    (position_gamma, color_gamma): (f64, f64),
+    max_color_dist: f64,
+    max_pos_dist: f64,
+) -> f64 {
+    let (pos_dist, color_dist) = (pos_dist / max_pos_dist, color_dist / max_color_dist);
+    (1.0 - balance) * pos_dist.powf(position_gamma) + balance * color_dist.powf(color_gamma)
+}
+
+/// The score pixels join points by: `--balance` when given, otherwise the
+/// `--distance` metric.
+fn distance_fn(args: &Args) -> Box<ScoreFn> {
+    let (distance, balance) = (args.distance, args.balance);
+    let gammas = (args.position_gamma, args.color_gamma);
+    Box::new(
+        move |pixel, point, img, metric, weight, max_color, max_pos| match balance {
+            Some(balance) => {
+                balanced_score(pixel, point, metric, balance, gammas, max_color, max_pos)
+            }
+            None => distance.score(pixel, point, img, metric, weight, max_color, max_pos),
+        },
+    )
+}
 
-    if let 0.0 = color_weight {
-        pos_dist
+/// The score `args` asks for on `img`: the stroke-following score of
+/// `--style brush`, otherwise [`distance_fn`].
+fn score_fn_for(img: &ImagePlane, args: &Args) -> Box<ScoreFn> {
+    if args.style == Style::Brush {
+        brush::score_fn(img)
     } else {
-        let color_dist = Iterator::zip(color.iter(), pcolor.iter())
-            .map(|(c1, c2)| f64::from(c1.abs_diff(*c2)))
-            .sum::<f64>();
+        distance_fn(args)
+    }
+}
 
-        pos_dist / max_pos_dist + color_dist / max_color_dist * color_weight / COLOR_WEIGHT_MULT
+/// Exits naming the first of `options` that was given, each a flag paired
+/// with whether it is set, for subcommands that flatten [`Args`] but have
+/// no use for some of it.
+fn reject_options(subcommand: &str, options: &[(&str, bool)]) {
+    if let Some((name, _)) = options.iter().find(|(_, set)| *set) {
+        eprintln!("{name} is not supported by {subcommand}");
+        std::process::exit(1);
     }
 }
 
+/// The options that only write outputs beside the rendered image or
+/// change its format, each paired with whether `args` sets it, for
+/// [`reject_options`] in commands that write nothing but their own output.
+fn side_output_options(args: &Args) -> Vec<(&'static str, bool)> {
+    vec![
+        ("--format", args.format != Format::Image),
+        ("--copy-metadata", args.copy_metadata),
+        ("--also-write", !args.also_write.is_empty()),
+        ("--separations", args.separations.is_some()),
+        ("--stitch-chart", args.stitch_chart.is_some()),
+        ("--jigsaw", args.jigsaw.is_some()),
+        ("--normal-map", args.normal_map.is_some()),
+        ("--boundary-matte", args.boundary_matte.is_some()),
+        ("--export-cells", args.export_cells.is_some()),
+        ("--sprite-sheet", args.sprite_sheet.is_some()),
+        ("--pyramid", args.pyramid.is_some()),
+        ("--dzi", args.dzi.is_some()),
+        ("--distance-field", args.distance_field.is_some()),
+        ("--edge-field", args.edge_field.is_some()),
+        ("--confidence", args.confidence.is_some()),
+        ("--heightmap", args.heightmap.is_some()),
+        ("--mesh", args.mesh.is_some()),
+    ]
+}
+
+/// The options that draw cells as more than their flat fill color, or
+/// draw over them, each paired with whether `args` sets it, for
+/// [`reject_options`] in commands that only keep the cells and colors.
+fn drawing_options(args: &Args) -> Vec<(&'static str, bool)> {
+    vec![
+        ("--style", !matches!(args.style, Style::Flat | Style::Brush)),
+        ("--cell-fill", args.cell_fill != CellFill::Flat),
+        ("--cell-gap", args.cell_gap.is_some()),
+        ("--bevel", args.bevel.is_some()),
+        ("--point-radius", args.point_radius.is_some()),
+        ("--detail-passthrough", args.detail_passthrough.is_some()),
+        ("--palette", args.palette.is_some()),
+        ("--levels", args.levels > 1),
+        ("--region-mask", args.region_mask.is_some()),
+        ("--post", args.post.is_some()),
+        ("--caption", args.caption.is_some()),
+        ("--watermark", args.watermark.is_some()),
+    ]
+}
+
+/// The options the gray and multispectral pipelines have no use for, each
+/// paired with whether `args` sets it: they sample, score and average
+/// their channels themselves and draw cells flat, with their sites marked
+/// if asked.
+fn channel_pipeline_options(args: &Args) -> Vec<(&'static str, bool)> {
+    let mut options = vec![
+        ("--symmetry", args.symmetry.is_some()),
+        ("--auto", args.auto),
+        ("--points auto", args.points == AUTO_POINTS),
+        ("--ensemble", args.ensemble.is_some()),
+        ("--passes", args.passes > 1),
+        ("--viewport", args.viewport.is_some()),
+        ("--shard", args.shard.is_some()),
+        ("--seed-from", args.seed_from != Source::Original),
+        ("--score-on", args.score_on != Source::Blurred),
+        ("--fill-from", args.fill_from != Source::Original),
+        ("--proxy-scale", args.proxy_scale < 1.0),
+        ("--sampler", args.sampler.name() != "weighted"),
+        ("--snap-to-edges", args.snap_to_edges.is_some()),
+        ("--text-safety", args.text_safety.is_some()),
+        ("--reject-outliers", args.reject_outliers.is_some()),
+        ("--seed-color", args.seed_color != SeedColor::Pixel),
+        ("--site-colors", args.site_colors > 1),
+        ("--colorizer", args.colorizer.name() != "cell-color"),
+        ("--cache-dir", args.cache_dir.is_some()),
+        ("--coarse-to-fine", args.coarse_to_fine.is_some()),
+        ("--boundary-dither", args.boundary_dither.is_some()),
+        ("--warp", args.warp.is_some()),
+        ("--relax", args.relax > 0),
+        ("--optimize", args.optimize.is_some()),
+        (
+            "--cell-color",
+            !matches!(args.cell_color, CellColor::Seed | CellColor::Average),
+        ),
+        ("--duotone", args.duotone.is_some()),
+        ("--match-histogram", args.match_histogram),
+        ("--posterize", args.posterize.is_some()),
+        ("--smooth-boundaries", args.smooth_boundaries.is_some()),
+        ("--style", args.style != Style::Flat),
+        ("--debug-dir", args.debug_dir.is_some()),
+    ];
+    // Both pipelines mark sites, and multispectral finishes in RGB
+    let drawn = [
+        "--style",
+        "--point-radius",
+        "--post",
+        "--caption",
+        "--watermark",
+    ];
+    options.extend(
+        drawing_options(args)
+            .into_iter()
+            .filter(|(name, _)| !drawn.contains(name)),
+    );
+    options.extend(side_output_options(args));
+    options
+}
+
 type ScoreFn = dyn Fn(
-    &(u32, u32, [u8; 3]), // pixel
-    &(u32, u32, [u8; 3]), // point
-    &image::RgbImage,     // img
-    f64,                  // color_weight
-    f64,                  // max_color_dist
-    f64,                  // max_pos_dist
-) -> f64;
+        &(u32, u32, [u8; 3]), // pixel
+        &(u32, u32, [u8; 3]), // point
+        &image::RgbImage,     // img
+        &metric::Metric,      // metric
+        f64,                  // color_weight
+        f64,                  // max_color_dist
+        f64,                  // max_pos_dist
+    ) -> f64
+    + Sync;
 
-fn generate_voronoi_(
+#[allow(clippy::too_many_arguments)]
+fn assign_labels_(
     img: &image::RgbImage,
+    scored: &image::RgbImage,
+    window: Viewport,
     points: &[(u32, u32, [u8; 3])],
     max_color_dist: f64,
     max_pos_dist: f64,
     score_fn: &ScoreFn,
     args: &Args,
     print_progress: bool,
-) -> image::RgbImage {
+    timings: &mut timings::Timings,
+) -> (Vec<u32>, Option<Vec<f32>>) {
     let img_height = img.height();
+    let rows = window.height;
     if print_progress {
-        eprint!("Calculating voronoi diagram... 0 / {img_height}");
-    }
-    let mut voronoi = fast_blur(img, args.blur);
-    for (x, y, pixel) in voronoi.enumerate_pixels_mut() {
-        let mut min_score = f64::MAX;
-        let mut min_color = [0, 0, 0];
-        let mut min_pos = (0, 0);
-        for &(px, py, pcolor) in points {
-            let s = score_fn(
-                &(x, y, pixel.0),
-                &(px, py, pcolor),
+        eprint!("Calculating voronoi diagram... 0 / {rows}");
+    }
+    let metric = metric::Metric::new(args.projection, img.width(), img.height());
+    let assignment = Instant::now();
+    let warp = args.warp.map(|warp| {
+        let mut rng = args.rng.seeded(args.seed.unwrap_or_default());
+        (warp, noise::Simplex::new(&mut rng))
+    });
+    let confidence = (args.confidence.is_some() || args.debug_dir.is_some()).then(|| {
+        let ones = std::iter::repeat_with(|| AtomicU32::new(1.0_f32.to_bits()));
+        ones.take((window.width * rows) as usize)
+            .collect::<Vec<_>>()
+    });
+    // Pixels are scored at their place in the whole image, window or not
+    let label_of = |x: u32, y: u32| {
+        let idx = (y * window.width + x) as usize;
+        let (x, y) = (window.x + x, window.y + y);
+        let pixel = scored.get_pixel(x, y);
+        let weight = color_weight(args, scored, x, y);
+        let (wx, wy) = warp.as_ref().map_or((x, y), |(warp, noise)| {
+            warp.apply(noise, x, y, img.width(), img_height)
+        });
+        let kernel::Nearest {
+            label: min_label,
+            score: min_score,
+            second_label,
+            second_score,
+        } = kernel::nearest(points, |point| {
+            score_fn(
+                &(wx, wy, pixel.0),
+                point,
                 img,
-                args.weight,
+                &metric,
+                weight,
                 max_color_dist,
                 max_pos_dist,
-            );
-            if s < min_score {
-                min_score = s;
-                min_color = pcolor;
-                min_pos = (px, py);
+            )
+        });
+        if let Some(confidence) = &confidence {
+            let margin = (second_score - min_score) / second_score.abs().max(f64::EPSILON);
+            #[allow(clippy::cast_possible_truncation)]
+            confidence[idx].store((margin.clamp(0.0, 1.0) as f32).to_bits(), Ordering::Relaxed);
+        }
+        if let Some(epsilon) = args.boundary_dither {
+            // Even odds on the bisector, fading to none at epsilon
+            let margin = (second_score - min_score) / (epsilon * min_score.abs()).max(f64::EPSILON);
+            let chance = 0.5 * (1.0 - margin);
+            if chance > 0.0 && noise::pixel_hash(x, y, args.seed.unwrap_or_default()) < chance {
+                return second_label;
             }
         }
+        min_label
+    };
+    let progress = |y: u32| {
+        if print_progress {
+            eprint!("\rCalculating voronoi diagram... {y} / {rows} rows");
+        }
+    };
+    let labels = if let Some(block) = args.coarse_to_fine {
+        labels::coarse_to_fine(window.width, rows, block, label_of, progress)
+    } else {
+        labels::by_rows(window.width, rows, label_of, progress)
+    };
+    if print_progress {
+        eprintln!("\rCalculating voronoi diagram... {rows} / {rows} rows");
+    }
+    timings.add("assignment", assignment.elapsed());
+    let confidence = confidence.map(|confidence| {
+        let margins = confidence.into_iter().map(AtomicU32::into_inner);
+        margins.map(f32::from_bits).collect()
+    });
+    (labels, confidence)
+}
+
+/// Picks the fill color of every cell.
+fn cell_colors(
+    img: &image::RgbImage,
+    labels: &[u32],
+    stats: &[cells::CellStats],
+    points: &[(u32, u32, [u8; 3])],
+    args: &Args,
+) -> Vec<[u8; 3]> {
+    let (width, height) = img.dimensions();
+    let seed_colors = || points.iter().map(|&(_, _, color)| color);
+    // Tonal remapping works from each cell's overall tone, not a single pixel
+    let tone_mapped = args.posterize.is_some() || args.duotone.is_some();
+    let mut colors: Vec<[u8; 3]> = match args.cell_color {
+        CellColor::Seed if !tone_mapped => seed_colors().collect(),
+        CellColor::Seed | CellColor::Average => Iterator::zip(stats.iter(), seed_colors())
+            .map(|(stats, seed)| stats.average().unwrap_or(seed))
+            .collect(),
+        CellColor::Random { seed } => {
+            colorize::random_colors(points.len(), &mut args.rng.seeded(seed))
+        }
+        CellColor::RandomSafe { seed } => colorize::safe_colors(
+            &labels::adjacency(labels, width, points.len()),
+            &mut args.rng.seeded(seed),
+        ),
+        CellColor::Map { property, colormap } => {
+            colorize::property_colors(stats, property, colormap, width, height)
+        }
+    };
+    if args.match_histogram {
+        colorize::match_histogram(&mut colors, stats, img);
+    }
+    if let Some(levels) = args.posterize {
+        for color in &mut colors {
+            *color = color::posterize(*color, levels);
+        }
+    }
+    if let Some(ramp) = &args.duotone {
+        for color in &mut colors {
+            *color = ramp.recolor(*color);
+        }
+    }
+    colors
+}
+
+fn render_cells(
+    window: Viewport,
+    labels: &[u32],
+    colors: &[[u8; 3]],
+    stats: &[cells::CellStats],
+    points: &[(u32, u32, [u8; 3])],
+    args: &Args,
+) -> image::RgbImage {
+    let fills: Vec<Fill> = Iterator::zip(colors.iter(), stats)
+        .map(|(&color, stats)| {
+            let fill = match args.cell_fill {
+                CellFill::Flat => None,
+                CellFill::Gradient => stats.gradient().map(Fill::Gradient),
+                CellFill::Pattern(pattern) => {
+                    pattern::CellPattern::new(pattern, stats, color).map(Fill::Pattern)
+                }
+            };
+            fill.unwrap_or(Fill::Flat(color))
+        })
+        .collect();
+    let (width, height) = (window.width, window.height);
+    let boundary_dist = args
+        .cell_gap
+        .map(|_| distance::boundary_distance(labels, width, height));
+    let layers = (args.style == Style::Papercut).then(|| effects::papercut_layers(colors));
+    let normals = args.bevel.map(|bevel| {
+        let peaks = effects::tile_peaks(stats, bevel, args.relief);
+        effects::bevel_normals(labels, width, height, bevel, &peaks)
+    });
+    image::RgbImage::from_fn(width, height, |x, y| {
+        let idx = (y * width + x) as usize;
+        let label = labels[idx] as usize;
+        let mut color = fills[label].at(x, y);
+        if let Some(layers) = &layers {
+            color = effects::papercut(color, labels, layers, width, x, y);
+        }
+        if let Some(normals) = &normals {
+            color = effects::shade(color, normals[idx], &args.light, args.specular);
+        }
+        let (px, py, _) = points[label];
 
         if let Some(radius) = args.point_radius
             && {
-                let dx = x.abs_diff(min_pos.0);
-                let dy = y.abs_diff(min_pos.1);
+                let dx = (window.x + x).abs_diff(px);
+                let dy = (window.y + y).abs_diff(py);
                 dx * dx + dy * dy < radius * (radius - 1)
             }
         {
-            min_color = min_color.map(|c| u8::MAX - c);
+            color = color.map(|c| u8::MAX - c);
         }
 
-        *pixel = image::Rgb(min_color);
+        if let (Some(gap), Some(dist)) = (args.cell_gap, &boundary_dist)
+            && f64::from(dist[idx]) * 2.0 < f64::from(gap)
+        {
+            color = args.grout_color;
+        }
+
+        image::Rgb(color)
+    })
+}
+
+/// A rendered diagram along with the cell assignment it was drawn from.
+pub struct Diagram {
+    pub image: image::RgbImage,
+    /// Index of the point owning each pixel, in row-major order
+    pub labels: Vec<u32>,
+    /// Score margin of each pixel from 0 for a tie to 1, with `--confidence`
+    pub confidence: Option<Vec<f32>>,
+    /// Fill color of each cell
+    pub colors: Vec<[u8; 3]>,
+    pub stats: Vec<cells::CellStats>,
+    /// Time spent in each phase of producing the diagram
+    pub timings: timings::Timings,
+}
 
-        if print_progress && x == 0 {
-            eprint!("\rCalculating voronoi diagram... {y} / {img_height} rows");
+#[allow(clippy::too_many_arguments)]
+fn generate_voronoi_(
+    img: &ImagePlane,
+    points: &[(u32, u32, [u8; 3])],
+    max_color_dist: f64,
+    max_pos_dist: f64,
+    score_fn: &ScoreFn,
+    args: &Args,
+    print_progress: bool,
+    window: Option<Viewport>,
+) -> Diagram {
+    let mut timings = timings::Timings::default();
+    let window = window.unwrap_or_else(|| Viewport::of(img));
+    let (width, height) = (window.width, window.height);
+    let blurred = [args.score_on, args.fill_from]
+        .contains(&Source::Blurred)
+        .then(|| timings.time("blur", || img.blurred()));
+    let source = |source| match (source, blurred) {
+        (Source::Blurred, Some(blurred)) => blurred,
+        _ => img.image(),
+    };
+    let (mut labels, confidence) = assign_labels_(
+        img,
+        source(args.score_on),
+        window,
+        points,
+        max_color_dist,
+        max_pos_dist,
+        score_fn,
+        args,
+        print_progress,
+        &mut timings,
+    );
+    let post_processing = Instant::now();
+    if let Some(sigma) = args.smooth_boundaries {
+        labels = labels::smooth_labels(&labels, width, height, sigma);
+    }
+    let filled = window.crop(source(args.fill_from));
+    let stats = cells::cell_stats(&filled, &labels, points.len());
+    let colors = args
+        .colorizer
+        .colors(&filled, &labels, &stats, points, args);
+    let mut image = render_cells(window, &labels, &colors, &stats, points, args);
+    if let Some(threshold) = args.detail_passthrough {
+        let detailed: Vec<bool> = stats
+            .iter()
+            .map(|stats| stats.color_variance().is_some_and(|v| v > threshold))
+            .collect();
+        let original = window.crop(img);
+        for ((pixel, original), &label) in image.pixels_mut().zip(original.pixels()).zip(&labels) {
+            if detailed[label as usize] {
+                *pixel = *original;
+            }
         }
     }
-    if print_progress {
-        eprintln!("\rCalculating voronoi diagram... {img_height} / {img_height} rows");
+    if args.style == Style::Bricks {
+        let layout = bricks::BrickLayout::new(&labels, &colors, width, height, args.brick_size);
+        image = layout.render(width, height);
+    }
+    if args.style == Style::Halftone {
+        image = halftone::render(&filled, args.points);
+    }
+    if let Some(palette) = &args.palette {
+        palette::quantize(&mut image, &labels, palette, args.dither);
+    }
+    timings.add("post-processing", post_processing.elapsed());
+    Diagram {
+        image,
+        labels,
+        confidence,
+        colors,
+        stats,
+        timings,
     }
-    voronoi
 }
 
 pub fn generate_voronoi(
-    img: &image::RgbImage,
+    img: &ImagePlane,
     points: &[(u32, u32, [u8; 3])],
     max_color_dist: f64,
     max_pos_dist: f64,
     score_fn: &ScoreFn,
     args: &Args,
-) -> image::RgbImage {
+) -> Diagram {
     generate_voronoi_(
         img,
         points,
@@ -172,17 +1556,50 @@ pub fn generate_voronoi(
         score_fn,
         args,
         false,
+        None,
     )
 }
 
+/// The label map of `points` on `img` alone, for steps that need to know
+/// which pixel belongs to which point but not what the cells look like.
+fn assign_labels(
+    img: &ImagePlane,
+    points: &[(u32, u32, [u8; 3])],
+    max_color_dist: f64,
+    max_pos_dist: f64,
+    score_fn: &ScoreFn,
+    args: &Args,
+) -> Vec<u32> {
+    let scored = match args.score_on {
+        Source::Original => img.image(),
+        Source::Blurred => img.blurred(),
+    };
+    let (labels, _) = assign_labels_(
+        img,
+        scored,
+        Viewport::of(img),
+        points,
+        max_color_dist,
+        max_pos_dist,
+        score_fn,
+        args,
+        false,
+        &mut timings::Timings::default(),
+    );
+    labels
+}
+
+/// Like `generate_voronoi`, but reporting progress and drawing only the
+/// pixels in `window` when one is given.
 pub fn generate_voronoi_print_progress(
-    img: &image::RgbImage,
+    img: &ImagePlane,
     points: &[(u32, u32, [u8; 3])],
     max_color_dist: f64,
     max_pos_dist: f64,
     score_fn: &ScoreFn,
     args: &Args,
-) -> image::RgbImage {
+    window: Option<Viewport>,
+) -> Diagram {
     generate_voronoi_(
         img,
         points,
@@ -191,80 +1608,1079 @@ pub fn generate_voronoi_print_progress(
         score_fn,
         args,
         true,
+        window,
     )
 }
 
-fn main() {
-    let args = Args::parse();
+fn exit_on_err<T, E: std::fmt::Display>(result: Result<T, E>, what: &str) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("Failed to {what}: {err}");
+        std::process::exit(1);
+    })
+}
+
+/// The document colors of a layered SVG: the `--palette` if one is given,
+/// otherwise every distinct cell color, most used first.
+fn swatches(voronoi: &Diagram, args: &Args) -> Vec<[u8; 3]> {
+    if let Some(palette) = &args.palette {
+        return palette.0.clone();
+    }
+    let mut counts: std::collections::HashMap<[u8; 3], usize> = std::collections::HashMap::new();
+    for &color in &voronoi.colors {
+        *counts.entry(color).or_default() += 1;
+    }
+    let mut colors: Vec<([u8; 3], usize)> = counts.into_iter().collect();
+    colors.sort_unstable_by_key(|&(color, count)| (std::cmp::Reverse(count), color));
+    colors.into_iter().map(|(color, _)| color).collect()
+}
+
+/// Writes the outputs that need the points: the `--also-write` exports next
+/// to the output and the distance and edge fields.
+fn write_also(
+    voronoi: &Diagram,
+    points: &[(u32, u32, [u8; 3])],
+    img_width: u32,
+    img_height: u32,
+    args: &Args,
+) {
+    for &kind in &args.also_write {
+        let path = kind.path(&args.output);
+        let result = output::save(&path, args.force, |path| match kind {
+            AlsoWrite::Svg => std::fs::write(
+                path,
+                export::svg(&voronoi.labels, &voronoi.colors, img_width, img_height),
+            )
+            .map_err(image::ImageError::IoError),
+            AlsoWrite::Labels => {
+                export::label_map(&voronoi.labels, img_width, img_height).save(path)
+            }
+            AlsoWrite::Points => std::fs::write(path, export::points_json(points))
+                .map_err(image::ImageError::IoError),
+            AlsoWrite::Layers => std::fs::write(
+                path,
+                export::layered_svg(
+                    &voronoi.labels,
+                    &voronoi.colors,
+                    points,
+                    (img_width, img_height),
+                    &swatches(voronoi, args),
+                ),
+            )
+            .map_err(image::ImageError::IoError),
+        });
+        exit_on_err(result, "save extra output");
+        eprintln!(
+            "Saved {} to {}",
+            kind.to_possible_value().unwrap().get_name(),
+            path.display()
+        );
+    }
+
+    if let (Some(path), Some(confidence)) = (&args.confidence, &voronoi.confidence) {
+        let (width, height) = voronoi.image.dimensions();
+        exit_on_err(
+            output::save(path, args.force, |path| {
+                export::save_unit_map(path, confidence, width, height)
+            }),
+            "save confidence",
+        );
+        eprintln!("Saved confidence to {}", path.display());
+    }
+
+    let fields = [
+        (
+            &args.distance_field,
+            "distance field",
+            distance::site_distance as fn(_, _, _) -> _,
+        ),
+        (&args.edge_field, "edge field", distance::edge_distance),
+    ];
+    for (path, name, field) in fields {
+        let Some(path) = path else { continue };
+        let values = field(points, img_width, img_height);
+        exit_on_err(
+            output::save(path, args.force, |path| {
+                export::save_distance_field(
+                    path,
+                    &values,
+                    img_width,
+                    img_height,
+                    args.distance_field_scale,
+                )
+            }),
+            &format!("save {name}"),
+        );
+        eprintln!("Saved {name} to {}", path.display());
+    }
+}
+
+/// Writes the cells cut out of the diagram, one file each or packed together.
+fn write_cell_exports(voronoi: &Diagram, args: &Args) {
+    if let Some(dir) = &args.export_cells {
+        let cells = voronoi.colors.len();
+        exit_on_err(
+            export::save_cells(dir, &voronoi.image, &voronoi.labels, cells, args.force),
+            "save cells",
+        );
+        eprintln!("Saved {cells} cells to {}", dir.display());
+    }
+
+    if let Some(path) = &args.sprite_sheet {
+        exit_on_err(
+            export::save_sprite_sheet(
+                path,
+                &voronoi.image,
+                &voronoi.labels,
+                &voronoi.stats,
+                args.force,
+            ),
+            "save sprite sheet",
+        );
+        eprintln!("Saved sprite sheet to {}", path.display());
+    }
+}
+
+/// Writes the optional outputs derived from a diagram besides the image itself.
+fn write_side_outputs(voronoi: &Diagram, img_width: u32, img_height: u32, args: &Args) {
+    if let (Some(path), Some(bevel)) = (&args.normal_map, args.bevel) {
+        let peaks = effects::tile_peaks(&voronoi.stats, bevel, args.relief);
+        let normals = effects::bevel_normals(&voronoi.labels, img_width, img_height, bevel, &peaks);
+        exit_on_err(
+            output::save(path, args.force, |path| {
+                effects::normal_map(&normals, img_width, img_height).save(path)
+            }),
+            "save normal map",
+        );
+        eprintln!("Saved normal map to {}", path.display());
+    }
 
-    let img = match image::open(&args.input) {
+    if let Some(path) = &args.boundary_matte {
+        let matte =
+            export::boundary_matte(&voronoi.labels, img_width, img_height, args.matte_width);
+        exit_on_err(
+            output::save(path, args.force, |path| matte.save(path)),
+            "save boundary matte",
+        );
+        eprintln!("Saved boundary matte to {}", path.display());
+    }
+
+    write_cell_exports(voronoi, args);
+
+    if args.style == Style::Bricks {
+        let layout = bricks::BrickLayout::new(
+            &voronoi.labels,
+            &voronoi.colors,
+            img_width,
+            img_height,
+            args.brick_size,
+        );
+        let (columns, rows) = layout.dimensions();
+        println!("Brick mosaic: {columns}x{rows} studs");
+        for (color, count) in layout.parts() {
+            println!("  {count:>6} x 1x1 plate, {color}");
+        }
+    }
+
+    if let Some(path) = &args.jigsaw {
+        let svg = jigsaw::svg(
+            &voronoi.labels,
+            &voronoi.stats,
+            img_width,
+            img_height,
+            &mut args.rng.seeded(args.seed.unwrap_or_default()),
+        );
+        exit_on_err(
+            output::save(path, args.force, |path| std::fs::write(path, svg)),
+            "save jigsaw",
+        );
+        eprintln!("Saved jigsaw to {}", path.display());
+    }
+
+    if let Some(path) = &args.stitch_chart {
+        let chart = stitch::chart(
+            &voronoi.labels,
+            &voronoi.colors,
+            img_width,
+            img_height,
+            args.stitch_count,
+        );
+        exit_on_err(
+            output::save(path, args.force, |path| chart.save(path)),
+            "save cross-stitch chart",
+        );
+        eprintln!("Saved cross-stitch chart to {}", path.display());
+    }
+
+    if args.heightmap.is_some() || args.mesh.is_some() {
+        let heights = extrude::cell_heights(&voronoi.colors, &voronoi.stats, args.extrude_by);
+        if let Some(path) = &args.heightmap {
+            exit_on_err(
+                output::save(path, args.force, |path| {
+                    extrude::heightmap(&voronoi.labels, &heights, img_width, img_height).save(path)
+                }),
+                "save heightmap",
+            );
+            eprintln!("Saved heightmap to {}", path.display());
+        }
+        if let Some(path) = &args.mesh {
+            let triangles = extrude::mesh(
+                &voronoi.labels,
+                &heights,
+                img_width,
+                img_height,
+                args.extrude_height,
+            );
+            let is_obj = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("obj"));
+            let result = output::save(path, args.force, |path| {
+                if is_obj {
+                    extrude::write_obj(path, &triangles)
+                } else {
+                    extrude::write_stl(path, &triangles)
+                }
+            });
+            exit_on_err(result, "save mesh");
+            eprintln!(
+                "Saved {} triangle mesh to {}",
+                triangles.len(),
+                path.display()
+            );
+        }
+    }
+}
+
+/// Writes the `--separations` SVGs, one per palette color in use.
+fn write_separations(voronoi: &Diagram, img_width: u32, img_height: u32, args: &Args) {
+    let (Some(dir), Some(palette)) = (&args.separations, &args.palette) else {
+        return;
+    };
+    exit_on_err(std::fs::create_dir_all(dir), "create separations directory");
+    let plates = export::separations(
+        &voronoi.labels,
+        &voronoi.colors,
+        palette,
+        img_width,
+        img_height,
+    );
+    for (entry, svg) in &plates {
+        let path = dir.join(export::separation_name(*entry, palette.0[*entry]));
+        exit_on_err(
+            output::save(&path, args.force, |path| std::fs::write(path, svg)),
+            "save separation",
+        );
+    }
+    eprintln!("Saved {} separations to {}", plates.len(), dir.display());
+}
+
+/// Writes the `--pyramid` levels below the diagram and the `--dzi` tiles.
+fn write_pyramid(voronoi: &Diagram, img_width: u32, img_height: u32, args: &Args) {
+    if let Some(path) = &args.dzi {
+        let tiles = pyramid::save_dzi_tiles(path, &voronoi.image, &voronoi.labels, &voronoi.colors);
+        exit_on_err(tiles, "save deep zoom tiles");
+        exit_on_err(
+            output::save(path, args.force, |path| {
+                std::fs::write(path, pyramid::dzi_descriptor(img_width, img_height))
+            }),
+            "save deep zoom image",
+        );
+        eprintln!("Saved deep zoom image to {}", path.display());
+    }
+    let levels = pyramid::levels(
+        &voronoi.labels,
+        img_width,
+        img_height,
+        args.pyramid.unwrap_or(0),
+    );
+    for (level, labels) in (1..).zip(&levels) {
+        let path = pyramid::path(&args.output, level);
+        exit_on_err(
+            output::save(&path, args.force, |path| {
+                labels.render(&voronoi.colors).save(path)
+            }),
+            "save pyramid level",
+        );
+        eprintln!(
+            "Saved {}x{} pyramid level to {}",
+            labels.width,
+            labels.height,
+            path.display()
+        );
+    }
+}
+
+/// Default of `--max-pixels`.
+pub const DEFAULT_MAX_PIXELS: u64 = 100_000_000;
+
+/// Most bytes a decoder may allocate per pixel of the largest image
+/// accepted; enough for 32-bit float RGBA.
+const DECODE_BYTES_PER_PIXEL: u64 = 16;
+
+/// Opens the image at `path` after reading only its header, refusing it if
+/// it is empty or has more than `max_pixels` pixels, and caps what the
+/// decoder may allocate, so a hostile or malformed file cannot exhaust
+/// memory.
+fn open_image(path: &Path, max_pixels: u64) -> Result<image::DynamicImage, String> {
+    let reader =
+        || -> image::ImageResult<_> { Ok(image::ImageReader::open(path)?.with_guessed_format()?) };
+    let (width, height) = reader()
+        .and_then(image::ImageReader::into_dimensions)
+        .map_err(|err| err.to_string())?;
+    check_pixels(width, height, max_pixels)?;
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(width);
+    limits.max_image_height = Some(height);
+    limits.max_alloc = Some(max_pixels.saturating_mul(DECODE_BYTES_PER_PIXEL));
+    let mut reader = reader().map_err(|err| err.to_string())?;
+    reader.limits(limits);
+    reader.decode().map_err(|err| err.to_string())
+}
+
+/// Fails for an empty image or one of more than `max_pixels` pixels.
+fn check_pixels(width: u32, height: u32, max_pixels: u64) -> Result<(), String> {
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels == 0 {
+        Err(format!("the image is empty ({width}x{height})"))
+    } else if pixels > max_pixels {
+        Err(format!(
+            "the image is {width}x{height}, over the --max-pixels limit of {max_pixels}"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The size of a `width` by `height` image enlarged `scale` times, or an
+/// error if that overflows or has more than `max_pixels` pixels.
+fn scaled_size(width: u32, height: u32, scale: u32, max_pixels: u64) -> Result<(u32, u32), String> {
+    let scaled = |side: u32| {
+        side.checked_mul(scale)
+            .ok_or_else(|| format!("{width}x{height} scaled {scale} times is too large"))
+    };
+    let (scaled_width, scaled_height) = (scaled(width)?, scaled(height)?);
+    check_pixels(scaled_width, scaled_height, max_pixels)?;
+    Ok((scaled_width, scaled_height))
+}
+
+/// Opens `path` as an RGB image, folded for `--symmetry` if requested.
+fn load_image(path: &Path, args: &Args) -> image::RgbImage {
+    let img = match open_image(path, args.max_pixels) {
         Err(err) => {
             eprintln!("Failed to open image: {err}");
             std::process::exit(1);
         }
-        Ok(img) => img.into_rgb8(),
+        Ok(mut img) => {
+            // The copied metadata says upright, so the pixels have to be
+            if args.copy_metadata {
+                let camera = exit_on_err(metadata::read_camera(path), "read image metadata");
+                img.apply_orientation(camera.orientation);
+            }
+            img.into_rgb8()
+        }
     };
-    let (img_width, img_height) = img.dimensions();
-    let img_size = img_height * img_width;
-    println!("Image dimensions: {img_width}x{img_height}");
+    match args.symmetry {
+        Some(symmetry) => symmetry.fold(&img),
+        None => img,
+    }
+}
+
+/// `args` with `--points` worked out from `--density` for an image of
+/// `width` by `height` pixels, at least one.
+fn with_density(args: &Args, (width, height): (u32, u32)) -> Args {
+    let mut args = args.clone();
+    if let Some(density) = args.density {
+        let megapixels = f64::from(width) * f64::from(height) / 1e6;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let points = (density * megapixels).round().max(1.0) as usize;
+        args.points = points;
+    }
+    args
+}
+
+/// `args` with the parameters that depend on the input worked out for `img`.
+fn for_image(args: &Args, img: &image::RgbImage) -> Args {
+    let mut args = with_density(args, img.dimensions());
+    if args.auto {
+        auto::apply(&mut args, img);
+    }
+    if args.points == AUTO_POINTS {
+        args.points = importance::auto_points(img, &args);
+    }
+    fit_points(&mut args, img.dimensions());
+    if let Some(shard) = args.shard {
+        shard::reject_unseamed_options(&args);
+        let (y, height) = exit_on_err(shard.rows(img.height()), "render shard");
+        println!(
+            "Shard {} of {}: rows {y} to {}",
+            shard.index,
+            shard.count,
+            y + height - 1
+        );
+        args.viewport = Some(Viewport {
+            x: 0,
+            y,
+            width: img.width(),
+            height,
+        });
+    }
+    args
+}
+
+/// Applies `--point-overflow` when `args` asks for more points than a
+/// `width` by `height` image has pixels.
+fn fit_points(args: &mut Args, (width, height): (u32, u32)) {
+    let pixels = usize::try_from(u64::from(width) * u64::from(height)).unwrap_or(usize::MAX);
+    if args.points <= pixels {
+        return;
+    }
+    match args.point_overflow {
+        PointOverflow::Allow => {}
+        PointOverflow::Cap => {
+            println!("Capping {} points at the {pixels} pixels", args.points);
+            args.points = pixels;
+        }
+        PointOverflow::Error => {
+            eprintln!(
+                "{} points are more than the {pixels} pixels of the image",
+                args.points
+            );
+            std::process::exit(1);
+        }
+    }
+}
 
-    let max_pos_dist = f64::from(img_width.pow(2)) + f64::from(img_height.pow(2));
+/// Normalization constants `(max_color_dist, max_pos_dist)` for scoring `img`.
+fn max_dists(img: &image::RgbImage, args: &Args) -> (f64, f64) {
+    let metric = metric::Metric::new(args.projection, img.width(), img.height());
     let max_color_dist = 255.0 * f64::from(<image::Rgb<u8> as image::Pixel>::CHANNEL_COUNT);
+    (max_color_dist, metric.max_pos_dist())
+}
 
-    let mut rng = {
-        let seed = match args.seed {
-            Some(seed) => seed,
-            None => rand::rng().random::<u64>(),
-        };
-        println!("Seed: {seed}");
-        StdRng::seed_from_u64(seed)
+fn seeded_rng(args: &Args) -> rng::Generator {
+    let seed = match args.seed {
+        Some(seed) => seed,
+        None => rand::rng().random::<u64>(),
     };
+    println!("Seed: {seed}");
+    args.rng.seeded(seed)
+}
 
-    println!("Points: {}", args.points);
-    println!("Color weight: {}", args.weight);
-
-    let pixels = {
-        eprint!("Indexing {img_size} pixels...");
-        let mut pixels = Vec::with_capacity(img_size as usize);
-        for (x, y, px) in img.enumerate_pixels() {
-            pixels.push((x, y, px.0));
-            if x == 0 {
-                eprint!("\rIndexing {img_size} pixels... {y} / {img_height} rows");
+fn sample_points_(
+    img: &image::RgbImage,
+    args: &Args,
+    count: usize,
+    rng: &mut rng::Generator,
+    print_progress: bool,
+) -> Vec<(u32, u32, [u8; 3])> {
+    if args.proxy_scale >= 1.0 {
+        return sample_pixels(img, args, count, rng, print_progress);
+    }
+    // Sample on a downscaled proxy, then map the points back to full size
+    // and take their colors from there
+    let (img_width, img_height) = img.dimensions();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let scaled = |size: u32| ((f64::from(size) * args.proxy_scale).round() as u32).max(1);
+    let proxy = image::imageops::resize(
+        img,
+        scaled(img_width),
+        scaled(img_height),
+        image::imageops::FilterType::Triangle,
+    );
+    let (scale_x, scale_y) = (
+        f64::from(img_width) / f64::from(proxy.width()),
+        f64::from(img_height) / f64::from(proxy.height()),
+    );
+    sample_pixels(&proxy, args, count, rng, print_progress)
+        .into_iter()
+        .map(|(x, y, _)| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let full = |v: u32, scale: f64, size: u32| {
+                (((f64::from(v) + 0.5) * scale) as u32).min(size - 1)
+            };
+            let (x, y) = (full(x, scale_x, img_width), full(y, scale_y, img_height));
+            (x, y, img.get_pixel(x, y).0)
+        })
+        .collect()
+}
+
+/// How strongly sampling favors pixel `(x, y)` of a `width` by `height`
+/// image, before normalizing.
+fn selection_weight(
+    args: &Args,
+    metric: &metric::Metric,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> f64 {
+    weight(
+        &(x, y, []),
+        width,
+        height,
+        args.selection_power,
+        args.selection_offset,
+    )
+    .max(0.0)
+        * metric.area_weight(y)
+}
+
+/// Sampling odds from `weights`, or even odds with a warning if none is
+/// positive, as when `--selection-offset` outweighs every pixel.
+fn selection_index(weights: impl IntoIterator<Item = f64>) -> WeightedIndex<f64> {
+    let weights: Vec<f64> = weights.into_iter().collect();
+    WeightedIndex::new(&weights).unwrap_or_else(|_| {
+        eprintln!("Warning: no pixel has a positive selection weight, sampling uniformly");
+        WeightedIndex::new(vec![1.0; weights.len()]).expect("images are never empty")
+    })
+}
+
+/// Draws pixel positions of a `width` by `height` image with the odds of
+/// [`selection_weight`].
+fn position_sampler(
+    args: &Args,
+    width: u32,
+    height: u32,
+) -> impl Fn(&mut rng::Generator) -> (u32, u32) {
+    let metric = metric::Metric::new(args.projection, width, height);
+    let weights = selection_index(
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| selection_weight(args, &metric, x, y, width, height)),
+    );
+    move |rng| {
+        let index = u32::try_from(weights.sample(rng)).expect("pixel indices fit in u32");
+        (index % width, index / width)
+    }
+}
+
+fn sample_pixels(
+    img: &image::RgbImage,
+    args: &Args,
+    count: usize,
+    rng: &mut rng::Generator,
+    print_progress: bool,
+) -> Vec<(u32, u32, [u8; 3])> {
+    let (img_width, img_height) = img.dimensions();
+    if print_progress {
+        eprint!("Generating {count} points...");
+    }
+    let draw = position_sampler(args, img_width, img_height);
+    let mut points: Vec<(u32, u32, [u8; 3])> = Vec::with_capacity(count);
+    match args.symmetry {
+        None => {
+            for _ in 0..count {
+                let (x, y) = draw(rng);
+                points.push((x, y, img.get_pixel(x, y).0));
             }
         }
-        eprintln!("\rIndexing {img_size} pixels... {img_height} / {img_height} rows",);
-        pixels
-    };
+        Some(symmetry) => {
+            // Sample whole orbits until there are enough points
+            while points.len() < count {
+                let (x, y) = draw(rng);
+                let color = img.get_pixel(x, y).0;
+                for (ox, oy) in symmetry.orbit(x, y, img_width, img_height) {
+                    points.push((ox, oy, color));
+                }
+            }
+        }
+    }
+    if print_progress {
+        eprintln!("\rGenerating {count} points... Done");
+    }
+    points
+}
 
-    let points = {
-        eprint!("Generating {} points...", args.points);
-        let mut points: Vec<(u32, u32, [u8; 3])> = Vec::with_capacity(args.points);
-        let weights = WeightedIndex::new(pixels.iter().map(|px| {
-            weight(
-                px,
-                img_width,
-                img_height,
-                args.selection_power,
-                args.selection_offset,
-            )
-            .max(0.0)
-        }))
-        .unwrap();
-        for _ in 0..args.points {
-            let idx = weights.sample(&mut rng);
-            points.push(pixels[idx]);
-        }
-        eprintln!("\rGenerating {} points... Done", args.points);
+/// Samples `count` points from `img`, favouring pixels by `weight`.
+pub fn sample_points(
+    img: &image::RgbImage,
+    args: &Args,
+    count: usize,
+    rng: &mut rng::Generator,
+) -> Vec<(u32, u32, [u8; 3])> {
+    args.sampler.sample(img, args, count, rng, false)
+}
+
+/// Samples `count` points from `img`, favouring pixels by `weight`, printing
+/// progress to stderr.
+pub fn sample_points_print_progress(
+    img: &image::RgbImage,
+    args: &Args,
+    count: usize,
+    rng: &mut rng::Generator,
+) -> Vec<(u32, u32, [u8; 3])> {
+    args.sampler.sample(img, args, count, rng, true)
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+/// Parameters recorded in the output at `path`, overridden by those given on
+/// this command line.
+fn liked_args(path: &Path) -> Args {
+    let recorded = exit_on_err(metadata::read_command(path), "read parameters");
+    let recorded_matches = exit_on_err(
+        Cli::command().try_get_matches_from(&recorded),
+        "parse recorded parameters",
+    );
+    if recorded_matches.subcommand().is_some() {
+        eprintln!(
+            "Failed to read parameters: {} was not made by a plain render",
+            path.display()
+        );
+        std::process::exit(1);
+    }
+    // Keep the recorded options but take the input and output from this run
+    let positionals = [
+        recorded_matches.index_of("input"),
+        recorded_matches.index_of("output"),
+    ];
+    let mut command: Vec<String> = recorded
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !positionals.contains(&Some(*i)))
+        .map(|(_, arg)| arg)
+        .collect();
+    // Later occurrences override earlier ones, so options given here win
+    let mut current = std::env::args().skip(1);
+    while let Some(arg) = current.next() {
+        if arg == "--like" {
+            current.next();
+        } else if !arg.starts_with("--like=") {
+            command.push(arg);
+        }
+    }
+
+    let cli = exit_on_err(Cli::try_parse_from(&command), "apply parameters");
+    let mut args = cli.args.expect("clap requires INPUT and OUTPUT");
+    args.command_line = command;
+    args
+}
+
+/// Delay between the frames written by `--relax-frames`.
+const RELAX_FRAME_DELAY_MS: u32 = 200;
+
+fn render(args: &Args) {
+    match args.pipeline {
+        Pipeline::Gray => return gray::render(args),
+        Pipeline::Multispectral => return multispectral::render(args),
+        Pipeline::Rgb => {}
+    }
+    // Settle the seed up front so it can be recorded with the output
+    let mut args = args.clone();
+    args.seed.get_or_insert_with(|| rand::rng().random());
+    let mut timings = timings::Timings::default();
+    let img = timings.time("load", || load_image(&args.input, &args));
+    render_image(&img, &args, timings);
+}
+
+/// Runs `--relax` steps of Lloyd relaxation on `points`, writing the
+/// intermediate diagrams to `--relax-frames` if given.
+fn relax_points(
+    img: &ImagePlane,
+    mut points: Vec<(u32, u32, [u8; 3])>,
+    max_color_dist: f64,
+    max_pos_dist: f64,
+    distance: &ScoreFn,
+    args: &Args,
+) -> Vec<(u32, u32, [u8; 3])> {
+    let mut frames = Vec::new();
+    for i in 0..args.relax {
+        eprint!("\rRelaxing points... {i} / {}", args.relax);
+        // Centroids need only the labels; the cells are drawn for frames
+        let stats = if args.relax_frames.is_some() {
+            let step = generate_voronoi(img, &points, max_color_dist, max_pos_dist, distance, args);
+            frames.push(step.image);
+            step.stats
+        } else {
+            let labels = assign_labels(img, &points, max_color_dist, max_pos_dist, distance, args);
+            cells::cell_stats(img.image(), &labels, points.len())
+        };
+        points = relax::lloyd_step(img, &points, &stats);
+    }
+    eprintln!("\rRelaxing points... {0} / {0}", args.relax);
+    if let Some(path) = &args.relax_frames {
+        animate::write_gif(path, args.force, RELAX_FRAME_DELAY_MS, args.relax, |i| {
+            std::mem::take(&mut frames[i as usize])
+        });
+    }
+    points
+}
+
+/// Points sampled from `seed_img`, or loaded from `--cache-dir` if they were
+/// sampled before.
+fn initial_points(
+    seed_img: &image::RgbImage,
+    args: &Args,
+    rng: &mut rng::Generator,
+    timings: &mut timings::Timings,
+) -> Vec<(u32, u32, [u8; 3])> {
+    let cache_key = args
+        .cache_dir
+        .as_ref()
+        .map(|dir| (dir, cache::key(seed_img, args)));
+    let cached = cache_key.and_then(|(dir, key)| cache::load(dir, key));
+    if let Some(points) = cached {
+        eprintln!("Loaded {} cached points", points.len());
         points
-    };
+    } else {
+        let points = timings.time("sampling", || {
+            sample_points_print_progress(seed_img, args, args.points, rng)
+        });
+        if let Some((dir, key)) = cache_key {
+            exit_on_err(cache::store(dir, key, &points), "cache points");
+        }
+        points
+    }
+}
 
-    let voronoi =
-        generate_voronoi_print_progress(&img, &points, max_color_dist, max_pos_dist, &score, &args);
+/// Exits if any file this render would write exists and `--force` is off.
+fn check_outputs(args: &Args) {
+    let side_outputs = [
+        &args.relax_frames,
+        &args.normal_map,
+        &args.boundary_matte,
+        &args.jigsaw,
+        &args.stitch_chart,
+        &args.heightmap,
+        &args.mesh,
+        &args.distance_field,
+        &args.edge_field,
+        &args.confidence,
+        &args.sprite_sheet,
+        &args.dzi,
+        &args
+            .sprite_sheet
+            .as_ref()
+            .map(|path| path.with_extension("json")),
+    ];
+    let also_write = args.also_write.iter().map(|kind| kind.path(&args.output));
+    let pyramid = (1..=args.pyramid.unwrap_or(0)).map(|level| pyramid::path(&args.output, level));
+    let separations = args.separations.iter().flat_map(|dir| {
+        let palette = args.palette.as_ref().map_or(&[][..], |palette| &palette.0);
+        (0..)
+            .zip(palette)
+            .map(|(entry, &color)| dir.join(export::separation_name(entry, color)))
+    });
+    let cell_exports = args
+        .export_cells
+        .iter()
+        .flat_map(|dir| export::cell_export_paths(dir));
+    for path in std::iter::once(args.output.clone())
+        .chain(side_outputs.into_iter().flatten().cloned())
+        .chain(cell_exports)
+        .chain(also_write)
+        .chain(pyramid)
+        .chain(separations)
+        .chain(args.debug_dir.iter().flat_map(|dir| debug::paths(dir)))
+    {
+        exit_on_err(output::check_clobber(&path, args.force), "write output");
+    }
+}
 
-    let save_result = voronoi.save(&args.output);
+/// Saves the diagram to the output in the `--format` asked for.
+fn save_output(voronoi: &Diagram, args: &Args) {
+    let image = &voronoi.image;
+    let icc = match (&args.icc, args.format) {
+        (Some(path), Format::TiffCmyk) => {
+            Some(exit_on_err(std::fs::read(path), "read ICC profile"))
+        }
+        _ => None,
+    };
+    let camera = match args.format {
+        Format::Image if args.copy_metadata => Some(exit_on_err(
+            metadata::read_camera(&args.input),
+            "read image metadata",
+        )),
+        _ if args.copy_metadata => {
+            eprintln!("Warning: --copy-metadata only applies to image outputs");
+            None
+        }
+        _ => None,
+    };
+    let save_result = output::save(&args.output, args.force, |path| match args.format {
+        Format::Image if is_png(path) => {
+            metadata::save_png(path, image, metadata::text_chunks(args), camera.as_ref())
+        }
+        Format::Image => match &camera {
+            Some(camera) => metadata::save_with_camera(path, image, camera),
+            None => image.save(path),
+        },
+        Format::Ansi => std::fs::write(path, text::ansi(image, args.columns))
+            .map_err(image::ImageError::IoError),
+        Format::Ascii => std::fs::write(path, text::ascii(image, args.columns))
+            .map_err(image::ImageError::IoError),
+        Format::Pdf => {
+            let (width, height) = image.dimensions();
+            std::fs::write(
+                path,
+                pdf::vector(&voronoi.labels, &voronoi.colors, width, height),
+            )
+            .map_err(image::ImageError::IoError)
+        }
+        Format::PdfRaster => {
+            std::fs::write(path, pdf::raster(image)).map_err(image::ImageError::IoError)
+        }
+        Format::TiffCmyk => cmyk::save(path, image, icc.as_deref(), args.black_generation)
+            .map_err(|err| image::ImageError::IoError(std::io::Error::other(err))),
+    });
     if let Err(err) = save_result {
         eprintln!("Failed to save image: {err}");
         std::process::exit(1);
     }
+}
+
+/// Runs the pipeline on `img` up to the finished diagram, adding its phases
+/// to `timings`. Returns the diagram and the points it was grown from.
+fn build_diagram(
+    img: &image::RgbImage,
+    args: &Args,
+    timings: &mut timings::Timings,
+) -> (Diagram, Vec<(u32, u32, [u8; 3])>) {
+    let (img_width, img_height) = img.dimensions();
+    println!("Image dimensions: {img_width}x{img_height}");
+
+    let (max_color_dist, max_pos_dist) = max_dists(img, args);
+    let mut rng = seeded_rng(args);
+
+    println!("Points: {}", args.points);
+    match (args.balance, args.weight_ramp) {
+        (Some(balance), _) => println!("Balance: {balance}"),
+        (None, Some(ramp)) => println!("Color weight: {ramp:?}"),
+        (None, None) => println!("Color weight: {}", args.weight),
+    }
+
+    if let Some(viewport) = args.viewport {
+        exit_on_err(viewport.check(img), "render viewport");
+        println!(
+            "Viewport: {}x{} at ({}, {})",
+            viewport.width, viewport.height, viewport.x, viewport.y
+        );
+    }
+    let img = &ImagePlane::new(img, args.blur);
+    let seed_img = match args.seed_from {
+        Source::Original => img.image(),
+        Source::Blurred => timings.time("blur", || img.blurred()),
+    };
+
+    let mut points = initial_points(seed_img, args, &mut rng, timings);
+    let initial = args.debug_dir.is_some().then(|| points.clone());
+
+    if let Some(radius) = args.snap_to_edges {
+        points = timings.time("edge snapping", || edges::snap(img, &points, radius));
+    }
+
+    let distance = if args.style == Style::Brush {
+        timings.time("orientation", || score_fn_for(img, args))
+    } else {
+        distance_fn(args)
+    };
+
+    if args.relax > 0 {
+        points = timings.time("relaxation", || {
+            relax_points(img, points, max_color_dist, max_pos_dist, &distance, args)
+        });
+    }
+
+    let relaxed = args.debug_dir.is_some().then(|| points.clone());
+
+    let text_mask = args.text_safety.map(|mode| {
+        (
+            mode,
+            timings.time("text detection", || legibility::text_mask(img)),
+        )
+    });
+    if let Some((legibility::TextSafety::Densify, mask)) = &text_mask {
+        points.extend(legibility::densify(img, mask, args.points, &mut rng));
+    }
+
+    points = args.seed_color.apply(seed_img, &points);
+
+    if let Some(anneal::Optimize::Anneal { iterations }) = args.optimize {
+        points = timings.time("optimization", || {
+            anneal::anneal(img.image(), points, iterations, args)
+        });
+    }
+
+    if let Some(threshold) = args.reject_outliers {
+        let first_pass = timings.time("outlier rejection", || {
+            generate_voronoi(img, &points, max_color_dist, max_pos_dist, &distance, args)
+        });
+        let replaced;
+        (points, replaced) = outliers::reject(img, &points, &first_pass.labels, threshold);
+        println!("Replaced {replaced} outlier point colors");
+    }
+
+    if args.site_colors > 1 {
+        points = multicolor::expand(img, &points, usize::from(args.site_colors));
+    }
+
+    let mut voronoi = generate_voronoi_print_progress(
+        img,
+        &points,
+        max_color_dist,
+        max_pos_dist,
+        &distance,
+        args,
+        args.viewport,
+    );
+    if args.levels > 1 {
+        let max_dists = (max_color_dist, max_pos_dist);
+        (voronoi, points) =
+            hierarchy::subdivide(img, voronoi, points, max_dists, &distance, args, &mut rng);
+    }
+    timings.extend(&voronoi.timings);
+    if let Some((legibility::TextSafety::Exclude, mask)) = &text_mask {
+        legibility::restore(&mut voronoi.image, img, mask);
+    }
+    if let (Some(dir), Some(initial), Some(relaxed)) = (&args.debug_dir, initial, relaxed) {
+        debug::write(dir, img, &initial, &relaxed, &voronoi, args);
+    }
+    (voronoi, points)
+}
+
+/// [`build_diagram`], or the blend of `--ensemble` diagrams if asked for.
+fn build_diagram_or_ensemble(
+    img: &image::RgbImage,
+    args: &Args,
+    timings: &mut timings::Timings,
+) -> (Diagram, Vec<(u32, u32, [u8; 3])>) {
+    match args.ensemble {
+        Some(members) => ensemble::build(img, args, members, args.ensemble_mode, timings),
+        None => build_diagram(img, args, timings),
+    }
+}
+
+/// Runs the whole pipeline on an already loaded `img` and saves the result,
+/// adding its phases to `timings`.
+fn render_image(img: &image::RgbImage, args: &Args, mut timings: timings::Timings) {
+    let args = &for_image(args, img);
+    // Fail before the work rather than at the end of it
+    check_outputs(args);
+
+    let (mut voronoi, points) = match args.passes {
+        1 => build_diagram_or_ensemble(img, args, &mut timings),
+        passes => passes::build(img, args, passes, &mut timings),
+    };
+    let (img_width, img_height) = voronoi.image.dimensions();
+    if let (Some(mask), Some(params)) = (&args.region_mask, &args.region_params) {
+        regions::composite(img, &mut voronoi, args, mask, params, &mut timings);
+    }
+    if let Some(effects) = &args.post {
+        let finishing = Instant::now();
+        effects.apply(&mut voronoi.image, args);
+        timings.add("finishing", finishing.elapsed());
+    }
+    annotate::apply(&mut voronoi.image, args);
+
+    let encode = Instant::now();
+    write_side_outputs(&voronoi, img_width, img_height, args);
+    write_pyramid(&voronoi, img_width, img_height, args);
+    write_separations(&voronoi, img_width, img_height, args);
+    write_also(&voronoi, &points, img_width, img_height, args);
+
+    save_output(&voronoi, args);
     eprintln!("Saved voronoi diagram to {}", &args.output.display());
+    timings.add("encode", encode.elapsed());
+
+    if args.timings {
+        timings.print();
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    match &cli.command {
+        None => {
+            let args = cli.args.as_ref().expect("clap requires INPUT and OUTPUT");
+            match &args.like {
+                Some(path) => render(&liked_args(path)),
+                None => render(args),
+            }
+        }
+        Some(Command::Animate(animate)) => animate::run(animate),
+        Some(Command::Generate(generate)) => generate::run(generate),
+        Some(Command::Treemap(treemap)) => treemap::run(treemap),
+        Some(Command::Rasterize(rasterize)) => rasterize::run(rasterize),
+        Some(Command::Worley(worley)) => worley::run(worley),
+        Some(Command::Edit(edit)) => edit::run(edit),
+        Some(Command::Render(render)) => project::run(render),
+        Some(Command::Encode(encode)) => codec::run_encode(encode),
+        Some(Command::Decode(decode)) => codec::run_decode(decode),
+        Some(Command::Selftest(selftest)) => selftest::run(selftest),
+        Some(Command::Merge(merge)) => shard::run(merge),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(img: &image::RgbImage, options: &[&str]) -> Diagram {
+        let args = testing::args(options);
+        let (max_color_dist, max_pos_dist) = max_dists(img, &args);
+        let plane = ImagePlane::new(img, args.blur);
+        let mut rng = seeded_rng(&args);
+        let points = sample_points(img, &args, args.points, &mut rng);
+        generate_voronoi(
+            &plane,
+            &points,
+            max_color_dist,
+            max_pos_dist,
+            &score_fn_for(&plane, &args),
+            &args,
+        )
+    }
+
+    #[test]
+    fn posterized_cells_take_their_average_to_the_nearest_step() {
+        let img = testing::image(64, 48);
+        let options = ["-p", "30", "--seed", "1", "--posterize", "4"];
+        let diagram = render(&img, &options);
+        for (color, stats) in diagram.colors.iter().zip(&diagram.stats) {
+            let average = stats.average().expect("every sampled site owns a pixel");
+            assert_eq!(*color, color::posterize(average, 4));
+        }
+
+        // Grays keep their luminance, so they land exactly on the steps
+        let gray = image::imageops::grayscale(&img);
+        let gray = image::DynamicImage::ImageLuma8(gray).into_rgb8();
+        let diagram = render(&gray, &options);
+        for pixel in diagram.image.pixels() {
+            assert!(
+                [0, 85, 170, 255].map(|level| [level; 3]).contains(&pixel.0),
+                "{pixel:?}"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    //! Helpers shared by the tests of several modules.
+
+    use clap::Parser;
+
+    /// Render options parsed from `options` as if given on the command line.
+    pub fn args(options: &[&str]) -> super::Args {
+        let command = ["voronoi", "in.png", "out.png"].iter().chain(options);
+        super::Cli::try_parse_from(command)
+            .expect("options parse")
+            .args
+            .expect("a plain render")
+    }
+
+    /// A `width` by `height` image of smooth ramps crossed by finer texture,
+    /// so cells differ in both position and color.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn image(width: u32, height: u32) -> image::RgbImage {
+        image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([
+                (x * 255 / width) as u8,
+                (y * 255 / height) as u8,
+                ((x * y) % 251) as u8,
+            ])
+        })
+    }
 }
@@ -0,0 +1,263 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use image::metadata::Orientation;
+use image::{ImageDecoder, ImageEncoder};
+
+use crate::Args;
+
+/// Keyword of the PNG text chunk holding XMP, and the namespace prefixing
+/// XMP in a JPEG APP1 segment.
+const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+const XMP_JPEG_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Capture metadata of a photo, for `--copy-metadata`.
+#[derive(Debug)]
+pub struct Camera {
+    /// Raw EXIF, with its orientation reset to upright
+    pub exif: Option<Vec<u8>>,
+    /// XMP packet, with any orientation reset likewise
+    pub xmp: Option<Vec<u8>>,
+    /// How the image has to be turned to show upright, baked into the
+    /// pixels instead of copied
+    pub orientation: Orientation,
+}
+
+/// `xmp` with any `tiff:Orientation`, attribute or element, set to upright.
+fn upright_xmp(xmp: &[u8]) -> Vec<u8> {
+    let mut text = String::from_utf8_lossy(xmp).into_owned();
+    for (open, close) in [("tiff:Orientation=\"", "\""), ("<tiff:Orientation>", "<")] {
+        let mut from = 0;
+        while let Some(start) = text[from..].find(open).map(|at| from + at + open.len()) {
+            let Some(end) = text[start..].find(close).map(|at| start + at) else {
+                break;
+            };
+            text.replace_range(start..end, "1");
+            from = start + 1;
+        }
+    }
+    text.into_bytes()
+}
+
+/// Reads the EXIF and XMP of the image at `path`, taking the orientation
+/// out of both.
+pub fn read_camera(path: &Path) -> Result<Camera, String> {
+    let mut decoder = image::ImageReader::open(path)
+        .and_then(image::ImageReader::with_guessed_format)
+        .map_err(|err| err.to_string())?
+        .into_decoder()
+        .map_err(|err| err.to_string())?;
+    let mut exif = decoder.exif_metadata().map_err(|err| err.to_string())?;
+    let xmp = decoder.xmp_metadata().map_err(|err| err.to_string())?;
+    let orientation = exif
+        .as_mut()
+        .and_then(|exif| Orientation::remove_from_exif_chunk(exif))
+        .unwrap_or(Orientation::NoTransforms);
+    Ok(Camera {
+        exif,
+        xmp: xmp.as_deref().map(upright_xmp),
+        orientation,
+    })
+}
+
+/// Text chunks recording how a render was made: the crate version, the seed,
+/// the random number generator and the command line, with `--seed` and
+/// `--rng` added if they were left to defaults.
+pub fn text_chunks(args: &Args) -> Vec<(String, String)> {
+    let command_line = if args.command_line.is_empty() {
+        std::env::args().collect()
+    } else {
+        args.command_line.clone()
+    };
+    let mut command: Vec<String> = command_line
+        .into_iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("\"{arg}\"")
+            } else {
+                arg
+            }
+        })
+        .collect();
+    let mut chunks = vec![(
+        "Software".to_string(),
+        format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+    )];
+    let has_option = |command: &[String], name: &str| {
+        command
+            .iter()
+            .any(|arg| arg == name || arg.starts_with(&format!("{name}=")))
+    };
+    if let Some(seed) = args.seed {
+        if !has_option(&command, "--seed") {
+            command.push(format!("--seed={seed}"));
+        }
+        chunks.push(("Seed".to_string(), seed.to_string()));
+    }
+    if !has_option(&command, "--rng") {
+        command.push(format!("--rng={}", args.rng.name()));
+    }
+    chunks.push(("Rng".to_string(), args.rng.name().to_string()));
+    chunks.push(("Command".to_string(), command.join(" ")));
+    chunks
+}
+
+/// Saves `img` as a PNG with `chunks` as tEXt metadata, and the EXIF and
+/// XMP of `camera` if given.
+pub fn save_png<P: image::Pixel<Subpixel = u8>>(
+    path: &Path,
+    img: &image::ImageBuffer<P, Vec<u8>>,
+    chunks: Vec<(String, String)>,
+    camera: Option<&Camera>,
+) -> image::ImageResult<()> {
+    let to_image_error = |err: png::EncodingError| match err {
+        png::EncodingError::IoError(err) => image::ImageError::IoError(err),
+        err => image::ImageError::IoError(std::io::Error::other(err)),
+    };
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, img.width(), img.height());
+    encoder.set_color(if P::CHANNEL_COUNT == 1 {
+        png::ColorType::Grayscale
+    } else {
+        png::ColorType::Rgb
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in chunks {
+        encoder
+            .add_text_chunk(keyword, text)
+            .map_err(to_image_error)?;
+    }
+    if let Some(xmp) = camera.and_then(|camera| camera.xmp.as_deref()) {
+        encoder
+            .add_itxt_chunk(
+                XMP_KEYWORD.to_string(),
+                String::from_utf8_lossy(xmp).into_owned(),
+            )
+            .map_err(to_image_error)?;
+    }
+    let mut writer = encoder.write_header().map_err(to_image_error)?;
+    if let Some(exif) = camera.and_then(|camera| camera.exif.as_deref()) {
+        writer
+            .write_chunk(png::chunk::eXIf, exif)
+            .map_err(to_image_error)?;
+    }
+    writer
+        .write_image_data(img.as_raw())
+        .map_err(to_image_error)?;
+    writer.finish().map_err(to_image_error)
+}
+
+/// `jpeg` with an APP1 segment holding `xmp` after its leading application
+/// segments, or unchanged if `xmp` is too large for one segment.
+fn with_jpeg_xmp(jpeg: Vec<u8>, xmp: &[u8]) -> Vec<u8> {
+    let payload = [XMP_JPEG_NAMESPACE, xmp].concat();
+    let Ok(length) = u16::try_from(payload.len() + 2) else {
+        eprintln!("Warning: the input's XMP is too large for a JPEG segment, leaving it out");
+        return jpeg;
+    };
+    // Past the start of image marker and any APP0 to APP15 segments
+    let mut at = 2;
+    while jpeg.len() >= at + 4 && jpeg[at] == 0xff && (0xe0..=0xef).contains(&jpeg[at + 1]) {
+        at += 2 + usize::from(u16::from_be_bytes([jpeg[at + 2], jpeg[at + 3]]));
+    }
+    let at = at.min(jpeg.len());
+    let mut out = Vec::with_capacity(jpeg.len() + payload.len() + 4);
+    out.extend_from_slice(&jpeg[..at]);
+    out.extend_from_slice(&[0xff, 0xe1]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(&jpeg[at..]);
+    out
+}
+
+/// Saves `img` at `path` in the format its extension names, carrying the
+/// EXIF and XMP of `camera` where the format allows: both in JPEG, EXIF
+/// alone in WebP. PNGs go through [`save_png`] instead.
+pub fn save_with_camera(
+    path: &Path,
+    img: &image::RgbImage,
+    camera: &Camera,
+) -> image::ImageResult<()> {
+    let (width, height) = img.dimensions();
+    let color = image::ExtendedColorType::Rgb8;
+    match image::ImageFormat::from_path(path)? {
+        image::ImageFormat::Jpeg => {
+            let mut jpeg = Vec::new();
+            let mut encoder = image::codecs::jpeg::JpegEncoder::new(&mut jpeg);
+            if let Some(exif) = &camera.exif {
+                let _ = encoder.set_exif_metadata(exif.clone());
+            }
+            encoder.write_image(img.as_raw(), width, height, color)?;
+            if let Some(xmp) = &camera.xmp {
+                jpeg = with_jpeg_xmp(jpeg, xmp);
+            }
+            File::create(path)?.write_all(&jpeg)?;
+            Ok(())
+        }
+        image::ImageFormat::WebP => {
+            let mut encoder =
+                image::codecs::webp::WebPEncoder::new_lossless(BufWriter::new(File::create(path)?));
+            if let Some(exif) = &camera.exif {
+                let _ = encoder.set_exif_metadata(exif.clone());
+            }
+            if camera.xmp.is_some() {
+                eprintln!("Warning: WebP output keeps the input's EXIF but not its XMP");
+            }
+            encoder.write_image(img.as_raw(), width, height, color)
+        }
+        format => {
+            eprintln!(
+                "Warning: {} output cannot carry the input's metadata, leaving it out",
+                format.extensions_str().first().unwrap_or(&"this")
+            );
+            img.save(path)
+        }
+    }
+}
+
+/// Reads back the command line recorded by `text_chunks` in a PNG's metadata.
+pub fn read_command(path: &Path) -> Result<Vec<String>, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map_err(|err| err.to_string())?;
+    let chunk = reader
+        .info()
+        .uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == "Command")
+        .ok_or_else(|| format!("{} has no recorded parameters", path.display()))?;
+    Ok(split_command(&chunk.text))
+}
+
+/// Splits a command line on whitespace outside of double quotes.
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
@@ -0,0 +1,152 @@
+//! Positional distance between pixels under different image projections.
+
+use std::f64::consts::{PI, TAU};
+use std::str::FromStr;
+
+use crate::kernel;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Euclidean distance on the image plane
+    Planar,
+    /// Distance on the sphere for 360° equirectangular panoramas, wrapping
+    /// around in longitude
+    Equirect,
+    /// Distance in (radius, angle) space around a center, in pixels, or the
+    /// image center if not given
+    Polar(Option<(f64, f64)>),
+}
+
+impl FromStr for Projection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "planar" => Ok(Self::Planar),
+            None if s == "equirect" => Ok(Self::Equirect),
+            None if s == "polar" => Ok(Self::Polar(None)),
+            Some(("polar", center)) => {
+                let err = || format!("invalid polar center '{center}' (expected cx,cy)");
+                let (cx, cy) = center.split_once(',').ok_or_else(err)?;
+                let cx = cx.trim().parse().map_err(|_| err())?;
+                let cy = cy.trim().parse().map_err(|_| err())?;
+                Ok(Self::Polar(Some((cx, cy))))
+            }
+            _ => Err(format!(
+                "unknown projection '{s}' (expected planar, equirect or polar[:cx,cy])"
+            )),
+        }
+    }
+}
+
+/// Positional distance for one image size and projection.
+#[derive(Debug, Clone)]
+pub struct Metric {
+    projection: Projection,
+    width: u32,
+    height: u32,
+    /// Cosine and sine of the longitude of each column
+    lon: Vec<(f64, f64)>,
+    /// Cosine and sine of the latitude of each row
+    lat: Vec<(f64, f64)>,
+    /// Radius and angle of each pixel, in row-major order, with angles scaled
+    /// so that half a turn spans the largest radius
+    polar: Vec<(f64, f64)>,
+    /// Length of a full turn in the scaled angle coordinate of `polar`
+    polar_turn: f64,
+}
+
+impl Metric {
+    #[must_use]
+    pub fn new(projection: Projection, width: u32, height: u32) -> Self {
+        let mut polar_turn = 0.0;
+        let polar = match projection {
+            Projection::Polar(center) => {
+                let (cx, cy) = center.unwrap_or((f64::from(width) / 2.0, f64::from(height) / 2.0));
+                let max_radius = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+                    .map(|(fx, fy)| (fx * f64::from(width) - cx).hypot(fy * f64::from(height) - cy))
+                    .into_iter()
+                    .fold(1.0, f64::max);
+                polar_turn = max_radius * 2.0;
+                (0..height)
+                    .flat_map(|y| (0..width).map(move |x| (x, y)))
+                    .map(|(x, y)| {
+                        let (dx, dy) = (f64::from(x) + 0.5 - cx, f64::from(y) + 0.5 - cy);
+                        (dx.hypot(dy), dy.atan2(dx) * max_radius / PI)
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        let (lon, lat) = match projection {
+            Projection::Planar | Projection::Polar(_) => (Vec::new(), Vec::new()),
+            Projection::Equirect => (
+                (0..width)
+                    .map(|x| {
+                        let lon = (f64::from(x) + 0.5) / f64::from(width) * TAU;
+                        (lon.cos(), lon.sin())
+                    })
+                    .collect(),
+                (0..height)
+                    .map(|y| {
+                        let lat = PI / 2.0 - (f64::from(y) + 0.5) / f64::from(height) * PI;
+                        (lat.cos(), lat.sin())
+                    })
+                    .collect(),
+            ),
+        };
+        Self {
+            projection,
+            width,
+            height,
+            lon,
+            lat,
+            polar,
+            polar_turn,
+        }
+    }
+
+    /// Squared distance between pixels `a` and `b`.
+    #[must_use]
+    pub fn pos_dist(&self, a: (u32, u32), b: (u32, u32)) -> f64 {
+        match self.projection {
+            Projection::Planar => kernel::planar_dist(a, b),
+            Projection::Equirect => {
+                // Squared chord between the points on the unit sphere
+                let (a_lon, a_lat) = (self.lon[a.0 as usize], self.lat[a.1 as usize]);
+                let (b_lon, b_lat) = (self.lon[b.0 as usize], self.lat[b.1 as usize]);
+                let cos_dlon = a_lon.0 * b_lon.0 + a_lon.1 * b_lon.1;
+                let cos_angle = a_lat.1 * b_lat.1 + a_lat.0 * b_lat.0 * cos_dlon;
+                2.0 - 2.0 * cos_angle
+            }
+            Projection::Polar(_) => {
+                let (a_r, a_theta) = self.polar[(a.1 * self.width + a.0) as usize];
+                let (b_r, b_theta) = self.polar[(b.1 * self.width + b.0) as usize];
+                let turn = self.polar_turn;
+                let dtheta = (a_theta - b_theta).rem_euclid(turn);
+                (a_r - b_r).powi(2) + dtheta.min(turn - dtheta).powi(2)
+            }
+        }
+    }
+
+    /// Normalization for [`Self::pos_dist`], chosen so that a given pixel
+    /// offset on the image plane, or along the equator of a panorama, weighs
+    /// the same against color distance.
+    #[must_use]
+    pub fn max_pos_dist(&self) -> f64 {
+        let diagonal = f64::from(self.width).powi(2) + f64::from(self.height).powi(2);
+        match self.projection {
+            Projection::Planar | Projection::Polar(_) => diagonal,
+            Projection::Equirect => (TAU / f64::from(self.width)).powi(2) * diagonal,
+        }
+    }
+
+    /// Area covered by a pixel in row `y`, relative to the image plane.
+    #[must_use]
+    pub fn area_weight(&self, y: u32) -> f64 {
+        match self.projection {
+            Projection::Planar | Projection::Polar(_) => 1.0,
+            Projection::Equirect => self.lat[y as usize].0,
+        }
+    }
+}
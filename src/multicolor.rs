@@ -0,0 +1,84 @@
+//! Sites that carry several representative colors, so a cell straddling an
+//! edge can take a different color on each side of it.
+
+type Point = (u32, u32, [u8; 3]);
+
+fn dist2(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Up to `k` representative colors of `samples` by k-means, seeded from
+/// `first` and then the sample farthest from the centers so far.
+fn representatives(samples: &[[f64; 3]], first: [u8; 3], k: usize) -> Vec<[u8; 3]> {
+    let mut centers = vec![first.map(f64::from)];
+    while centers.len() < k {
+        let farthest = samples.iter().max_by(|a, b| {
+            let nearest = |c: &[f64; 3]| {
+                centers
+                    .iter()
+                    .map(|center| dist2(*c, *center))
+                    .fold(f64::INFINITY, f64::min)
+            };
+            nearest(a).total_cmp(&nearest(b))
+        });
+        match farthest {
+            Some(&sample) if !centers.contains(&sample) => centers.push(sample),
+            _ => break,
+        }
+    }
+
+    for _ in 0..8 {
+        let mut sums = vec![([0.0; 3], 0usize); centers.len()];
+        for &sample in samples {
+            let nearest = (0..centers.len())
+                .min_by(|&a, &b| dist2(sample, centers[a]).total_cmp(&dist2(sample, centers[b])))
+                .unwrap_or_default();
+            let (sum, count) = &mut sums[nearest];
+            for c in 0..3 {
+                sum[c] += sample[c];
+            }
+            *count += 1;
+        }
+        for (center, (sum, count)) in centers.iter_mut().zip(&sums) {
+            if *count > 0 {
+                #[allow(clippy::cast_precision_loss)]
+                let count = *count as f64;
+                *center = sum.map(|s| s / count);
+            }
+        }
+    }
+    centers
+        .into_iter()
+        .map(|center| center.map(crate::color::to_u8))
+        .collect()
+}
+
+/// Replaces every point with up to `k` points at the same position, one per
+/// representative color of the pixels around it. Pixels then pick the
+/// closest color of their site, each becoming a cell of its own.
+pub fn expand(img: &image::RgbImage, points: &[Point], k: usize) -> Vec<Point> {
+    let (width, height) = img.dimensions();
+    // About half the spacing between points, so neighborhoods cover the cell
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let radius =
+        ((f64::from(width) * f64::from(height) / points.len().max(1) as f64).sqrt() / 2.0) as u32;
+    let radius = radius.max(1);
+    points
+        .iter()
+        .flat_map(|&(x, y, color)| {
+            let mut samples = Vec::new();
+            for sy in y.saturating_sub(radius)..=(y + radius).min(height - 1) {
+                for sx in x.saturating_sub(radius)..=(x + radius).min(width - 1) {
+                    samples.push(img.get_pixel(sx, sy).0.map(f64::from));
+                }
+            }
+            representatives(&samples, color, k)
+                .into_iter()
+                .map(move |rep| (x, y, rep))
+        })
+        .collect()
+}
@@ -0,0 +1,427 @@
+//! A pipeline for inputs with any number of channels, such as RGB plus
+//! near-infrared TIFFs from remote sensing: cells are grown over every
+//! channel, each weighted by `--channel-weights`, and drawn in RGB through
+//! `--channel-map`.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use image::GrayImage;
+use image::imageops::fast_blur;
+use rand::prelude::*;
+use tiff::decoder::{Decoder, DecodingResult};
+
+use crate::colorize::CellColor;
+use crate::{
+    Args, balanced_mix, channel_pipeline_options, color, exit_on_err, kernel, labels, metadata,
+    metric, output, reject_options, seeded_rng, timings, weight_at,
+};
+
+/// Most channels an input may have.
+const MAX_CHANNELS: usize = 8;
+
+/// Which input channels are drawn as red, green and blue.
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelMap(pub [usize; 3]);
+
+impl FromStr for ChannelMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid channel map '{s}' (expected R,G,B channel indices)");
+        let indices: Vec<usize> = s
+            .split(',')
+            .map(|index| index.trim().parse().map_err(|_| err()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self(indices.try_into().map_err(|_| err())?))
+    }
+}
+
+/// An input image as interleaved 8-bit channels.
+struct Bands {
+    width: u32,
+    height: u32,
+    channels: usize,
+    data: Vec<u8>,
+}
+
+/// Reads every channel of a TIFF, narrowing 16-bit samples to 8 bits.
+fn load_tiff(path: &Path, max_pixels: u64) -> Result<Bands, String> {
+    let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    let mut decoder = Decoder::new(std::io::BufReader::new(file)).map_err(|err| err.to_string())?;
+    let (width, height) = decoder.dimensions().map_err(|err| err.to_string())?;
+    crate::check_pixels(width, height, max_pixels)?;
+    let data = match decoder.read_image().map_err(|err| err.to_string())? {
+        DecodingResult::U8(data) => data,
+        DecodingResult::U16(data) => data.iter().map(|&sample| (sample >> 8) as u8).collect(),
+        _ => return Err("only 8- and 16-bit integer samples are supported".to_string()),
+    };
+    let channels = data.len() / (width as usize * height as usize).max(1);
+    Ok(Bands {
+        width,
+        height,
+        channels,
+        data,
+    })
+}
+
+/// Exits if `args` asks for anything the multispectral pipeline does not
+/// do. Only the color distance is measured across channels.
+fn reject_unsupported_options(args: &Args) {
+    let mut options = channel_pipeline_options(args);
+    options.push(("--distance", args.distance.name() != "color"));
+    reject_options("the multispectral pipeline", &options);
+}
+
+fn load(args: &Args) -> Bands {
+    let is_tiff = args
+        .input
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tif") || ext.eq_ignore_ascii_case("tiff"));
+    if is_tiff {
+        return exit_on_err(load_tiff(&args.input, args.max_pixels), "open image");
+    }
+    let img = exit_on_err(
+        crate::open_image(&args.input, args.max_pixels),
+        "open image",
+    );
+    let (width, height) = (img.width(), img.height());
+    let (channels, data) = match (img.color().has_color(), img.color().has_alpha()) {
+        (_, true) => (4, img.into_rgba8().into_raw()),
+        (true, false) => (3, img.into_rgb8().into_raw()),
+        (false, false) => (1, img.into_luma8().into_raw()),
+    };
+    Bands {
+        width,
+        height,
+        channels,
+        data,
+    }
+}
+
+/// `pixels` with each channel blurred by `sigma` on its own.
+fn blur<const N: usize>(pixels: &[[u8; N]], width: u32, height: u32, sigma: f32) -> Vec<[u8; N]> {
+    let mut blurred = pixels.to_vec();
+    for channel in 0..N {
+        let plane = GrayImage::from_fn(width, height, |x, y| {
+            image::Luma([pixels[(y * width + x) as usize][channel]])
+        });
+        for (pixel, level) in blurred.iter_mut().zip(fast_blur(&plane, sigma).pixels()) {
+            pixel[channel] = level.0[0];
+        }
+    }
+    blurred
+}
+
+/// The weight of each channel from `--channel-weights`, all 1 by default.
+fn channel_weights<const N: usize>(args: &Args) -> [f64; N] {
+    if args.channel_weights.is_empty() {
+        return [1.0; N];
+    }
+    args.channel_weights
+        .clone()
+        .try_into()
+        .unwrap_or_else(|weights: Vec<f64>| {
+            eprintln!(
+                "--channel-weights has {} weights but the input has {N} channels",
+                weights.len()
+            );
+            std::process::exit(1);
+        })
+}
+
+/// Which channels to draw, checked against the `N` there are. Inputs of
+/// three or more channels show their first three by default, others their
+/// first as gray.
+fn channel_map<const N: usize>(args: &Args) -> [usize; 3] {
+    let map = args
+        .channel_map
+        .map_or(if N >= 3 { [0, 1, 2] } else { [0; 3] }, |map| map.0);
+    if let Some(&index) = map.iter().find(|&&index| index >= N) {
+        eprintln!("--channel-map picks channel {index} but the input has {N} channels");
+        std::process::exit(1);
+    }
+    map
+}
+
+type Point<const N: usize> = (u32, u32, [u8; N]);
+
+fn sample<const N: usize>(
+    pixels: &[[u8; N]],
+    (width, height): (u32, u32),
+    args: &Args,
+) -> Vec<Point<N>> {
+    let draw = crate::position_sampler(args, width, height);
+    let mut rng = seeded_rng(args);
+    (0..args.points)
+        .map(|_| {
+            let (x, y) = draw(&mut rng);
+            (x, y, pixels[(y * width + x) as usize])
+        })
+        .collect()
+}
+
+/// The label map of `points`, scored like the RGB pipeline's color distance
+/// but over every channel. `--weight-ramp` follows the luminance of the
+/// channels `map` draws.
+fn assign_labels<const N: usize>(
+    blurred: &[[u8; N]],
+    (width, height): (u32, u32),
+    points: &[Point<N>],
+    (channel_weights, map): (&[f64; N], [usize; 3]),
+    args: &Args,
+    metric: &metric::Metric,
+) -> Vec<u32> {
+    let max_color_dist = 255.0 * channel_weights.iter().sum::<f64>();
+    let max_pos_dist = metric.max_pos_dist();
+    let gammas = (args.position_gamma, args.color_gamma);
+    let label_of = |x: u32, y: u32| {
+        let pixel = &blurred[(y * width + x) as usize];
+        let luminance = || color::luminance(map.map(|channel| pixel[channel]));
+        let weight = weight_at(args, (x, y), (width, height), luminance);
+        kernel::nearest(points, |&(px, py, pcolor)| {
+            let pos_dist = metric.pos_dist((x, y), (px, py));
+            let color_dist = kernel::weighted_color_dist(pixel, &pcolor, channel_weights);
+            match args.balance {
+                Some(balance) => balanced_mix(
+                    pos_dist,
+                    color_dist,
+                    balance,
+                    gammas,
+                    max_color_dist,
+                    max_pos_dist,
+                ),
+                None => {
+                    kernel::mixed_score(pos_dist, color_dist, weight, max_color_dist, max_pos_dist)
+                }
+            }
+        })
+        .label
+    };
+    let progress = |y: u32| eprint!("\rCalculating voronoi diagram... {y} / {height} rows");
+    let labels = labels::by_rows(width, height, label_of, progress);
+    eprintln!("\rCalculating voronoi diagram... {height} / {height} rows");
+    labels
+}
+
+/// Each cell's seed color with `--cell-color seed`, otherwise its average
+/// over every channel.
+fn cell_colors<const N: usize>(
+    pixels: &[[u8; N]],
+    labels: &[u32],
+    points: &[Point<N>],
+    args: &Args,
+) -> Vec<[u8; N]> {
+    if args.cell_color == CellColor::Seed {
+        return points.iter().map(|&(_, _, color)| color).collect();
+    }
+    let mut sums = vec![([0u64; N], 0u64); points.len()];
+    for (pixel, &label) in pixels.iter().zip(labels) {
+        let (sum, count) = &mut sums[label as usize];
+        for (sum, &level) in sum.iter_mut().zip(pixel) {
+            *sum += u64::from(level);
+        }
+        *count += 1;
+    }
+    sums.iter()
+        .zip(points)
+        .map(|(&(sum, count), &(_, _, seed))| {
+            let mut color = seed;
+            for (level, sum) in color.iter_mut().zip(sum) {
+                if let Some(average) = (sum + count / 2).checked_div(count) {
+                    *level = u8::try_from(average).unwrap_or(u8::MAX);
+                }
+            }
+            color
+        })
+        .collect()
+}
+
+fn run<const N: usize>(bands: &Bands, args: &Args, mut timings: timings::Timings) {
+    let (width, height) = (bands.width, bands.height);
+    let pixels: Vec<[u8; N]> = bands
+        .data
+        .chunks_exact(N)
+        .map(|chunk| chunk.try_into().expect("chunks hold N channels"))
+        .collect();
+    let channel_weights = channel_weights::<N>(args);
+    let map = channel_map::<N>(args);
+    let metric = metric::Metric::new(args.projection, width, height);
+
+    let points = timings.time("sampling", || sample(&pixels, (width, height), args));
+    let labels = timings.time("assignment", || {
+        let blurred = blur(&pixels, width, height, args.blur);
+        assign_labels(
+            &blurred,
+            (width, height),
+            &points,
+            (&channel_weights, map),
+            args,
+            &metric,
+        )
+    });
+    let colors = cell_colors(&pixels, &labels, &points, args);
+
+    let encode = std::time::Instant::now();
+    let mut diagram = image::RgbImage::from_fn(width, height, |x, y| {
+        let label = labels[(y * width + x) as usize] as usize;
+        let (px, py, _) = points[label];
+        let on_point = args.point_radius.is_some_and(|radius| {
+            let (dx, dy) = (x.abs_diff(px), y.abs_diff(py));
+            dx * dx + dy * dy < radius * (radius - 1)
+        });
+        let color = map.map(|channel| colors[label][channel]);
+        image::Rgb(if on_point {
+            color.map(|c| u8::MAX - c)
+        } else {
+            color
+        })
+    });
+    if let Some(effects) = &args.post {
+        effects.apply(&mut diagram, args);
+    }
+    crate::annotate::apply(&mut diagram, args);
+    let save_result = output::save(&args.output, args.force, |path| {
+        if crate::is_png(path) {
+            metadata::save_png(path, &diagram, metadata::text_chunks(args), None)
+        } else {
+            diagram.save(path)
+        }
+    });
+    exit_on_err(save_result, "save image");
+    eprintln!("Saved voronoi diagram to {}", args.output.display());
+    timings.add("encode", encode.elapsed());
+    if args.timings {
+        timings.print();
+    }
+}
+
+/// Renders an input of 1 to `MAX_CHANNELS` channels to an RGB diagram.
+pub fn render(args: &Args) {
+    reject_unsupported_options(args);
+    crate::check_outputs(args);
+    let mut args = args.clone();
+    args.seed.get_or_insert_with(|| rand::rng().random());
+    let mut timings = timings::Timings::default();
+    let bands = timings.time("load", || load(&args));
+    let mut args = crate::with_density(&args, (bands.width, bands.height));
+    crate::fit_points(&mut args, (bands.width, bands.height));
+    println!("Image dimensions: {}x{}", bands.width, bands.height);
+    println!("Channels: {}", bands.channels);
+    println!("Points: {}", args.points);
+    match bands.channels {
+        1 => run::<1>(&bands, &args, timings),
+        2 => run::<2>(&bands, &args, timings),
+        3 => run::<3>(&bands, &args, timings),
+        4 => run::<4>(&bands, &args, timings),
+        5 => run::<5>(&bands, &args, timings),
+        6 => run::<6>(&bands, &args, timings),
+        7 => run::<7>(&bands, &args, timings),
+        8 => run::<8>(&bands, &args, timings),
+        channels => {
+            eprintln!("The input has {channels} channels; at most {MAX_CHANNELS} are supported");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImagePlane, max_dists, score_fn_for, testing};
+
+    fn points<const N: usize>(pixels: &[[u8; N]], width: u32) -> Vec<Point<N>> {
+        (0..12)
+            .map(|i| {
+                let (x, y) = (2 + i * 17 % 50, 1 + i * 23 % 36);
+                (x, y, pixels[(y * width + x) as usize])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rgb_labels_match_the_rgb_pipeline() {
+        let img = testing::image(52, 38);
+        let pixels: Vec<[u8; 3]> = img.pixels().map(|pixel| pixel.0).collect();
+        let points = points(&pixels, 52);
+        for options in [
+            &[][..],
+            &["--weight", "40"],
+            &["--balance", "0.5"],
+            &["--weight-ramp", "luminance:1..40"],
+        ] {
+            let args = testing::args(options);
+            let metric = metric::Metric::new(args.projection, 52, 38);
+            let (max_color_dist, max_pos_dist) = max_dists(&img, &args);
+            let plane = ImagePlane::new(&img, args.blur);
+            let expected = crate::assign_labels(
+                &plane,
+                &points,
+                max_color_dist,
+                max_pos_dist,
+                &score_fn_for(&plane, &args),
+                &args,
+            );
+            let blurred = blur(&pixels, 52, 38, args.blur);
+            let labels = assign_labels(
+                &blurred,
+                (52, 38),
+                &points,
+                (&[1.0; 3], [0, 1, 2]),
+                &args,
+                &metric,
+            );
+            assert_eq!(labels, expected, "{options:?}");
+        }
+    }
+
+    #[test]
+    fn unweighted_channels_do_not_move_cells() {
+        let img = testing::image(52, 38);
+        let pixels: Vec<[u8; 4]> = img
+            .pixels()
+            .map(|&image::Rgb([r, g, b])| [r, g, b, 0])
+            .collect();
+        let noisy: Vec<[u8; 4]> = Iterator::zip(pixels.iter(), (0..=u8::MAX).cycle())
+            .map(|(&[r, g, b, _], noise)| [r, g, b, noise.wrapping_mul(97)])
+            .collect();
+        let args = testing::args(&["--weight", "60"]);
+        let metric = metric::Metric::new(args.projection, 52, 38);
+        let labels_of = |pixels: &[[u8; 4]], weights: [f64; 4]| {
+            let points = points(pixels, 52);
+            assign_labels(
+                pixels,
+                (52, 38),
+                &points,
+                (&weights, [0, 1, 2]),
+                &args,
+                &metric,
+            )
+        };
+        let quiet = labels_of(&pixels, [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(labels_of(&noisy, [1.0, 1.0, 1.0, 0.0]), quiet);
+        assert_ne!(labels_of(&noisy, [1.0; 4]), quiet);
+    }
+
+    #[test]
+    fn cells_average_every_channel() {
+        let pixels: Vec<[u8; 4]> = (0..40_u8).map(|i| [i, 2 * i, 255 - i, 7]).collect();
+        let labels: Vec<u32> = (0..40).map(|i| u32::from(i >= 10)).collect();
+        let points = [(0, 0, [1, 2, 3, 4]), (5, 5, [5, 6, 7, 8])];
+        let averages = cell_colors(
+            &pixels,
+            &labels,
+            &points,
+            &testing::args(&["--cell-color", "average"]),
+        );
+        // Halves round up, as in the RGB pipeline
+        assert_eq!(averages, [[5, 9, 251, 7], [25, 49, 231, 7]]);
+        let seeds = cell_colors(
+            &pixels,
+            &labels,
+            &points,
+            &testing::args(&["--cell-color", "seed"]),
+        );
+        assert_eq!(seeds, [[1, 2, 3, 4], [5, 6, 7, 8]]);
+    }
+}
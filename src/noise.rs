@@ -0,0 +1,137 @@
+use rand::prelude::*;
+use std::f64::consts::FRAC_1_SQRT_2;
+use std::str::FromStr;
+
+/// Skew and unskew factors between the simplex and square grids.
+const F2: f64 = 0.366_025_403_784_438_6; // (sqrt(3) - 1) / 2
+const G2: f64 = 0.211_324_865_405_187_1; // (3 - sqrt(3)) / 6
+
+const D: f64 = FRAC_1_SQRT_2;
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (D, D),
+    (-D, D),
+    (D, -D),
+    (-D, -D),
+];
+
+/// 2D simplex noise over a seeded permutation table.
+pub struct Simplex {
+    perm: [u8; 512],
+}
+
+impl Simplex {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        let mut table: [u8; 256] = std::array::from_fn(|i| u8::try_from(i).unwrap());
+        table.shuffle(rng);
+        Self {
+            perm: std::array::from_fn(|i| table[i % 256]),
+        }
+    }
+
+    fn gradient(&self, i: i64, j: i64) -> (f64, f64) {
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let (i, j) = ((i & 255) as usize, (j & 255) as usize);
+        GRADIENTS[usize::from(self.perm[i + usize::from(self.perm[j])]) % GRADIENTS.len()]
+    }
+
+    /// Noise value at `(x, y)`, roughly in `[-1, 1]`.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn at(&self, x: f64, y: f64) -> f64 {
+        let skew = (x + y) * F2;
+        let (cell_x, cell_y) = ((x + skew).floor(), (y + skew).floor());
+        let unskew = (cell_x + cell_y) * G2;
+        let (x0, y0) = (x - (cell_x - unskew), y - (cell_y - unskew));
+        // Which of the two triangles of the skewed cell we're in
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let corners = [
+            (0, 0, x0, y0),
+            (i1, j1, x0 - f64::from(i1) + G2, y0 - f64::from(j1) + G2),
+            (1, 1, x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2),
+        ];
+
+        let (cell_x, cell_y) = (cell_x as i64, cell_y as i64);
+        let sum: f64 = corners
+            .iter()
+            .map(|&(di, dj, dx, dy)| {
+                let falloff = 0.5 - dx * dx - dy * dy;
+                if falloff <= 0.0 {
+                    return 0.0;
+                }
+                let (gx, gy) = self.gradient(cell_x + i64::from(di), cell_y + i64::from(dj));
+                falloff.powi(4) * (gx * dx + gy * dy)
+            })
+            .sum();
+        70.0 * sum
+    }
+}
+
+/// White noise in `0..1` for pixel `(x, y)`, the same on every call with the
+/// same `seed` (the splitmix64 finalizer over the packed coordinates).
+#[must_use]
+pub fn pixel_hash(x: u32, y: u32, seed: u64) -> f64 {
+    let mut z = (u64::from(x) << 32 | u64::from(y)) ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    // The top 53 bits fill a double's mantissa exactly
+    #[allow(clippy::cast_precision_loss)]
+    let unit = (z >> 11) as f64 / (1_u64 << 53) as f64;
+    unit
+}
+
+/// Displacement of pixel coordinates by smooth noise, for wobbly edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Warp {
+    /// Largest displacement in pixels
+    amplitude: f64,
+    /// Noise features across the width of the image
+    frequency: f64,
+}
+
+impl FromStr for Warp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amplitude, frequency) = s
+            .split_once(',')
+            .ok_or_else(|| format!("expected \"amplitude,frequency\", got \"{s}\""))?;
+        let amplitude = amplitude
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid amplitude \"{amplitude}\": {err}"))?;
+        let frequency: f64 = frequency
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid frequency \"{frequency}\": {err}"))?;
+        if frequency <= 0.0 {
+            return Err(format!("frequency must be positive, got {frequency}"));
+        }
+        Ok(Self {
+            amplitude,
+            frequency,
+        })
+    }
+}
+
+impl Warp {
+    /// Where `(x, y)` lands in a `width`x`height` image, kept inside it.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn apply(&self, noise: &Simplex, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        let scale = self.frequency / f64::from(width);
+        let (u, v) = (f64::from(x) * scale, f64::from(y) * scale);
+        // Offset the second lookup far away so the axes move independently
+        let dx = noise.at(u, v) * self.amplitude;
+        let dy = noise.at(u + 173.1, v - 91.7) * self.amplitude;
+        (
+            (f64::from(x) + dx).round().clamp(0.0, f64::from(width - 1)) as u32,
+            (f64::from(y) + dy)
+                .round()
+                .clamp(0.0, f64::from(height - 1)) as u32,
+        )
+    }
+}
@@ -0,0 +1,59 @@
+//! Catching sites whose color misrepresents their cell, such as a site that
+//! landed on a speck of dust.
+
+use crate::color;
+
+type Point = (u32, u32, [u8; 3]);
+
+/// Per-channel median color of each cell's pixels, or `None` for empty cells.
+fn cell_medians(img: &image::RgbImage, labels: &[u32], cells: usize) -> Vec<Option<[u8; 3]>> {
+    let mut histograms = vec![[[0u32; 256]; 3]; cells];
+    for (pixel, &label) in img.pixels().zip(labels) {
+        for (histogram, value) in histograms[label as usize].iter_mut().zip(pixel.0) {
+            histogram[usize::from(value)] += 1;
+        }
+    }
+    histograms
+        .iter()
+        .map(|channels| {
+            let count: u32 = channels[0].iter().sum();
+            (count > 0).then(|| {
+                channels.map(|histogram| {
+                    let mut seen = 0;
+                    let median = histogram.iter().position(|&n| {
+                        seen += n;
+                        seen * 2 >= count
+                    });
+                    median
+                        .and_then(|value| u8::try_from(value).ok())
+                        .unwrap_or(0)
+                })
+            })
+        })
+        .collect()
+}
+
+/// Replaces the color of every point further than `threshold` from the
+/// median color of its cell in `labels` with that median. Returns the
+/// corrected points and how many were replaced.
+#[must_use]
+pub fn reject(
+    img: &image::RgbImage,
+    points: &[Point],
+    labels: &[u32],
+    threshold: f64,
+) -> (Vec<Point>, usize) {
+    let mut replaced = 0;
+    let points = points
+        .iter()
+        .zip(cell_medians(img, labels, points.len()))
+        .map(|(&(x, y, site), median)| match median {
+            Some(median) if color::distance(site, median) > threshold => {
+                replaced += 1;
+                (x, y, median)
+            }
+            _ => (x, y, site),
+        })
+        .collect();
+    (points, replaced)
+}
@@ -0,0 +1,43 @@
+//! Writing output files without clobbering existing ones by accident or
+//! leaving half-written ones behind.
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Fails if `path` already exists, unless `force` allows overwriting it.
+pub fn check_clobber(path: &Path, force: bool) -> Result<(), String> {
+    if !force && path.exists() {
+        return Err(format!(
+            "{} already exists, pass --force to overwrite it",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Temporary path in the same directory as `path`, keeping its extension so
+/// encoders that pick a format from it still work.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{name}", std::process::id()))
+}
+
+/// Saves `path` atomically: `write` fills a temporary file that is renamed
+/// over `path` only once complete, so an interrupted save leaves no partial
+/// output behind.
+pub fn save<E: Display>(
+    path: &Path,
+    force: bool,
+    write: impl FnOnce(&Path) -> Result<(), E>,
+) -> Result<(), String> {
+    check_clobber(path, force)?;
+    let temp = temp_path(path);
+    let result = write(&temp)
+        .map_err(|err| err.to_string())
+        .and_then(|()| std::fs::rename(&temp, path).map_err(|err| err.to_string()));
+    if result.is_err() {
+        // Best effort: the temporary file may never have been created
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
@@ -0,0 +1,69 @@
+//! Option overrides read from small TOML files, for renders that repeat
+//! with some options changed, such as mask regions and passes.
+
+use clap::Parser;
+
+use crate::{Args, Cli};
+
+/// Options for one table: the long option names and their values.
+pub type Overrides = Vec<(String, String)>;
+
+/// Reads the tables of a TOML file, each holding options as `key = value`
+/// pairs, in file order. Only the flat subset of TOML that such tables need
+/// is understood: strings, numbers and booleans.
+pub fn parse_tables(text: &str) -> Result<Vec<(String, Overrides)>, String> {
+    let mut tables: Vec<(String, Overrides)> = Vec::new();
+    for (number, line) in (1..).zip(text.lines()) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            tables.push((name.trim().trim_matches('"').to_string(), Vec::new()));
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {number}: expected key = value"))?;
+        let value = value.trim();
+        let value = if let Some(string) = value.strip_prefix('"') {
+            string
+                .split_once('"')
+                .ok_or_else(|| format!("line {number}: unterminated string"))?
+                .0
+        } else {
+            value.split('#').next().unwrap_or_default().trim()
+        };
+        let (_, overrides) = tables
+            .last_mut()
+            .ok_or_else(|| format!("line {number}: option outside a table"))?;
+        overrides.push((key.trim().replace('_', "-"), value.to_string()));
+    }
+    Ok(tables)
+}
+
+/// `args` with `overrides` applied, parsed as if given after the command line
+/// that produced `args`. The seed carries over unless overridden.
+pub fn with_overrides(args: &Args, overrides: &Overrides) -> Result<Args, String> {
+    let mut command = if args.command_line.is_empty() {
+        std::env::args().collect()
+    } else {
+        args.command_line.clone()
+    };
+    for (key, value) in overrides {
+        match value.as_str() {
+            "true" => command.push(format!("--{key}")),
+            "false" => {}
+            _ => command.extend([format!("--{key}"), value.clone()]),
+        }
+    }
+    let cli = Cli::try_parse_from(&command).map_err(|err| err.to_string())?;
+    let mut overridden = cli
+        .args
+        .ok_or("overrides need a plain render, not a subcommand")?;
+    overridden.seed = overridden.seed.or(args.seed);
+    Ok(overridden)
+}
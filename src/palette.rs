@@ -0,0 +1,135 @@
+//! Quantization of rendered images to a fixed palette.
+
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::color::parse_hex;
+
+/// A fixed set of colors the output is limited to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette(pub Vec<[u8; 3]>);
+
+const BUILTIN: &[(&str, &[[u8; 3]])] = &[
+    ("bw", &[[0, 0, 0], [255, 255, 255]]),
+    (
+        "gameboy",
+        &[[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]],
+    ),
+    (
+        "pico8",
+        &[
+            [0x00, 0x00, 0x00],
+            [0x1d, 0x2b, 0x53],
+            [0x7e, 0x25, 0x53],
+            [0x00, 0x87, 0x51],
+            [0xab, 0x52, 0x36],
+            [0x5f, 0x57, 0x4f],
+            [0xc2, 0xc3, 0xc7],
+            [0xff, 0xf1, 0xe8],
+            [0xff, 0x00, 0x4d],
+            [0xff, 0xa3, 0x00],
+            [0xff, 0xec, 0x27],
+            [0x00, 0xe4, 0x36],
+            [0x29, 0xad, 0xff],
+            [0x83, 0x76, 0x9c],
+            [0xff, 0x77, 0xa8],
+            [0xff, 0xcc, 0xaa],
+        ],
+    ),
+];
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, colors)) = BUILTIN.iter().find(|(name, _)| *name == s) {
+            return Ok(Self(colors.to_vec()));
+        }
+        let colors = s
+            .split(',')
+            .map(|color| parse_hex(color.trim()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("{err}; palettes are bw, gameboy, pico8 or a list of colors"))?;
+        Ok(Self(colors))
+    }
+}
+
+impl Palette {
+    /// The palette entry closest to `color`.
+    #[must_use]
+    pub fn nearest(&self, color: [f64; 3]) -> [u8; 3] {
+        let dist = |entry: &[u8; 3]| -> f64 {
+            (0..3)
+                .map(|c| (f64::from(entry[c]) - color[c]).powi(2))
+                .sum()
+        };
+        self.0
+            .iter()
+            .copied()
+            .min_by(|a, b| dist(a).total_cmp(&dist(b)))
+            .unwrap_or([0; 3])
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Floyd–Steinberg error diffusion
+    Fs,
+    /// Ordered dithering with a 4×4 Bayer matrix
+    Bayer,
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Quantizes `img` to `palette` in place. With dithering, error is only
+/// diffused between pixels of the same cell, so each cell dithers towards
+/// its own color.
+pub fn quantize(
+    img: &mut image::RgbImage,
+    labels: &[u32],
+    palette: &Palette,
+    dither: Option<Dither>,
+) {
+    let width = img.width() as usize;
+    match dither {
+        None => {
+            for pixel in img.pixels_mut() {
+                pixel.0 = palette.nearest(pixel.0.map(f64::from));
+            }
+        }
+        Some(Dither::Bayer) => {
+            // Spread thresholds over roughly one palette step
+            #[allow(clippy::cast_precision_loss)]
+            let spread = 255.0 / (palette.0.len().max(2) - 1) as f64;
+            for (x, y, pixel) in img.enumerate_pixels_mut() {
+                let threshold = f64::from(BAYER_4X4[y as usize % 4][x as usize % 4]) / 16.0 - 0.5;
+                pixel.0 = palette.nearest(pixel.0.map(|c| f64::from(c) + threshold * spread));
+            }
+        }
+        Some(Dither::Fs) => {
+            let mut values: Vec<[f64; 3]> = img.pixels().map(|p| p.0.map(f64::from)).collect();
+            let height = values.len() / width.max(1);
+            for (idx, pixel) in img.pixels_mut().enumerate() {
+                let wanted = values[idx].map(|c| c.clamp(0.0, 255.0));
+                let chosen = palette.nearest(wanted);
+                pixel.0 = chosen;
+                let error: [f64; 3] = std::array::from_fn(|c| wanted[c] - f64::from(chosen[c]));
+                let (x, y) = (idx % width, idx / width);
+                let targets = [
+                    (x + 1 < width, idx + 1, 7.0),
+                    (y + 1 < height && x > 0, idx + width - 1, 3.0),
+                    (y + 1 < height, idx + width, 5.0),
+                    (y + 1 < height && x + 1 < width, idx + width + 1, 1.0),
+                ];
+                for (in_bounds, target, share) in targets {
+                    if in_bounds && labels[target] == labels[idx] {
+                        for c in 0..3 {
+                            values[target][c] += error[c] * share / 16.0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Rendering a diagram of a diagram, for `--passes`: each pass takes the
+//! previous pass's image as its input, optionally with its own options.
+
+use std::path::Path;
+
+use crate::overrides::{Overrides, parse_tables, with_overrides};
+use crate::{Args, Diagram, build_diagram_or_ensemble, exit_on_err, for_image, timings};
+
+type Point = (u32, u32, [u8; 3]);
+
+/// Reads the per-pass options at `path`: tables named after pass numbers,
+/// from 1 for the first pass.
+fn pass_params(path: &Path, passes: u32) -> Vec<(u32, Overrides)> {
+    let text = exit_on_err(std::fs::read_to_string(path), "read pass parameters");
+    let tables = exit_on_err(parse_tables(&text), "parse pass parameters");
+    tables
+        .into_iter()
+        .map(|(name, overrides)| match name.parse() {
+            Ok(pass) if (1..=passes).contains(&pass) => (pass, overrides),
+            _ => {
+                eprintln!(
+                    "Failed to parse pass parameters: table [{name}] is not a pass from 1 to {passes}"
+                );
+                std::process::exit(1);
+            }
+        })
+        .collect()
+}
+
+/// Renders `passes` diagrams in turn, the first of `img` and each next one
+/// of the image before it, each with `args` and the options `--pass-params`
+/// gives its pass. Returns the last diagram and its points.
+pub fn build(
+    img: &image::RgbImage,
+    args: &Args,
+    passes: u32,
+    timings: &mut timings::Timings,
+) -> (Diagram, Vec<Point>) {
+    let params = args
+        .pass_params
+        .as_ref()
+        .map(|path| pass_params(path, passes))
+        .unwrap_or_default();
+    let mut input = img.clone();
+    let mut last = None;
+    for pass in 1..=passes {
+        println!("Pass {pass} / {passes}:");
+        let pass_args = match params.iter().find(|(number, _)| *number == pass) {
+            Some((_, overrides)) => for_image(
+                &exit_on_err(with_overrides(args, overrides), "apply pass parameters"),
+                &input,
+            ),
+            None => args.clone(),
+        };
+        let (diagram, points) = build_diagram_or_ensemble(&input, &pass_args, timings);
+        if pass < passes {
+            input.clone_from(&diagram.image);
+        }
+        last = Some((diagram, points));
+    }
+    last.expect("there is at least one pass")
+}
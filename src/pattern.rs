@@ -0,0 +1,97 @@
+//! Engraving-style pattern fills clipped to individual cells.
+
+use std::f64::consts::FRAC_PI_2;
+use std::str::FromStr;
+
+use crate::cells::CellStats;
+use crate::color::{luminance, to_u8};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    Stripes,
+    Dots,
+    Crosshatch,
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stripes" => Ok(Self::Stripes),
+            "dots" => Ok(Self::Dots),
+            "crosshatch" => Ok(Self::Crosshatch),
+            _ => Err(format!(
+                "unknown pattern '{s}' (expected stripes, dots or crosshatch)"
+            )),
+        }
+    }
+}
+
+/// A pattern laid out for one cell.
+#[derive(Debug, Clone, Copy)]
+pub struct CellPattern {
+    pattern: Pattern,
+    origin: (f64, f64),
+    /// Unit vector along the cell's mean luminance gradient
+    direction: (f64, f64),
+    period: f64,
+    /// Fraction of the cell covered in ink, from its darkness
+    coverage: f64,
+    ink: [u8; 3],
+    paper: [u8; 3],
+}
+
+impl CellPattern {
+    /// Lays out `pattern` over a cell filled with `color`. Lines run across
+    /// the cell's luminance gradient, and darker cells get heavier ink.
+    #[must_use]
+    pub fn new(pattern: Pattern, stats: &CellStats, color: [u8; 3]) -> Option<Self> {
+        let gradient = stats.gradient()?;
+        let [r, g, b] = [0, 1, 2].map(|c| (gradient.dx[c], gradient.dy[c]));
+        let gx = 0.2126 * r.0 + 0.7152 * g.0 + 0.0722 * b.0;
+        let gy = 0.2126 * r.1 + 0.7152 * g.1 + 0.0722 * b.1;
+        let angle = if gx == 0.0 && gy == 0.0 {
+            FRAC_PI_2
+        } else {
+            gy.atan2(gx)
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let period = ((stats.count as f64).sqrt() / 4.0).clamp(4.0, 12.0);
+        Some(Self {
+            pattern,
+            origin: gradient.center,
+            direction: (angle.cos(), angle.sin()),
+            period,
+            coverage: 1.0 - luminance(color) / 255.0,
+            ink: color.map(|c| to_u8(f64::from(c) * 0.4)),
+            paper: color.map(|c| to_u8(f64::from(c) * 0.5 + 127.5)),
+        })
+    }
+
+    /// Color of the pattern at pixel `(x, y)`.
+    #[must_use]
+    pub fn at(&self, x: u32, y: u32) -> [u8; 3] {
+        let ox = f64::from(x) - self.origin.0;
+        let oy = f64::from(y) - self.origin.1;
+        let (cos, sin) = self.direction;
+        // Coordinates along and across the gradient, in pattern periods
+        let u = (ox * cos + oy * sin) / self.period;
+        let v = (oy * cos - ox * sin) / self.period;
+        let inked = match self.pattern {
+            Pattern::Stripes => u.rem_euclid(1.0) < self.coverage,
+            Pattern::Crosshatch => {
+                let diagonal_a = (u + v).rem_euclid(1.0);
+                let diagonal_b = (u - v).rem_euclid(1.0);
+                diagonal_a < self.coverage.min(0.5) || diagonal_b < (self.coverage - 0.5).max(0.0)
+            }
+            Pattern::Dots => {
+                let du = u - u.round();
+                let dv = v - v.round();
+                // Dot area matches coverage until neighbouring dots touch
+                (du * du + dv * dv) < self.coverage / std::f64::consts::PI
+            }
+        };
+        if inked { self.ink } else { self.paper }
+    }
+}
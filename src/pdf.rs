@@ -0,0 +1,126 @@
+//! Single-page PDF documents of a diagram, one point per pixel.
+
+use std::fmt::Write as _;
+
+use crate::export;
+
+/// A PDF file built one numbered object at a time.
+struct Document {
+    bytes: Vec<u8>,
+    /// Byte offset of each object, for the cross-reference table
+    offsets: Vec<usize>,
+}
+
+impl Document {
+    fn new() -> Self {
+        // The binary comment marks the file as binary for transfer tools
+        Self {
+            bytes: b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Appends the next object with `dictionary` and an optional stream.
+    fn object(&mut self, dictionary: &str, stream: Option<&[u8]>) {
+        self.offsets.push(self.bytes.len());
+        let number = self.offsets.len();
+        match stream {
+            Some(stream) => {
+                let length = format!("/Length {}", stream.len());
+                let dictionary = [dictionary, &length].join(" ");
+                self.bytes.extend(
+                    format!("{number} 0 obj\n<< {} >>\nstream\n", dictionary.trim()).bytes(),
+                );
+                self.bytes.extend(stream);
+                self.bytes.extend(b"\nendstream\nendobj\n");
+            }
+            None => self
+                .bytes
+                .extend(format!("{number} 0 obj\n<< {dictionary} >>\nendobj\n").bytes()),
+        }
+    }
+
+    /// The finished file, with object 1 as its catalog.
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.bytes.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            let _ = writeln!(table, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            self.offsets.len() + 1
+        );
+        self.bytes.extend(table.bytes());
+        self.bytes
+    }
+}
+
+/// A `width` by `height` page drawing `content`, with `image` available to
+/// it as `/Im0` if given.
+fn page(width: u32, height: u32, content: &[u8], image: Option<&image::RgbImage>) -> Vec<u8> {
+    let mut document = Document::new();
+    document.object("/Type /Catalog /Pages 2 0 R", None);
+    document.object("/Type /Pages /Kids [3 0 R] /Count 1", None);
+    let resources = if image.is_some() {
+        "/Resources << /XObject << /Im0 5 0 R >> >>"
+    } else {
+        "/Resources << >>"
+    };
+    document.object(
+        &format!(
+            "/Type /Page /Parent 2 0 R /MediaBox [0 0 {width} {height}] /Contents 4 0 R {resources}"
+        ),
+        None,
+    );
+    document.object("", Some(content));
+    if let Some(image) = image {
+        document.object(
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8",
+                image.width(),
+                image.height()
+            ),
+            Some(image.as_raw()),
+        );
+    }
+    document.finish()
+}
+
+/// A PDF with every cell as a filled vector path traced along its pixel
+/// boundary, so it prints sharply at any size.
+#[must_use]
+pub fn vector(labels: &[u32], colors: &[[u8; 3]], width: u32, height: u32) -> Vec<u8> {
+    let mut content = String::new();
+    for (rings, color) in export::cell_outlines(labels, width, height, colors.len())
+        .iter()
+        .zip(colors)
+    {
+        if rings.is_empty() {
+            continue;
+        }
+        let [r, g, b] = color.map(|c| f64::from(c) / 255.0);
+        let _ = writeln!(content, "{r:.4} {g:.4} {b:.4} rg");
+        for ring in rings {
+            // PDF's y axis points up
+            let flip = |y: f64| f64::from(height) - y;
+            let _ = writeln!(content, "{} {} m", ring[0].0, flip(ring[0].1));
+            for &(x, y) in &ring[1..] {
+                let _ = writeln!(content, "{x} {} l", flip(y));
+            }
+            content.push_str("h\n");
+        }
+        content.push_str("f*\n");
+    }
+    page(width, height, content.as_bytes(), None)
+}
+
+/// A PDF showing `image` as is, for styles and effects that cell outlines
+/// cannot express.
+#[must_use]
+pub fn raster(image: &image::RgbImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let content = format!("q {width} 0 0 {height} 0 0 cm /Im0 Do Q\n");
+    page(width, height, content.as_bytes(), Some(image))
+}
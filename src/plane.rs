@@ -0,0 +1,70 @@
+//! An input image together with the conversions of it that stages share,
+//! each computed the first time a stage asks for it.
+
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use image::imageops::fast_blur;
+
+use crate::edges;
+
+/// The image a diagram is grown from, with cached conversions: the blurred
+/// copy at the pipeline's `--blur`, and the luminance gradients and their
+/// magnitudes. New conversions that several stages need belong here rather
+/// than in each stage, so every one is paid for at most once per image.
+///
+/// Dereferences to the image itself.
+pub struct ImagePlane<'a> {
+    img: &'a image::RgbImage,
+    blur: f32,
+    blurred: OnceLock<image::RgbImage>,
+    gradients: OnceLock<Vec<(f64, f64)>>,
+    gradient_magnitudes: OnceLock<Vec<f64>>,
+}
+
+impl<'a> ImagePlane<'a> {
+    /// A plane over `img` whose blurred copy has standard deviation `blur`.
+    #[must_use]
+    pub fn new(img: &'a image::RgbImage, blur: f32) -> Self {
+        Self {
+            img,
+            blur,
+            blurred: OnceLock::new(),
+            gradients: OnceLock::new(),
+            gradient_magnitudes: OnceLock::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn image(&self) -> &'a image::RgbImage {
+        self.img
+    }
+
+    /// The image blurred by the plane's standard deviation.
+    pub fn blurred(&self) -> &image::RgbImage {
+        self.blurred.get_or_init(|| fast_blur(self.img, self.blur))
+    }
+
+    /// Sobel gradient `(gx, gy)` of the luminance at every pixel, row by row.
+    pub fn gradients(&self) -> &[(f64, f64)] {
+        self.gradients.get_or_init(|| edges::gradients(self.img))
+    }
+
+    /// Magnitude of the luminance gradient at every pixel, row by row.
+    pub fn gradient_magnitudes(&self) -> &[f64] {
+        self.gradient_magnitudes.get_or_init(|| {
+            self.gradients()
+                .iter()
+                .map(|(gx, gy)| gx.hypot(*gy))
+                .collect()
+        })
+    }
+}
+
+impl Deref for ImagePlane<'_> {
+    type Target = image::RgbImage;
+
+    fn deref(&self) -> &image::RgbImage {
+        self.img
+    }
+}
@@ -0,0 +1,318 @@
+//! Extension points for the stages of the pipeline. Implementations are
+//! registered in the tables below and picked by name on the command line.
+
+use std::fmt::Debug;
+
+use rand::Rng;
+
+use crate::cells::CellStats;
+use crate::metric::Metric;
+use crate::{Args, color, greedy, post, rng};
+
+type Point = (u32, u32, [u8; 3]);
+
+/// Picks the points cells grow from.
+pub trait PointSampler: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn sample(
+        &self,
+        img: &image::RgbImage,
+        args: &Args,
+        count: usize,
+        rng: &mut rng::Generator,
+        print_progress: bool,
+    ) -> Vec<Point>;
+}
+
+/// Scores how well a pixel matches a point; each pixel joins the point with
+/// the lowest score.
+pub trait DistanceMetric: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    #[allow(clippy::too_many_arguments)]
+    fn score(
+        &self,
+        pixel: &Point,
+        point: &Point,
+        img: &image::RgbImage,
+        metric: &Metric,
+        color_weight: f64,
+        max_color_dist: f64,
+        max_pos_dist: f64,
+    ) -> f64;
+}
+
+/// Picks the fill color of every cell.
+pub trait CellColorizer: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn colors(
+        &self,
+        img: &image::RgbImage,
+        labels: &[u32],
+        stats: &[CellStats],
+        points: &[Point],
+        args: &Args,
+    ) -> Vec<[u8; 3]>;
+}
+
+/// A finishing effect run over the rendered image by `--post`.
+pub trait PostEffect: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Applies the effect to `image` with strength `amount`, drawing any
+    /// randomness from `rng`.
+    fn apply(&self, image: &mut image::RgbImage, amount: f64, rng: &mut rng::Generator);
+}
+
+/// Points drawn favouring pixels by `weight`.
+#[derive(Debug)]
+struct Weighted;
+
+impl PointSampler for Weighted {
+    fn name(&self) -> &'static str {
+        "weighted"
+    }
+
+    fn sample(
+        &self,
+        img: &image::RgbImage,
+        args: &Args,
+        count: usize,
+        rng: &mut rng::Generator,
+        print_progress: bool,
+    ) -> Vec<Point> {
+        crate::sample_points_(img, args, count, rng, print_progress)
+    }
+}
+
+/// Points drawn uniformly over the image.
+#[derive(Debug)]
+struct Uniform;
+
+impl PointSampler for Uniform {
+    fn name(&self) -> &'static str {
+        "uniform"
+    }
+
+    fn sample(
+        &self,
+        img: &image::RgbImage,
+        _args: &Args,
+        count: usize,
+        rng: &mut rng::Generator,
+        _print_progress: bool,
+    ) -> Vec<Point> {
+        (0..count)
+            .map(|_| {
+                let x = rng.random_range(0..img.width());
+                let y = rng.random_range(0..img.height());
+                (x, y, img.get_pixel(x, y).0)
+            })
+            .collect()
+    }
+}
+
+/// Points placed one by one where the picture drawn from those before is
+/// most wrong.
+#[derive(Debug)]
+struct Greedy;
+
+impl PointSampler for Greedy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn sample(
+        &self,
+        img: &image::RgbImage,
+        args: &Args,
+        count: usize,
+        _rng: &mut rng::Generator,
+        print_progress: bool,
+    ) -> Vec<Point> {
+        greedy::sample(img, args.blur, count, print_progress)
+    }
+}
+
+/// Position and color distance, as `--weight` balances them.
+#[derive(Debug)]
+struct ColorDistance;
+
+impl DistanceMetric for ColorDistance {
+    fn name(&self) -> &'static str {
+        "color"
+    }
+
+    fn score(
+        &self,
+        pixel: &Point,
+        point: &Point,
+        img: &image::RgbImage,
+        metric: &Metric,
+        color_weight: f64,
+        max_color_dist: f64,
+        max_pos_dist: f64,
+    ) -> f64 {
+        crate::score(
+            pixel,
+            point,
+            img,
+            metric,
+            color_weight,
+            max_color_dist,
+            max_pos_dist,
+        )
+    }
+}
+
+/// Position alone, for classic Voronoi cells.
+#[derive(Debug)]
+struct Spatial;
+
+impl DistanceMetric for Spatial {
+    fn name(&self) -> &'static str {
+        "spatial"
+    }
+
+    fn score(
+        &self,
+        pixel: &Point,
+        point: &Point,
+        _img: &image::RgbImage,
+        metric: &Metric,
+        _color_weight: f64,
+        _max_color_dist: f64,
+        _max_pos_dist: f64,
+    ) -> f64 {
+        metric.pos_dist((pixel.0, pixel.1), (point.0, point.1))
+    }
+}
+
+/// Colors as chosen by `--cell-color` and the tone options.
+#[derive(Debug)]
+struct CellColorOption;
+
+impl CellColorizer for CellColorOption {
+    fn name(&self) -> &'static str {
+        "cell-color"
+    }
+
+    fn colors(
+        &self,
+        img: &image::RgbImage,
+        labels: &[u32],
+        stats: &[CellStats],
+        points: &[Point],
+        args: &Args,
+    ) -> Vec<[u8; 3]> {
+        crate::cell_colors(img, labels, stats, points, args)
+    }
+}
+
+/// The luminance of each cell's average color as a gray.
+#[derive(Debug)]
+struct Grayscale;
+
+impl CellColorizer for Grayscale {
+    fn name(&self) -> &'static str {
+        "grayscale"
+    }
+
+    fn colors(
+        &self,
+        _img: &image::RgbImage,
+        _labels: &[u32],
+        stats: &[CellStats],
+        points: &[Point],
+        _args: &Args,
+    ) -> Vec<[u8; 3]> {
+        stats
+            .iter()
+            .zip(points)
+            .map(|(stats, &(_, _, seed))| {
+                let gray = color::to_u8(color::luminance(stats.average().unwrap_or(seed)));
+                [gray; 3]
+            })
+            .collect()
+    }
+}
+
+/// Film grain with standard deviation `amount` of full scale.
+#[derive(Debug)]
+struct Grain;
+
+impl PostEffect for Grain {
+    fn name(&self) -> &'static str {
+        "grain"
+    }
+
+    fn apply(&self, image: &mut image::RgbImage, amount: f64, rng: &mut rng::Generator) {
+        post::grain(image, amount, rng);
+    }
+}
+
+/// Corners darkened by `amount` of their brightness.
+#[derive(Debug)]
+struct Vignette;
+
+impl PostEffect for Vignette {
+    fn name(&self) -> &'static str {
+        "vignette"
+    }
+
+    fn apply(&self, image: &mut image::RgbImage, amount: f64, _rng: &mut rng::Generator) {
+        post::vignette(image, amount);
+    }
+}
+
+/// Unsharp masking of strength `amount`.
+#[derive(Debug)]
+struct Sharpen;
+
+impl PostEffect for Sharpen {
+    fn name(&self) -> &'static str {
+        "sharpen"
+    }
+
+    fn apply(&self, image: &mut image::RgbImage, amount: f64, _rng: &mut rng::Generator) {
+        post::sharpen(image, amount);
+    }
+}
+
+pub const SAMPLERS: &[&dyn PointSampler] = &[&Weighted, &Uniform, &Greedy];
+pub const DISTANCE_METRICS: &[&dyn DistanceMetric] = &[&ColorDistance, &Spatial];
+pub const CELL_COLORIZERS: &[&dyn CellColorizer] = &[&CellColorOption, &Grayscale];
+pub const POST_EFFECTS: &[&dyn PostEffect] = &[&Grain, &Vignette, &Sharpen];
+
+fn lookup<T: ?Sized>(
+    table: &[&'static T],
+    name: &str,
+    name_of: impl Fn(&T) -> &'static str,
+) -> Result<&'static T, String> {
+    table
+        .iter()
+        .copied()
+        .find(|entry| name_of(entry) == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = table.iter().map(|entry| name_of(entry)).collect();
+            format!("unknown \"{name}\", expected one of: {}", names.join(", "))
+        })
+}
+
+pub fn parse_sampler(name: &str) -> Result<&'static dyn PointSampler, String> {
+    lookup(SAMPLERS, name, PointSampler::name)
+}
+
+pub fn parse_distance_metric(name: &str) -> Result<&'static dyn DistanceMetric, String> {
+    lookup(DISTANCE_METRICS, name, DistanceMetric::name)
+}
+
+pub fn parse_cell_colorizer(name: &str) -> Result<&'static dyn CellColorizer, String> {
+    lookup(CELL_COLORIZERS, name, CellColorizer::name)
+}
+
+pub fn parse_post_effect(name: &str) -> Result<&'static dyn PostEffect, String> {
+    lookup(POST_EFFECTS, name, PostEffect::name)
+}
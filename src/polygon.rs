@@ -0,0 +1,166 @@
+//! Vector boundaries extracted from label maps.
+
+use std::collections::HashMap;
+
+/// A corner of the pixel grid; pixel `(x, y)` spans `(x, y)` to `(x + 1, y + 1)`.
+pub type Vertex = (i64, i64);
+
+/// Pixel-grid edges separating each pair of touching cells, keyed by the
+/// pair's labels in ascending order.
+#[must_use]
+pub fn shared_edges(
+    labels: &[u32],
+    width: u32,
+    height: u32,
+) -> HashMap<(u32, u32), Vec<[Vertex; 2]>> {
+    let mut edges: HashMap<(u32, u32), Vec<[Vertex; 2]>> = HashMap::new();
+    let mut add = |a: u32, b: u32, edge: [Vertex; 2]| {
+        if a != b {
+            edges.entry((a.min(b), a.max(b))).or_default().push(edge);
+        }
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) as usize;
+            let (vx, vy) = (i64::from(x), i64::from(y));
+            if x + 1 < width {
+                add(
+                    labels[idx],
+                    labels[idx + 1],
+                    [(vx + 1, vy), (vx + 1, vy + 1)],
+                );
+            }
+            if y + 1 < height {
+                add(
+                    labels[idx],
+                    labels[idx + width as usize],
+                    [(vx, vy + 1), (vx + 1, vy + 1)],
+                );
+            }
+        }
+    }
+    edges
+}
+
+/// Pixel-grid edges around each cell, including those along the image
+/// border, indexed by label.
+#[must_use]
+pub fn cell_edges(labels: &[u32], width: u32, height: u32, cells: usize) -> Vec<Vec<[Vertex; 2]>> {
+    let mut edges = vec![Vec::new(); cells];
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) as usize;
+            let label = labels[idx];
+            let (vx, vy) = (i64::from(x), i64::from(y));
+            let sides = [
+                (x == 0 || labels[idx - 1] != label, [(vx, vy), (vx, vy + 1)]),
+                (
+                    x + 1 == width || labels[idx + 1] != label,
+                    [(vx + 1, vy), (vx + 1, vy + 1)],
+                ),
+                (
+                    y == 0 || labels[idx - width as usize] != label,
+                    [(vx, vy), (vx + 1, vy)],
+                ),
+                (
+                    y + 1 == height || labels[idx + width as usize] != label,
+                    [(vx, vy + 1), (vx + 1, vy + 1)],
+                ),
+            ];
+            for (is_boundary, edge) in sides {
+                if is_boundary {
+                    edges[label as usize].push(edge);
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Links unit edges into polylines. Open chains run between their ends;
+/// closed loops repeat their first vertex at the end.
+#[must_use]
+pub fn chain(edges: &[[Vertex; 2]]) -> Vec<Vec<Vertex>> {
+    let mut at_vertex: HashMap<Vertex, Vec<usize>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        for vertex in edge {
+            at_vertex.entry(*vertex).or_default().push(i);
+        }
+    }
+    let mut used = vec![false; edges.len()];
+    let mut chains = Vec::new();
+    // Start from chain ends first so open chains are walked whole
+    let mut starts: Vec<Vertex> = at_vertex
+        .iter()
+        .filter(|(_, list)| list.len() % 2 == 1)
+        .map(|(&vertex, _)| vertex)
+        .collect();
+    starts.sort_unstable();
+    let mut loop_starts: Vec<Vertex> = edges.iter().map(|edge| edge[0]).collect();
+    loop_starts.sort_unstable();
+    for start in starts.into_iter().chain(loop_starts) {
+        while let Some(&first) = at_vertex[&start].iter().find(|&&i| !used[i]) {
+            let mut polyline = vec![start];
+            let mut current = start;
+            let mut next_edge = Some(first);
+            while let Some(i) = next_edge {
+                used[i] = true;
+                let [a, b] = edges[i];
+                current = if a == current { b } else { a };
+                polyline.push(current);
+                next_edge = at_vertex[&current].iter().copied().find(|&j| !used[j]);
+            }
+            chains.push(polyline);
+        }
+    }
+    chains
+}
+
+/// Simplifies a polyline with the Douglas–Peucker algorithm, keeping its
+/// end points and dropping vertices closer than `tolerance` to the result.
+#[must_use]
+pub fn simplify(points: &[Vertex], tolerance: f64) -> Vec<(f64, f64)> {
+    #[allow(clippy::cast_precision_loss)]
+    let points: Vec<(f64, f64)> = points.iter().map(|&(x, y)| (x as f64, y as f64)).collect();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    // Closed loops have coincident ends, so split them at the far point first
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        if last <= first + 1 {
+            continue;
+        }
+        let (ax, ay) = points[first];
+        let (bx, by) = points[last];
+        let (dx, dy) = (bx - ax, by - ay);
+        let len = dx.hypot(dy);
+        let dist = |&(px, py): &(f64, f64)| {
+            if len == 0.0 {
+                (px - ax).hypot(py - ay)
+            } else {
+                ((px - ax) * dy - (py - ay) * dx).abs() / len
+            }
+        };
+        let (far, far_dist) =
+            (first + 1..last)
+                .map(|i| (i, dist(&points[i])))
+                .fold(
+                    (first, 0.0),
+                    |best, cur| if cur.1 > best.1 { cur } else { best },
+                );
+        if far_dist > tolerance {
+            keep[far] = true;
+            stack.push((first, far));
+            stack.push((far, last));
+        }
+    }
+    points
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(point))
+        .collect()
+}
@@ -0,0 +1,102 @@
+//! Finishing effects run over the rendered image, in the order `--post`
+//! lists them.
+
+use std::str::FromStr;
+
+use image::imageops::fast_blur;
+use rand::Rng;
+
+use crate::color::to_u8;
+use crate::plugins::{self, PostEffect};
+use crate::{Args, rng};
+
+/// Standard deviation, in pixels, of the blur `sharpen` subtracts.
+const SHARPEN_RADIUS: f32 = 1.0;
+
+/// Adds monochrome film grain: every pixel brightened or darkened by the
+/// same normally distributed amount in each channel, with standard
+/// deviation `amount` of full scale.
+pub fn grain(image: &mut image::RgbImage, amount: f64, rng: &mut rng::Generator) {
+    for pixel in image.pixels_mut() {
+        // Box-Muller, with 1 - u keeping the logarithm finite
+        let (u, v): (f64, f64) = (rng.random(), rng.random());
+        let normal = (-2.0 * (1.0 - u).ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+        let offset = normal * amount * 255.0;
+        pixel.0 = pixel.0.map(|c| to_u8(f64::from(c) + offset));
+    }
+}
+
+/// Darkens the image towards its edges, the corners by `amount` of their
+/// brightness and the center not at all.
+pub fn vignette(image: &mut image::RgbImage, amount: f64) {
+    let (cx, cy) = (
+        f64::from(image.width()) / 2.0,
+        f64::from(image.height()) / 2.0,
+    );
+    let corner = cx.hypot(cy);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let reach = (f64::from(x) + 0.5 - cx).hypot(f64::from(y) + 0.5 - cy) / corner;
+        let factor = (1.0 - amount * reach * reach).max(0.0);
+        pixel.0 = pixel.0.map(|c| to_u8(f64::from(c) * factor));
+    }
+}
+
+/// Unsharp masking: adds `amount` times the difference between the image
+/// and a slightly blurred copy, crisping cell edges after resampling or
+/// grain.
+pub fn sharpen(image: &mut image::RgbImage, amount: f64) {
+    let blurred = fast_blur(image, SHARPEN_RADIUS);
+    for (pixel, soft) in image.pixels_mut().zip(blurred.pixels()) {
+        for (c, s) in pixel.0.iter_mut().zip(soft.0) {
+            let (c_f, s_f) = (f64::from(*c), f64::from(s));
+            *c = to_u8(c_f + amount * (c_f - s_f));
+        }
+    }
+}
+
+/// One effect of a `--post` pipeline and how strongly it applies.
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    pub effect: &'static dyn PostEffect,
+    pub amount: f64,
+}
+
+/// The effects of `--post`, e.g. "grain:0.05,vignette:0.3,sharpen:0.5",
+/// applied left to right.
+#[derive(Debug, Clone)]
+pub struct Effects(pub Vec<Step>);
+
+impl FromStr for Effects {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|step| {
+                let (name, amount) = step
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid effect '{step}' (expected name:amount)"))?;
+                let effect = plugins::parse_post_effect(name.trim())?;
+                let amount = amount
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|amount| amount.is_finite() && *amount >= 0.0)
+                    .ok_or_else(|| format!("invalid amount '{amount}' for {name}"))?;
+                Ok(Step { effect, amount })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Effects {
+    /// Runs every step over `image` in turn, drawing any randomness from
+    /// the `--rng` generator seeded by `--seed`, so grain repeats with it.
+    pub fn apply(&self, image: &mut image::RgbImage, args: &Args) {
+        let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+        let mut rng = args.rng.seeded(seed);
+        for step in &self.0 {
+            step.effect.apply(image, step.amount, &mut rng);
+        }
+    }
+}
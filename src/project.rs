@@ -0,0 +1,251 @@
+//! Project files saved by `edit --session`, and rendering them headless.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::Parser;
+
+use crate::edit::Edit;
+use crate::incremental::Editor;
+use crate::plane::ImagePlane;
+use crate::{
+    Args, Cli, Command, Diagram, exit_on_err, for_image, generate_voronoi, load_image, max_dists,
+    output, sample_points_print_progress, save_output, scaled_size, score_fn_for, seeded_rng,
+};
+
+type Point = (u32, u32, [u8; 3]);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct RenderArgs {
+    /// Project file saved by `edit --session`
+    #[arg(long)]
+    project: PathBuf,
+
+    /// Render this many times larger than the project was edited at
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=64))]
+    scale: u32,
+
+    /// Output path, instead of the one the project was edited with
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Overwrite the output if it exists
+    #[arg(short, long)]
+    force: bool,
+}
+
+/// First line of every project file.
+const PROJECT_HEADER: &str = "# voronoi project";
+
+/// A project file: the command line of the `edit` run that started it, one
+/// argument per "arg" line, then its edits in order, one per "edit" line.
+#[derive(Debug, Clone, Default)]
+pub struct Project {
+    pub command: Vec<String>,
+    pub edits: Vec<Edit>,
+}
+
+impl FromStr for Project {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = (1..).zip(s.lines());
+        if lines.next().map(|(_, line)| line.trim()) != Some(PROJECT_HEADER) {
+            return Err(format!(
+                "not a project file: it should start with \"{PROJECT_HEADER}\""
+            ));
+        }
+        let mut project = Self::default();
+        for (number, line) in lines {
+            match line.split_once(' ') {
+                Some(("arg", arg)) => project.command.push(arg.to_string()),
+                Some(("edit", edit)) => project.edits.push(
+                    edit.parse()
+                        .map_err(|err| format!("line {number}: {err}"))?,
+                ),
+                _ if line.trim().is_empty() => {}
+                _ => return Err(format!("line {number}: expected \"arg\" or \"edit\"")),
+            }
+        }
+        if project.command.is_empty() {
+            return Err("the project has no command line".to_string());
+        }
+        Ok(project)
+    }
+}
+
+impl std::fmt::Display for Project {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{PROJECT_HEADER}")?;
+        for arg in &self.command {
+            writeln!(f, "arg {arg}")?;
+        }
+        for edit in &self.edits {
+            writeln!(f, "edit {edit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the project file at `path`.
+pub fn load_project(path: &Path) -> Result<Project, String> {
+    std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())?
+        .parse()
+}
+
+/// The parameters of the `edit` run recorded in `project`.
+pub fn project_args(project: &Project) -> Result<Args, String> {
+    let cli = Cli::try_parse_from(&project.command).map_err(|err| err.to_string())?;
+    match cli.command {
+        Some(Command::Edit(edit)) => Ok(edit.args),
+        _ => Err("the project was not made by the edit command".to_string()),
+    }
+}
+
+/// Renders the project's edited sites with its full set of options, on its
+/// image enlarged `--scale` times.
+pub fn run(render: &RenderArgs) {
+    let project = exit_on_err(load_project(&render.project), "read project");
+    let mut args = exit_on_err(project_args(&project), "read project");
+    if let Some(output) = &render.output {
+        args.output.clone_from(output);
+    }
+    args.force |= render.force;
+    exit_on_err(
+        output::check_clobber(&args.output, args.force),
+        "write output",
+    );
+
+    // Replay the edits at the size they were made at
+    let img = load_image(&args.input, &args);
+    let args = for_image(&args, &img);
+    let points = exit_on_err(replay(&project, &img, &args), "replay edits");
+    println!(
+        "Replayed {} edits to {} sites",
+        project.edits.len(),
+        points.len()
+    );
+    let diagram = exit_on_err(
+        render_scaled(&img, &points, render.scale, &args),
+        "render project",
+    );
+    save_output(&diagram, &args);
+    let (width, height) = diagram.image.dimensions();
+    eprintln!(
+        "Saved {width}x{height} voronoi diagram to {}",
+        args.output.display()
+    );
+}
+
+/// The sites `edit` ended with: those sampled from `img`, then changed by
+/// each of the project's edits in turn.
+fn replay(project: &Project, img: &image::RgbImage, args: &Args) -> Result<Vec<Point>, String> {
+    let mut rng = seeded_rng(args);
+    let points = sample_points_print_progress(img, args, args.points, &mut rng);
+    let points = args.seed_color.apply(img, &points);
+    let mut editor = Editor::new(img.clone(), points, args);
+    for (number, edit) in (1..).zip(&project.edits) {
+        edit.apply(&mut editor)
+            .map_err(|err| format!("edit {number}: {err}"))?;
+    }
+    Ok(editor.points().to_vec())
+}
+
+/// The full render of `points` on `img` enlarged `scale` times, or an
+/// error if that is too large for `--max-pixels`.
+fn render_scaled(
+    img: &image::RgbImage,
+    points: &[Point],
+    scale: u32,
+    args: &Args,
+) -> Result<Diagram, String> {
+    let (width, height) = scaled_size(img.width(), img.height(), scale, args.max_pixels)?;
+    let scaled;
+    let img = if scale == 1 {
+        img
+    } else {
+        scaled = image::imageops::resize(img, width, height, image::imageops::FilterType::Lanczos3);
+        &scaled
+    };
+    // Sites move to the middle of the block their pixel grew into
+    let points: Vec<_> = points
+        .iter()
+        .map(|&(x, y, color)| (x * scale + scale / 2, y * scale + scale / 2, color))
+        .collect();
+    let (max_color_dist, max_pos_dist) = max_dists(img, args);
+    let plane = ImagePlane::new(img, args.blur);
+    Ok(generate_voronoi(
+        &plane,
+        &points,
+        max_color_dist,
+        max_pos_dist,
+        &score_fn_for(&plane, args),
+        args,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn project(edits: &[&str]) -> Project {
+        Project {
+            command: ["voronoi", "edit", "in.png", "out.png"]
+                .map(String::from)
+                .into(),
+            edits: edits.iter().map(|edit| edit.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn project_files_round_trip() {
+        let project = project(&["add 3 4", "move 0 5 6", "delete 1 2"]);
+        let text = project.to_string();
+        assert_eq!(text.parse::<Project>().unwrap().to_string(), text);
+        assert!("arg voronoi".parse::<Project>().is_err());
+    }
+
+    #[test]
+    fn unedited_projects_render_as_the_plain_render() {
+        let img = testing::image(48, 36);
+        for options in [
+            &["-p", "15", "--seed", "1"][..],
+            &["-p", "15", "--seed", "2", "--style", "brush"],
+        ] {
+            let args = for_image(&testing::args(options), &img);
+            let points = replay(&project(&[]), &img, &args).unwrap();
+            let mut timings = crate::timings::Timings::default();
+            let plain = crate::build_diagram(&img, &args, &mut timings).0;
+            let replayed = render_scaled(&img, &points, 1, &args).unwrap();
+            assert_eq!(replayed.image, plain.image, "{options:?}");
+        }
+    }
+
+    #[test]
+    fn edits_are_replayed_in_order() {
+        let img = testing::image(48, 36);
+        let args = for_image(&testing::args(&["-p", "15", "--seed", "1"]), &img);
+        let points = replay(&project(&["move 0 5 6", "add 40 30"]), &img, &args).unwrap();
+        assert_eq!(points.len(), 16);
+        assert_eq!((points[0].0, points[0].1), (5, 6));
+        assert_eq!((points[15].0, points[15].1), (40, 30));
+        assert!(replay(&project(&["remove 99"]), &img, &args).is_err());
+    }
+
+    #[test]
+    fn scales_over_max_pixels_are_refused() {
+        let img = testing::image(64, 48);
+        let args = testing::args(&["-p", "10", "--seed", "1", "--max-pixels", "4000"]);
+        let points = replay(&project(&[]), &img, &args).unwrap();
+        assert!(render_scaled(&img, &points, 20, &args).is_err());
+        assert_eq!(
+            render_scaled(&img, &points, 1, &args)
+                .unwrap()
+                .image
+                .dimensions(),
+            (64, 48)
+        );
+    }
+}
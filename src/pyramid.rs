@@ -0,0 +1,136 @@
+//! Smaller copies of a diagram for zoomable viewers, drawn from a halved
+//! label map at each level so cells stay crisp instead of blurring together.
+
+use std::path::{Path, PathBuf};
+
+/// A label map `width` by `height` pixels.
+pub struct Level {
+    pub labels: Vec<u32>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Level {
+    /// The level half as large in each direction, rounding up, each pixel
+    /// taking the label nearest the middle of the block it covers.
+    #[must_use]
+    pub fn halved(&self) -> Self {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let mut labels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let sy = (2 * y + 1).min(self.height - 1);
+            labels.extend((0..width).map(|x| {
+                let sx = (2 * x + 1).min(self.width - 1);
+                self.labels[(sy * self.width + sx) as usize]
+            }));
+        }
+        Self {
+            labels,
+            width,
+            height,
+        }
+    }
+
+    /// The level drawn with each cell in its color.
+    #[must_use]
+    pub fn render(&self, colors: &[[u8; 3]]) -> image::RgbImage {
+        image::RgbImage::from_fn(self.width, self.height, |x, y| {
+            image::Rgb(colors[self.labels[(y * self.width + x) as usize] as usize])
+        })
+    }
+}
+
+/// The label maps `count` halvings below `labels`, largest first.
+#[must_use]
+pub fn levels(labels: &[u32], width: u32, height: u32, count: u8) -> Vec<Level> {
+    let mut level = Level {
+        labels: labels.to_vec(),
+        width,
+        height,
+    };
+    (0..count)
+        .map(|_| {
+            level = level.halved();
+            Level {
+                labels: level.labels.clone(),
+                ..level
+            }
+        })
+        .collect()
+}
+
+/// Where level `level` of the pyramid for `output` goes: "out.png" has its
+/// half-size copy at "out.mip1.png", its quarter-size one at "out.mip2.png"
+/// and so on.
+#[must_use]
+pub fn path(output: &Path, level: u8) -> PathBuf {
+    let extension = output
+        .extension()
+        .map_or_else(String::new, |ext| format!(".{}", ext.to_string_lossy()));
+    output.with_extension(format!("mip{level}{extension}"))
+}
+
+/// Edge length of Deep Zoom tiles, less the overlap on each side.
+const DZI_TILE_SIZE: u32 = 254;
+
+/// Pixels each Deep Zoom tile shares with its neighbours.
+const DZI_OVERLAP: u32 = 1;
+
+/// Saves the tiles of `image` as a Deep Zoom image for `OpenSeadragon` and
+/// similar viewers: a tile tree of PNGs in the "`name`_files" directory
+/// beside the descriptor at `path`, one subdirectory per level from 1x1 up
+/// to full size. Levels below full size are drawn from halved label maps
+/// with each cell in its color from `colors`.
+pub fn save_dzi_tiles(
+    path: &Path,
+    image: &image::RgbImage,
+    labels: &[u32],
+    colors: &[[u8; 3]],
+) -> image::ImageResult<()> {
+    let (width, height) = image.dimensions();
+    let mut level = Level {
+        labels: labels.to_vec(),
+        width,
+        height,
+    };
+    let top = width.max(height).next_power_of_two().trailing_zeros();
+    let dir = path.with_file_name(format!(
+        "{}_files",
+        path.file_stem().unwrap_or_default().to_string_lossy()
+    ));
+    for number in (0..=top).rev() {
+        let drawn;
+        let picture = if number == top {
+            image
+        } else {
+            level = level.halved();
+            drawn = level.render(colors);
+            &drawn
+        };
+        let level_dir = dir.join(number.to_string());
+        std::fs::create_dir_all(&level_dir)?;
+        let (level_width, level_height) = picture.dimensions();
+        for row in 0..level_height.div_ceil(DZI_TILE_SIZE) {
+            for column in 0..level_width.div_ceil(DZI_TILE_SIZE) {
+                let x = (column * DZI_TILE_SIZE).saturating_sub(DZI_OVERLAP);
+                let y = (row * DZI_TILE_SIZE).saturating_sub(DZI_OVERLAP);
+                let x_end = ((column + 1) * DZI_TILE_SIZE + DZI_OVERLAP).min(level_width);
+                let y_end = ((row + 1) * DZI_TILE_SIZE + DZI_OVERLAP).min(level_height);
+                image::imageops::crop_imm(picture, x, y, x_end - x, y_end - y)
+                    .to_image()
+                    .save(level_dir.join(format!("{column}_{row}.png")))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `.dzi` descriptor of a `width` by `height` Deep Zoom image.
+#[must_use]
+pub fn dzi_descriptor(width: u32, height: u32) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" TileSize=\"{DZI_TILE_SIZE}\" \
+         Overlap=\"{DZI_OVERLAP}\" Format=\"png\">\n  <Size Width=\"{width}\" Height=\"{height}\"/>\n</Image>\n"
+    )
+}
@@ -0,0 +1,160 @@
+//! Diagrams drawn straight from a list of sites, with no source image.
+
+use std::path::PathBuf;
+
+use rand::prelude::*;
+
+use crate::generate::parse_size;
+use crate::{color, colorize, exit_on_err, export, labels, output, rng, treemap};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct RasterizeArgs {
+    /// Output file path; an .svg extension writes vector cells
+    output: PathBuf,
+
+    /// JSON array of sites, each {"x", "y"} with an optional "color"
+    /// ("#rrggbb") and "weight" (a power-diagram weight in square pixels)
+    #[arg(long)]
+    points: PathBuf,
+
+    /// Size of the diagram, e.g. "800x600"
+    #[arg(long, value_parser = parse_size)]
+    size: (u32, u32),
+
+    /// Refuse sizes with more pixels than this
+    #[arg(long, default_value_t = crate::DEFAULT_MAX_PIXELS, value_parser = clap::value_parser!(u64).range(1..))]
+    max_pixels: u64,
+
+    /// Seed for the colors of sites that have none
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Random number generator algorithm
+    #[arg(long, value_enum, default_value_t = rng::Algorithm::Stdrng)]
+    rng: rng::Algorithm,
+
+    /// Overwrite the output if it exists
+    #[arg(short, long)]
+    force: bool,
+}
+
+/// A site read from the points file.
+#[derive(Debug, Clone, Copy)]
+struct Site {
+    position: (f64, f64),
+    color: Option<[u8; 3]>,
+    weight: f64,
+}
+
+fn parse_sites(data: &str) -> Result<Vec<Site>, String> {
+    let sites = export::json_objects(data)?
+        .iter()
+        .enumerate()
+        .map(|(i, object)| {
+            let number = |key: &str| {
+                object
+                    .get(key)
+                    .map(|value| {
+                        value.parse::<f64>().map_err(|err| {
+                            format!("site {}: invalid {key} \"{value}\": {err}", i + 1)
+                        })
+                    })
+                    .transpose()
+            };
+            let (Some(x), Some(y)) = (number("x")?, number("y")?) else {
+                return Err(format!("site {} has no x and y", i + 1));
+            };
+            Ok(Site {
+                position: (x, y),
+                color: object.get("color").map(color::parse_hex).transpose()?,
+                weight: number("weight")?.unwrap_or(0.0),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if sites.is_empty() {
+        return Err("no sites".to_string());
+    }
+    Ok(sites)
+}
+
+pub fn run(args: &RasterizeArgs) {
+    exit_on_err(
+        output::check_clobber(&args.output, args.force),
+        "write output",
+    );
+    let (width, height) = args.size;
+    exit_on_err(
+        crate::check_pixels(width, height, args.max_pixels),
+        "check size",
+    );
+    let data = exit_on_err(std::fs::read_to_string(&args.points), "read points");
+    let sites = exit_on_err(parse_sites(&data), "parse points");
+    println!("Sites: {}", sites.len());
+
+    let positions: Vec<(f64, f64)> = sites.iter().map(|site| site.position).collect();
+    let weights: Vec<f64> = sites.iter().map(|site| site.weight).collect();
+    let labels = treemap::power_labels(width, height, &positions, &weights);
+
+    let mut colors: Vec<[u8; 3]> = sites
+        .iter()
+        .map(|site| site.color.unwrap_or_default())
+        .collect();
+    if sites.iter().any(|site| site.color.is_none()) {
+        let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+        println!("Seed: {seed}");
+        let safe = colorize::safe_colors(
+            &labels::adjacency(&labels, width, sites.len()),
+            &mut args.rng.seeded(seed),
+        );
+        for ((color, site), safe) in colors.iter_mut().zip(&sites).zip(safe) {
+            if site.color.is_none() {
+                *color = safe;
+            }
+        }
+    }
+
+    let is_svg = args
+        .output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    let result = output::save(&args.output, args.force, |path| {
+        if is_svg {
+            std::fs::write(path, export::svg(&labels, &colors, width, height))
+                .map_err(image::ImageError::IoError)
+        } else {
+            image::RgbImage::from_fn(width, height, |x, y| {
+                image::Rgb(colors[labels[(y * width + x) as usize] as usize])
+            })
+            .save(path)
+        }
+    });
+    exit_on_err(result, "save diagram");
+    eprintln!("Saved voronoi diagram to {}", args.output.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_named_like_keys_are_not_keys() {
+        let sites = parse_sites(r#"[{"label": "x", "x": 10, "y": 20}]"#).unwrap();
+        assert_eq!(sites[0].position, (10.0, 20.0));
+    }
+
+    #[test]
+    fn braces_in_strings_stay_in_their_object() {
+        let sites =
+            parse_sites(r##"[{"note": "{\"x\": 1}", "x": 3, "y": 4, "color": "#ff0000"}]"##)
+                .unwrap();
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0].position, (3.0, 4.0));
+        assert_eq!(sites[0].color, Some([255, 0, 0]));
+    }
+
+    #[test]
+    fn sites_need_x_and_y() {
+        assert!(parse_sites(r#"[{"x": 1}]"#).is_err());
+        assert!(parse_sites("[]").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Rendering parts of an image with different options, picked by a mask.
+
+use std::path::Path;
+
+use crate::overrides::{parse_tables, with_overrides};
+use crate::{Args, Diagram, build_diagram, exit_on_err, for_image, timings};
+
+/// Renders each mask level that has a table in `params_path` with that
+/// table's options, and paints the result over `diagram` where the mask at
+/// `mask_path` has that level. Gray levels without a table keep `diagram`.
+pub fn composite(
+    img: &image::RgbImage,
+    diagram: &mut Diagram,
+    args: &Args,
+    mask_path: &Path,
+    params_path: &Path,
+    timings: &mut timings::Timings,
+) {
+    let mask = exit_on_err(
+        crate::open_image(mask_path, args.max_pixels),
+        "open region mask",
+    )
+    .into_luma8();
+    if mask.dimensions() != img.dimensions() {
+        eprintln!(
+            "Failed to apply regions: the mask is {}x{} but the image is {}x{}",
+            mask.width(),
+            mask.height(),
+            img.width(),
+            img.height()
+        );
+        std::process::exit(1);
+    }
+    let text = exit_on_err(
+        std::fs::read_to_string(params_path),
+        "read region parameters",
+    );
+    let tables = exit_on_err(parse_tables(&text), "parse region parameters");
+    for (name, overrides) in tables {
+        let Ok(level) = name.parse::<u8>() else {
+            eprintln!(
+                "Failed to parse region parameters: table [{name}] is not a gray level 0-255"
+            );
+            std::process::exit(1);
+        };
+        println!("Region {level}:");
+        let region = exit_on_err(with_overrides(args, &overrides), "apply region parameters");
+        let region = for_image(&region, img);
+        let (rendered, _) = build_diagram(img, &region, timings);
+        for ((pixel, shade), source) in diagram
+            .image
+            .pixels_mut()
+            .zip(mask.pixels())
+            .zip(rendered.image.pixels())
+        {
+            if shade.0[0] == level {
+                *pixel = *source;
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Lloyd relaxation, which evens out cells by moving each point to the
+//! middle of its cell.
+
+use crate::cells::CellStats;
+
+/// One step of Lloyd relaxation: moves every point to the centroid of its
+/// cell and picks up the image color there. Points with empty cells stay put.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn lloyd_step(
+    img: &image::RgbImage,
+    points: &[(u32, u32, [u8; 3])],
+    stats: &[CellStats],
+) -> Vec<(u32, u32, [u8; 3])> {
+    let (max_x, max_y) = (img.width() - 1, img.height() - 1);
+    points
+        .iter()
+        .zip(stats)
+        .map(|(&point, cell)| match cell.centroid() {
+            Some((cx, cy)) => {
+                let x = (cx.round() as u32).min(max_x);
+                let y = (cy.round() as u32).min(max_y);
+                (x, y, img.get_pixel(x, y).0)
+            }
+            None => point,
+        })
+        .collect()
+}
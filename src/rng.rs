@@ -0,0 +1,128 @@
+//! Named random number generators, so a seed keeps reproducing the same
+//! image even if `rand` changes what `StdRng` is.
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// Whatever `rand` currently uses as its standard generator
+    #[default]
+    Stdrng,
+    /// The chacha stream cipher with 12 rounds
+    Chacha12,
+    /// PCG XSL RR 128/64, as in `rand_pcg::Pcg64`
+    Pcg64,
+}
+
+impl Algorithm {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Stdrng => "stdrng",
+            Algorithm::Chacha12 => "chacha12",
+            Algorithm::Pcg64 => "pcg64",
+        }
+    }
+
+    /// A generator of this algorithm seeded from `seed`.
+    #[must_use]
+    pub fn seeded(self, seed: u64) -> Generator {
+        match self {
+            Algorithm::Stdrng => Generator::Std(StdRng::seed_from_u64(seed)),
+            Algorithm::Chacha12 => Generator::ChaCha12(ChaCha12Rng::seed_from_u64(seed)),
+            Algorithm::Pcg64 => Generator::Pcg64(Pcg64::seed_from_u64(seed)),
+        }
+    }
+}
+
+/// A generator of any of the named algorithms.
+#[derive(Debug, Clone)]
+pub enum Generator {
+    Std(StdRng),
+    ChaCha12(ChaCha12Rng),
+    Pcg64(Pcg64),
+}
+
+impl RngCore for Generator {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            Generator::Std(rng) => rng.next_u32(),
+            Generator::ChaCha12(rng) => rng.next_u32(),
+            Generator::Pcg64(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            Generator::Std(rng) => rng.next_u64(),
+            Generator::ChaCha12(rng) => rng.next_u64(),
+            Generator::Pcg64(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        match self {
+            Generator::Std(rng) => rng.fill_bytes(dst),
+            Generator::ChaCha12(rng) => rng.fill_bytes(dst),
+            Generator::Pcg64(rng) => rng.fill_bytes(dst),
+        }
+    }
+}
+
+/// Permuted congruential generator with 128-bit state and 64-bit output
+/// (XSL RR), matching `rand_pcg::Lcg128Xsl64` output for output.
+#[derive(Debug, Clone)]
+pub struct Pcg64 {
+    state: u128,
+    increment: u128,
+}
+
+impl Pcg64 {
+    const MULTIPLIER: u128 = 0x2360_ED05_1FC6_5DA4_4385_DF64_9FCC_F645;
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl SeedableRng for Pcg64 {
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let (state, stream) = seed.split_at(16);
+        let state = u128::from_le_bytes(state.try_into().unwrap());
+        let stream = u128::from_le_bytes(stream.try_into().unwrap());
+        // The increment must be odd
+        let mut pcg = Pcg64 {
+            state,
+            increment: (stream << 1) | 1,
+        };
+        pcg.state = pcg.state.wrapping_add(pcg.increment);
+        pcg.step();
+        pcg
+    }
+}
+
+impl RngCore for Pcg64 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn next_u64(&mut self) -> u64 {
+        let state = self.state;
+        self.step();
+        let rotation = (state >> 122) as u32;
+        (((state >> 64) as u64) ^ (state as u64)).rotate_right(rotation)
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        rand::rand_core::impls::fill_bytes_via_next(self, dst);
+    }
+}
@@ -0,0 +1,58 @@
+//! Uneven work spread over every core.
+//!
+//! Jobs are handed out one at a time from a shared queue, most expensive
+//! first by the caller's estimate, so a thread that finishes early takes
+//! the next job instead of idling while others work through a fixed share.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Runs `job` on each index of `costs` across the available threads,
+/// starting the jobs with the highest cost first. `finished` is called
+/// with the number of jobs done after each one. Returns the results in
+/// index order.
+pub fn run<T: Send>(
+    costs: &[u64],
+    job: impl Fn(usize) -> T + Sync,
+    finished: impl Fn(usize) + Sync,
+) -> Vec<T> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, std::num::NonZero::get)
+        .min(costs.len());
+    if threads <= 1 {
+        return (0..costs.len())
+            .map(|index| {
+                let result = job(index);
+                finished(index + 1);
+                result
+            })
+            .collect();
+    }
+
+    let mut queue: Vec<usize> = (0..costs.len()).collect();
+    queue.sort_by_key(|&index| std::cmp::Reverse(costs[index]));
+    let (next, done) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let mut results: Vec<Option<T>> = std::iter::repeat_with(|| None).take(costs.len()).collect();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    while let Some(&index) = queue.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        results.push((index, job(index)));
+                        finished(done.fetch_add(1, Ordering::Relaxed) + 1);
+                    }
+                    results
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (index, result) in worker.join().expect("worker thread panicked") {
+                results[index] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("every job ran"))
+        .collect()
+}
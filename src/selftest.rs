@@ -0,0 +1,187 @@
+//! The `selftest` subcommand: renders tiny built-in images through each
+//! sampler, metric, projection and assignment path with fixed seeds and
+//! checks the results against known hashes, so a build can be confirmed to
+//! reproduce the reference output on its hardware.
+
+use clap::Parser;
+
+use crate::plane::ImagePlane;
+use crate::{Cli, distance_fn, generate_voronoi, max_dists, relax, sample_points};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct SelftestArgs {
+    /// Print each render's hash without checking it, for updating the
+    /// reference hashes after an intended change to the output
+    #[arg(long)]
+    print: bool,
+}
+
+/// A smooth RGB ramp with a bright disk off center.
+fn ramp_with_disk(x: u32, y: u32) -> [u8; 3] {
+    let inside = (x.abs_diff(20).pow(2) + y.abs_diff(10).pow(2)) < 36;
+    if inside {
+        [250, 240, 40]
+    } else {
+        #[allow(clippy::cast_possible_truncation)]
+        [(x * 8) as u8, (y * 10) as u8, ((x + y) * 4) as u8]
+    }
+}
+
+/// Four flat quadrants of strongly different colors.
+fn quadrants(x: u32, y: u32) -> [u8; 3] {
+    match (x < 16, y < 12) {
+        (true, true) => [200, 30, 30],
+        (false, true) => [30, 200, 30],
+        (true, false) => [30, 30, 200],
+        (false, false) => [220, 220, 220],
+    }
+}
+
+/// One reference render: an image, the options it is rendered with, and the
+/// hash of the expected output.
+struct Case {
+    name: &'static str,
+    pixel: fn(u32, u32) -> [u8; 3],
+    options: &'static [&'static str],
+    hash: u64,
+}
+
+/// Size of the built-in images.
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+
+const CASES: &[Case] = &[
+    Case {
+        name: "default",
+        pixel: ramp_with_disk,
+        options: &[],
+        hash: 0xba93_5453_e473_5d1d,
+    },
+    Case {
+        name: "uniform sampler",
+        pixel: ramp_with_disk,
+        options: &["--sampler", "uniform"],
+        hash: 0x4298_a0f1_ea25_0c91,
+    },
+    Case {
+        name: "spatial distance",
+        pixel: quadrants,
+        options: &["--distance", "spatial"],
+        hash: 0xf2a8_22ab_5e67_cb8d,
+    },
+    Case {
+        name: "balanced score",
+        pixel: quadrants,
+        options: &["--balance", "0.6"],
+        hash: 0x43de_f0a9_0e9c_2141,
+    },
+    Case {
+        name: "coarse to fine",
+        pixel: ramp_with_disk,
+        options: &["--coarse-to-fine", "4"],
+        hash: 0xba93_5453_e473_5d1d,
+    },
+    Case {
+        name: "equirect projection",
+        pixel: ramp_with_disk,
+        options: &["--projection", "equirect"],
+        hash: 0xd9e1_8285_3870_3e7b,
+    },
+    Case {
+        name: "polar projection",
+        pixel: quadrants,
+        options: &["--projection", "polar"],
+        hash: 0xafd0_f143_c2aa_731d,
+    },
+    Case {
+        name: "grayscale colorizer",
+        pixel: ramp_with_disk,
+        options: &["--colorizer", "grayscale"],
+        hash: 0x64ac_818e_e436_9a5d,
+    },
+    Case {
+        name: "relaxation",
+        pixel: quadrants,
+        options: &["--relax", "2"],
+        hash: 0xa4f9_7392_efdd_64f1,
+    },
+];
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher is fixed
+/// across Rust versions and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Renders `case` without printing progress and hashes the image.
+fn render(case: &Case) -> u64 {
+    let command = [
+        "voronoi",
+        "selftest.png",
+        "selftest.png",
+        "-p",
+        "24",
+        "--seed",
+        "1",
+    ];
+    let cli = Cli::try_parse_from(command.iter().chain(case.options))
+        .unwrap_or_else(|err| panic!("bad options for {}: {err}", case.name));
+    let args = cli.args.expect("clap requires INPUT and OUTPUT");
+    let img = image::RgbImage::from_fn(WIDTH, HEIGHT, |x, y| image::Rgb((case.pixel)(x, y)));
+    let plane = ImagePlane::new(&img, args.blur);
+    let (max_color_dist, max_pos_dist) = max_dists(&img, &args);
+    let distance = distance_fn(&args);
+    let mut rng = args.rng.seeded(1);
+    let mut points = sample_points(&img, &args, args.points, &mut rng);
+    for _ in 0..args.relax {
+        let step = generate_voronoi(
+            &plane,
+            &points,
+            max_color_dist,
+            max_pos_dist,
+            &distance,
+            &args,
+        );
+        points = relax::lloyd_step(&img, &points, &step.stats);
+    }
+    let diagram = generate_voronoi(
+        &plane,
+        &points,
+        max_color_dist,
+        max_pos_dist,
+        &distance,
+        &args,
+    );
+    fnv1a(diagram.image.as_raw())
+}
+
+pub fn run(selftest: &SelftestArgs) {
+    let mut failures = 0;
+    for case in CASES {
+        let hash = render(case);
+        if selftest.print {
+            println!("{:016x}  {}", hash, case.name);
+        } else if hash == case.hash {
+            println!("ok    {}", case.name);
+        } else {
+            println!(
+                "FAIL  {}: expected {:016x}, got {hash:016x}",
+                case.name, case.hash
+            );
+            failures += 1;
+        }
+    }
+    if selftest.print {
+        return;
+    }
+    println!(
+        "{} of {} reference renders match",
+        CASES.len() - failures,
+        CASES.len()
+    );
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,303 @@
+//! Renders split into horizontal bands for `--shard`, so one huge diagram
+//! can be drawn by several machines at once, and `merge` to stack the
+//! bands back into the whole.
+//!
+//! Every shard places the sites over the whole image, as `--viewport`
+//! does, and draws only its band. Options that color or shape a cell from
+//! all of its pixels or from its neighbours would see only the band's part
+//! of the cells cut by its edges, so those are refused and the bands meet
+//! seamlessly.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::CommandFactory;
+
+use crate::{Args, CellFill, Cli, Style, exit_on_err, metadata, output, reject_options};
+
+/// One of `count` equal bands of rows, numbered from 1 at the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected \"I/N\", got \"{s}\""))?;
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<u32>()
+                .map_err(|err| format!("invalid shard value \"{value}\": {err}"))
+        };
+        let (index, count) = (parse(index)?, parse(count)?);
+        if index == 0 || index > count {
+            return Err(format!("shard {index} is not between 1 and {count}"));
+        }
+        Ok(Self { index, count })
+    }
+}
+
+impl Shard {
+    /// The first row and the number of rows of this band of an image
+    /// `height` rows tall, or an error if there are fewer rows than bands.
+    pub fn rows(self, height: u32) -> Result<(u32, u32), String> {
+        if self.count > height {
+            return Err(format!(
+                "{} shards are more than the {height} rows of the image",
+                self.count
+            ));
+        }
+        let edge = |band: u32| {
+            u32::try_from(u64::from(height) * u64::from(band) / u64::from(self.count))
+                .expect("band edges lie within the image")
+        };
+        let (top, bottom) = (edge(self.index - 1), edge(self.index));
+        Ok((top, bottom - top))
+    }
+}
+
+/// Exits if `args` asks for something drawn from whole cells or their
+/// neighbours, which a band cannot see past its edges. Options clap can
+/// rule out by presence alone conflict with `--shard` there instead.
+pub fn reject_unseamed_options(args: &Args) {
+    reject_options(
+        "--shard",
+        &[
+            (
+                "--cell-color average",
+                args.cell_color == crate::CellColor::Average,
+            ),
+            (
+                "--cell-color random-safe",
+                matches!(args.cell_color, crate::CellColor::RandomSafe { .. }),
+            ),
+            (
+                "--cell-color map",
+                matches!(args.cell_color, crate::CellColor::Map { .. }),
+            ),
+            ("--cell-fill gradient", args.cell_fill == CellFill::Gradient),
+            (
+                "--cell-fill pattern",
+                matches!(args.cell_fill, CellFill::Pattern(_)),
+            ),
+            ("--style papercut", args.style == Style::Papercut),
+            ("--style bricks", args.style == Style::Bricks),
+            ("--style halftone", args.style == Style::Halftone),
+            (
+                "--colorizer grayscale",
+                args.colorizer.name() == "grayscale",
+            ),
+        ],
+    );
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct MergeArgs {
+    /// Output image file path
+    output: PathBuf,
+
+    /// Images rendered with --shard 1/N to N/N, in that order
+    #[arg(required = true, num_args = 1..)]
+    shards: Vec<PathBuf>,
+
+    /// Refuse a merged image with more pixels than this
+    #[arg(long, default_value_t = crate::DEFAULT_MAX_PIXELS, value_parser = clap::value_parser!(u64).range(1..))]
+    max_pixels: u64,
+
+    /// Overwrite the output if it exists
+    #[arg(short, long)]
+    force: bool,
+}
+
+/// The shard a PNG was rendered as and the options it was rendered with,
+/// without the program, input, output and shard, if it recorded them.
+fn recorded(path: &Path) -> Option<(Shard, Vec<String>)> {
+    let command = metadata::read_command(path).ok()?;
+    let matches = Cli::command().try_get_matches_from(&command).ok()?;
+    let shard = *matches.get_one::<Shard>("shard")?;
+    let skipped: Vec<usize> = ["input", "output", "shard"]
+        .into_iter()
+        .filter_map(|id| matches.index_of(id))
+        .chain([0])
+        .collect();
+    let options = command
+        .into_iter()
+        .enumerate()
+        .filter(|(i, arg)| !skipped.contains(i) && arg != "--shard")
+        .map(|(_, arg)| arg)
+        .collect();
+    Some((shard, options))
+}
+
+/// Checks that images of `sizes` at `paths` are the bands of one render in
+/// order: equally wide, each as tall as its band of the whole, and where
+/// they recorded how they were rendered, numbered 1 to N with the same
+/// options. Returns the size of the whole.
+fn check_shards(paths: &[PathBuf], sizes: &[(u32, u32)]) -> Result<(u32, u32), String> {
+    let width = sizes[0].0;
+    let height = sizes.iter().map(|&(_, height)| height).sum();
+    let count = u32::try_from(sizes.len()).map_err(|_| "too many shards".to_string())?;
+    let mut first_options = None;
+    for (index, (path, &(shard_width, shard_height))) in (1..).zip(paths.iter().zip(sizes)) {
+        let name = path.display();
+        if shard_width != width {
+            return Err(format!(
+                "shard {name} is {shard_width} pixels wide, the first is {width}"
+            ));
+        }
+        let (_, rows) = Shard { index, count }.rows(height)?;
+        if shard_height != rows {
+            return Err(format!(
+                "shard {name} is {shard_height} rows tall, but band {index} of {count} \
+                 of a {width}x{height} image has {rows}"
+            ));
+        }
+        let Some((shard, options)) = recorded(path) else {
+            continue;
+        };
+        if shard != (Shard { index, count }) {
+            return Err(format!(
+                "{name} was rendered as shard {}/{}, but is given as {index}/{count}",
+                shard.index, shard.count
+            ));
+        }
+        match &first_options {
+            None => first_options = Some(options),
+            Some(first) if *first != options => {
+                return Err(format!(
+                    "{name} was rendered with other options than {}",
+                    paths[0].display()
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok((width, height))
+}
+
+/// Stacks `shards` top to bottom into one image `width` pixels wide.
+fn stack(shards: &[image::RgbImage], width: u32) -> image::RgbImage {
+    let height = shards.iter().map(image::RgbImage::height).sum();
+    let mut merged = image::RgbImage::new(width, height);
+    let mut top = 0;
+    for shard in shards {
+        image::imageops::replace(&mut merged, shard, 0, i64::from(top));
+        top += shard.height();
+    }
+    merged
+}
+
+/// Stacks the shard images of `merge` top to bottom into one image.
+pub fn run(merge: &MergeArgs) {
+    exit_on_err(
+        output::check_clobber(&merge.output, merge.force),
+        "write output",
+    );
+    // Check the sizes from the headers before decoding any of them
+    let sizes: Vec<(u32, u32)> = merge
+        .shards
+        .iter()
+        .map(|path| exit_on_err(image::image_dimensions(path), "read shard"))
+        .collect();
+    let (width, height) = exit_on_err(check_shards(&merge.shards, &sizes), "merge shards");
+    exit_on_err(
+        crate::check_pixels(width, height, merge.max_pixels),
+        "merge shards",
+    );
+    let shards: Vec<image::RgbImage> = merge
+        .shards
+        .iter()
+        .map(|path| exit_on_err(crate::open_image(path, merge.max_pixels), "open shard").to_rgb8())
+        .collect();
+    let merged = stack(&shards, width);
+    exit_on_err(
+        output::save(&merge.output, merge.force, |path| merged.save(path)),
+        "save merged image",
+    );
+    println!(
+        "Merged {} shards into a {width}x{height} image at {}",
+        shards.len(),
+        merge.output.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// Renders `img` with `options`, or only band `shard` of it.
+    fn render(img: &image::RgbImage, options: &[&str], shard: Option<Shard>) -> image::RgbImage {
+        let command = ["voronoi", "in.png", "out.png"].iter().chain(options);
+        let mut args = Cli::try_parse_from(command).unwrap().args.unwrap();
+        args.shard = shard;
+        let args = crate::for_image(&args, img);
+        let mut timings = crate::timings::Timings::default();
+        crate::build_diagram(img, &args, &mut timings).0.image
+    }
+
+    #[test]
+    fn merged_shards_match_the_full_render() {
+        #[allow(clippy::cast_possible_truncation)]
+        let img = image::RgbImage::from_fn(96, 71, |x, y| {
+            image::Rgb([(x * 2) as u8, (y * 3) as u8, ((x * y) % 251) as u8])
+        });
+        for options in [
+            &["-p", "40", "--seed", "1"][..],
+            &[
+                "-p",
+                "60",
+                "--seed",
+                "2",
+                "--relax",
+                "2",
+                "--point-radius",
+                "2",
+            ],
+            &[
+                "-p",
+                "30",
+                "--seed",
+                "3",
+                "--palette",
+                "#000000,#ffffff,#ff0000",
+            ],
+        ] {
+            let full = render(&img, options, None);
+            let count = 3;
+            let shards: Vec<_> = (1..=count)
+                .map(|index| render(&img, options, Some(Shard { index, count })))
+                .collect();
+            assert_eq!(stack(&shards, img.width()), full, "{options:?}");
+        }
+    }
+
+    #[test]
+    fn bands_split_the_rows_evenly() {
+        let count = 3;
+        let rows: Vec<_> = (1..=count)
+            .map(|index| Shard { index, count }.rows(71).unwrap())
+            .collect();
+        assert_eq!(rows, [(0, 23), (23, 24), (47, 24)]);
+        assert!(Shard { index: 1, count: 5 }.rows(4).is_err());
+    }
+
+    #[test]
+    fn shards_must_fit_their_bands() {
+        let paths: Vec<PathBuf> = ["a.png", "b.png", "c.png"].map(PathBuf::from).into();
+        assert_eq!(
+            check_shards(&paths, &[(96, 23), (96, 24), (96, 24)]),
+            Ok((96, 71))
+        );
+        // Out of order
+        assert!(check_shards(&paths, &[(96, 24), (96, 23), (96, 24)]).is_err());
+        // Mismatched widths
+        assert!(check_shards(&paths, &[(96, 23), (90, 24), (96, 24)]).is_err());
+    }
+}
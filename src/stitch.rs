@@ -0,0 +1,205 @@
+//! Cross-stitch charts with colors matched to DMC embroidery floss.
+
+use crate::color::{blend, luminance};
+use crate::font;
+
+/// A commonly stocked subset of DMC floss colors: number, name and RGB.
+const DMC: &[(&str, &str, [u8; 3])] = &[
+    ("310", "Black", [0, 0, 0]),
+    ("B5200", "Snow White", [255, 255, 255]),
+    ("3865", "Winter White", [249, 247, 241]),
+    ("762", "Very Lt Pearl Gray", [236, 236, 236]),
+    ("415", "Pearl Gray", [211, 211, 214]),
+    ("318", "Lt Steel Gray", [171, 171, 171]),
+    ("414", "Dk Steel Gray", [140, 140, 140]),
+    ("413", "Dk Pewter Gray", [86, 86, 86]),
+    ("3371", "Black Brown", [30, 17, 8]),
+    ("898", "Very Dk Coffee Brown", [73, 42, 19]),
+    ("801", "Dk Coffee Brown", [101, 57, 25]),
+    ("838", "Very Dk Beige Brown", [89, 73, 55]),
+    ("433", "Med Brown", [122, 69, 31]),
+    ("434", "Lt Brown", [152, 94, 51]),
+    ("435", "Very Lt Brown", [184, 119, 72]),
+    ("436", "Tan", [203, 144, 81]),
+    ("437", "Lt Tan", [228, 187, 142]),
+    ("738", "Very Lt Tan", [236, 204, 158]),
+    ("739", "Ultra Very Lt Tan", [248, 228, 200]),
+    ("3064", "Desert Sand", [196, 142, 112]),
+    ("950", "Lt Desert Sand", [238, 211, 196]),
+    ("948", "Very Lt Peach", [254, 231, 218]),
+    ("754", "Lt Peach", [247, 203, 191]),
+    ("353", "Peach", [254, 215, 204]),
+    ("352", "Lt Coral", [253, 156, 151]),
+    ("350", "Med Coral", [224, 72, 72]),
+    ("666", "Bright Red", [227, 29, 66]),
+    ("321", "Red", [199, 43, 59]),
+    ("304", "Med Red", [183, 31, 51]),
+    ("498", "Dk Red", [167, 19, 43]),
+    ("815", "Med Garnet", [135, 7, 31]),
+    ("224", "Very Lt Shell Pink", [235, 183, 175]),
+    ("3326", "Lt Rose", [251, 173, 180]),
+    ("603", "Cranberry", [255, 164, 190]),
+    ("602", "Med Cranberry", [226, 72, 116]),
+    ("3687", "Mauve", [201, 107, 112]),
+    ("554", "Lt Violet", [219, 179, 203]),
+    ("552", "Med Violet", [128, 58, 107]),
+    ("550", "Very Dk Violet", [92, 24, 78]),
+    ("210", "Med Lavender", [195, 159, 195]),
+    ("208", "Very Dk Lavender", [131, 91, 139]),
+    ("800", "Pale Delft Blue", [192, 204, 222]),
+    ("799", "Med Delft Blue", [116, 142, 182]),
+    ("798", "Dk Delft Blue", [70, 106, 142]),
+    ("797", "Royal Blue", [19, 71, 125]),
+    ("796", "Dk Royal Blue", [17, 65, 109]),
+    ("820", "Very Dk Royal Blue", [14, 54, 92]),
+    ("996", "Med Electric Blue", [48, 194, 236]),
+    ("3843", "Electric Blue", [20, 170, 208]),
+    ("995", "Dk Electric Blue", [38, 150, 182]),
+    ("3348", "Lt Yellow Green", [204, 217, 177]),
+    ("907", "Lt Parrot Green", [199, 230, 102]),
+    ("704", "Bright Chartreuse", [158, 207, 52]),
+    ("3347", "Med Yellow Green", [113, 147, 92]),
+    ("702", "Kelly Green", [71, 167, 47]),
+    ("700", "Bright Green", [7, 115, 27]),
+    ("699", "Green", [5, 101, 23]),
+    ("3346", "Hunter Green", [64, 106, 58]),
+    ("3345", "Dk Hunter Green", [27, 89, 21]),
+    ("911", "Med Emerald Green", [24, 144, 101]),
+    ("909", "Very Dk Emerald Green", [21, 111, 73]),
+    ("744", "Pale Yellow", [255, 231, 147]),
+    ("743", "Med Yellow", [254, 211, 118]),
+    ("307", "Lemon", [253, 237, 84]),
+    ("973", "Bright Canary", [255, 227, 0]),
+    ("742", "Lt Tangerine", [255, 191, 87]),
+    ("741", "Med Tangerine", [255, 163, 43]),
+    ("740", "Tangerine", [255, 139, 0]),
+];
+
+/// Chart symbols, in the order threads are assigned them.
+const SYMBOLS: &str = "+X/O#*=%&@\\<>^?!:ABCDEFGHIJKLMNPQRSTUVWYZ123456789";
+
+/// Pixels per stitch square on the chart.
+const STITCH_SIZE: u32 = 12;
+const LEGEND_ROW: u32 = 16;
+const MARGIN: u32 = 8;
+
+/// Index into [`DMC`] of the floss closest to `color`.
+fn nearest_floss(color: [u8; 3]) -> usize {
+    let dist = |rgb: [u8; 3]| -> u32 {
+        (0..3)
+            .map(|c| u32::from(rgb[c].abs_diff(color[c])).pow(2))
+            .sum()
+    };
+    (0..DMC.len()).min_by_key(|&i| dist(DMC[i].2)).unwrap_or(0)
+}
+
+/// Draws a chart `stitches` squares wide where each square takes the color of
+/// the cell under its center, matched to DMC floss and marked with a symbol,
+/// followed by a legend with stitch counts.
+#[must_use]
+pub fn chart(
+    labels: &[u32],
+    colors: &[[u8; 3]],
+    width: u32,
+    height: u32,
+    stitches: u32,
+) -> image::RgbImage {
+    let columns = stitches.clamp(1, width);
+    let rows = u32::try_from(u64::from(columns) * u64::from(height) / u64::from(width))
+        .unwrap_or(u32::MAX)
+        .max(1);
+
+    // Floss of every stitch, and the flosses used in order of first appearance
+    let mut used: Vec<(usize, u32)> = Vec::new();
+    let mut grid = Vec::with_capacity((columns * rows) as usize);
+    for row in 0..rows {
+        for col in 0..columns {
+            let x = (col * 2 + 1) * width / (columns * 2);
+            let y = (row * 2 + 1) * height / (rows * 2);
+            let floss = nearest_floss(colors[labels[(y * width + x) as usize] as usize]);
+            let slot = used
+                .iter()
+                .position(|&(f, _)| f == floss)
+                .unwrap_or_else(|| {
+                    used.push((floss, 0));
+                    used.len() - 1
+                });
+            used[slot].1 += 1;
+            grid.push(slot);
+        }
+    }
+
+    let symbols: Vec<char> = SYMBOLS.chars().collect();
+    let symbol = |slot: usize| symbols.get(slot).copied().unwrap_or(' ');
+    let grid_width = columns * STITCH_SIZE + 1;
+    let grid_height = rows * STITCH_SIZE + 1;
+    let legend_height = u32::try_from(used.len()).unwrap_or(u32::MAX) * LEGEND_ROW;
+    let legend_width =
+        STITCH_SIZE + MARGIN + font::text_width("B5200 Very Dk Emerald Green 000000", 1);
+    let mut img = image::RgbImage::from_pixel(
+        grid_width.max(legend_width) + MARGIN * 2,
+        grid_height + legend_height + MARGIN * 3,
+        image::Rgb([255, 255, 255]),
+    );
+
+    for (idx, &slot) in grid.iter().enumerate() {
+        let idx = u32::try_from(idx).unwrap_or(u32::MAX);
+        let (col, row) = (idx % columns, idx / columns);
+        let (x0, y0) = (MARGIN + col * STITCH_SIZE, MARGIN + row * STITCH_SIZE);
+        draw_square(&mut img, x0, y0, used[slot].0, symbol(slot));
+    }
+
+    // Thin lines between stitches, heavy lines every ten
+    for col in 0..=columns {
+        let color = if col % 10 == 0 { [0; 3] } else { [170; 3] };
+        for y in MARGIN..MARGIN + grid_height {
+            img.put_pixel(MARGIN + col * STITCH_SIZE, y, image::Rgb(color));
+        }
+    }
+    for row in 0..=rows {
+        let color = if row % 10 == 0 { [0; 3] } else { [170; 3] };
+        for x in MARGIN..MARGIN + grid_width {
+            img.put_pixel(x, MARGIN + row * STITCH_SIZE, image::Rgb(color));
+        }
+    }
+
+    let legend_top = MARGIN * 2 + grid_height;
+    for (slot, &(floss, count)) in used.iter().enumerate() {
+        let y0 = legend_top + u32::try_from(slot).unwrap_or(u32::MAX) * LEGEND_ROW;
+        draw_square(&mut img, MARGIN, y0, floss, symbol(slot));
+        let (number, name, _) = DMC[floss];
+        font::draw_text(
+            &mut img,
+            i64::from(MARGIN * 2 + STITCH_SIZE),
+            i64::from(y0 + (STITCH_SIZE - font::GLYPH_HEIGHT) / 2),
+            &format!("{number} {name} {count}"),
+            1,
+            [0; 3],
+        );
+    }
+    img
+}
+
+/// Fills a stitch square with a light tint of the floss and its symbol.
+fn draw_square(img: &mut image::RgbImage, x0: u32, y0: u32, floss: usize, symbol: char) {
+    let thread = DMC[floss].2;
+    let tint = blend(thread, [255; 3], 0.4);
+    for y in y0..y0 + STITCH_SIZE {
+        for x in x0..x0 + STITCH_SIZE {
+            img.put_pixel(x, y, image::Rgb(tint));
+        }
+    }
+    let ink = if luminance(tint) > 110.0 {
+        [0; 3]
+    } else {
+        [255; 3]
+    };
+    font::draw_text(
+        img,
+        i64::from(x0 + (STITCH_SIZE - font::GLYPH_WIDTH) / 2 + 1),
+        i64::from(y0 + (STITCH_SIZE - font::GLYPH_HEIGHT) / 2 + 1),
+        &symbol.to_string(),
+        1,
+        ink,
+    );
+}
@@ -0,0 +1,92 @@
+//! Symmetric point sets for kaleidoscopic diagrams.
+
+use std::f64::consts::TAU;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Mirrored left to right
+    MirrorX,
+    /// Mirrored top to bottom
+    MirrorY,
+    /// N-fold rotation about the image center
+    Rot(u32),
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "mirror-x" => Ok(Self::MirrorX),
+            None if s == "mirror-y" => Ok(Self::MirrorY),
+            Some(("rot", n)) => match n.parse() {
+                Ok(n) if n >= 2 => Ok(Self::Rot(n)),
+                _ => Err(format!(
+                    "invalid rotation order '{n}' (expected an integer of at least 2)"
+                )),
+            },
+            _ => Err(format!(
+                "unknown symmetry '{s}' (expected mirror-x, mirror-y or rot:N)"
+            )),
+        }
+    }
+}
+
+impl Symmetry {
+    /// Rotates pixel `(x, y)` by `angle` radians about the image center.
+    fn rotate(x: f64, y: f64, width: u32, height: u32, angle: f64) -> (f64, f64) {
+        let (cx, cy) = (f64::from(width) / 2.0, f64::from(height) / 2.0);
+        let (dx, dy) = (x + 0.5 - cx, y + 0.5 - cy);
+        let (sin, cos) = angle.sin_cos();
+        (
+            cx + dx * cos - dy * sin - 0.5,
+            cy + dx * sin + dy * cos - 0.5,
+        )
+    }
+
+    /// All images of pixel `(x, y)` that fall inside the image, starting with
+    /// the pixel itself.
+    #[must_use]
+    pub fn orbit(self, x: u32, y: u32, width: u32, height: u32) -> Vec<(u32, u32)> {
+        match self {
+            Self::MirrorX => vec![(x, y), (width - 1 - x, y)],
+            Self::MirrorY => vec![(x, y), (x, height - 1 - y)],
+            Self::Rot(n) => (0..n)
+                .filter_map(|k| {
+                    let angle = TAU * f64::from(k) / f64::from(n);
+                    let (rx, ry) = Self::rotate(f64::from(x), f64::from(y), width, height, angle);
+                    let (rx, ry) = (rx.round(), ry.round());
+                    let inside = (0.0..f64::from(width)).contains(&rx)
+                        && (0.0..f64::from(height)).contains(&ry);
+                    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                    inside.then_some((rx as u32, ry as u32))
+                })
+                .collect(),
+        }
+    }
+
+    /// Copies the fundamental region of `img` onto the rest of the image, so
+    /// that colors are as symmetric as the point set.
+    #[must_use]
+    pub fn fold(self, img: &image::RgbImage) -> image::RgbImage {
+        let (width, height) = img.dimensions();
+        image::RgbImage::from_fn(width, height, |x, y| match self {
+            Self::MirrorX => *img.get_pixel(x.min(width - 1 - x), y),
+            Self::MirrorY => *img.get_pixel(x, y.min(height - 1 - y)),
+            Self::Rot(n) => {
+                let (cx, cy) = (f64::from(width) / 2.0, f64::from(height) / 2.0);
+                let angle = (f64::from(y) + 0.5 - cy).atan2(f64::from(x) + 0.5 - cx);
+                let sector = TAU / f64::from(n);
+                let back = -(angle.rem_euclid(TAU) / sector).floor() * sector;
+                let (sx, sy) = Self::rotate(f64::from(x), f64::from(y), width, height, back);
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let (sx, sy) = (
+                    (sx.round().max(0.0) as u32).min(width - 1),
+                    (sy.round().max(0.0) as u32).min(height - 1),
+                );
+                *img.get_pixel(sx, sy)
+            }
+        })
+    }
+}
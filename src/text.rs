@@ -0,0 +1,67 @@
+//! Text renderers for showing diagrams in a terminal.
+
+use std::fmt::Write;
+
+use crate::color::luminance;
+
+/// Characters in order of increasing ink coverage.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Nearest-neighbour sample of `img` at the center of cell `(col, row)` of a
+/// `columns` × `rows` grid.
+fn sample(img: &image::RgbImage, columns: u32, rows: u32, col: u32, row: u32) -> [u8; 3] {
+    let x = (u64::from(col) * 2 + 1) * u64::from(img.width()) / (u64::from(columns) * 2);
+    let y = (u64::from(row) * 2 + 1) * u64::from(img.height()) / (u64::from(rows) * 2);
+    let x = u32::try_from(x).unwrap_or(u32::MAX).min(img.width() - 1);
+    let y = u32::try_from(y).unwrap_or(u32::MAX).min(img.height() - 1);
+    img.get_pixel(x, y).0
+}
+
+/// Number of text rows for `columns` columns, given terminal cells about
+/// twice as tall as they are wide and `per_cell` image rows per text row.
+fn rows_for(img: &image::RgbImage, columns: u32, per_cell: u32) -> u32 {
+    let rows = u64::from(columns) * u64::from(img.height()) * u64::from(per_cell)
+        / (u64::from(img.width()) * 2);
+    u32::try_from(rows).unwrap_or(u32::MAX).max(1)
+}
+
+/// Renders `img` with 24-bit ANSI colors, two pixels per character using the
+/// upper half block.
+#[must_use]
+pub fn ansi(img: &image::RgbImage, columns: u32) -> String {
+    let rows = rows_for(img, columns, 2);
+    let mut out = String::new();
+    for row in 0..rows.div_ceil(2) {
+        for col in 0..columns {
+            let [tr, tg, tb] = sample(img, columns, rows, col, row * 2);
+            let [br, bg, bb] = sample(img, columns, rows, col, (row * 2 + 1).min(rows - 1));
+            let _ = write!(
+                out,
+                "\x1b[38;2;{tr};{tg};{tb}m\x1b[48;2;{br};{bg};{bb}m\u{2580}"
+            );
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+/// Renders `img` as plain ASCII, picking denser characters for darker pixels.
+#[must_use]
+pub fn ascii(img: &image::RgbImage, columns: u32) -> String {
+    let rows = rows_for(img, columns, 1);
+    let mut out = String::new();
+    for row in 0..rows {
+        for col in 0..columns {
+            let darkness = 1.0 - luminance(sample(img, columns, rows, col, row)) / 255.0;
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let idx = ((darkness * ASCII_RAMP.len() as f64) as usize).min(ASCII_RAMP.len() - 1);
+            out.push(char::from(ASCII_RAMP[idx]));
+        }
+        out.push('\n');
+    }
+    out
+}
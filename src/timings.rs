@@ -0,0 +1,44 @@
+//! Per-phase timing of a render, printed with `--timings`.
+
+use std::time::{Duration, Instant};
+
+/// Wall-clock time spent in each phase of a render, in the order they ran.
+#[derive(Debug, Clone, Default)]
+pub struct Timings(Vec<(&'static str, Duration)>);
+
+impl Timings {
+    /// Runs `f`, adding its duration to `phase`.
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add(phase, start.elapsed());
+        result
+    }
+
+    /// Adds `duration` to `phase`.
+    pub fn add(&mut self, phase: &'static str, duration: Duration) {
+        match self.0.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.0.push((phase, duration)),
+        }
+    }
+
+    pub fn extend(&mut self, other: &Timings) {
+        for &(phase, duration) in &other.0 {
+            self.add(phase, duration);
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Timings:");
+        for (phase, duration) in &self.0 {
+            println!("  {phase:<16} {:>10.3} ms", duration.as_secs_f64() * 1000.0);
+        }
+        let total: Duration = self.0.iter().map(|(_, duration)| *duration).sum();
+        println!(
+            "  {:<16} {:>10.3} ms",
+            "total",
+            total.as_secs_f64() * 1000.0
+        );
+    }
+}
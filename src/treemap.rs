@@ -0,0 +1,309 @@
+//! Weighted Voronoi treemaps: cells whose areas are proportional to the
+//! values of data items, grown as a power diagram.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use rand::prelude::*;
+
+use crate::generate::parse_size;
+use crate::{color, colorize, exit_on_err, export, font, labels, output, rng};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct TreemapArgs {
+    /// CSV of "label,value" rows, or a JSON array of {"label", "value"} objects
+    input: PathBuf,
+
+    /// Output file path; an .svg extension writes vector cells and labels
+    output: PathBuf,
+
+    /// Size of the treemap, e.g. "800x600"
+    #[arg(long, default_value = "800x600", value_parser = parse_size)]
+    size: (u32, u32),
+
+    /// Refuse sizes with more pixels than this
+    #[arg(long, default_value_t = crate::DEFAULT_MAX_PIXELS, value_parser = clap::value_parser!(u64).range(1..))]
+    max_pixels: u64,
+
+    /// Rounds of moving sites and adjusting weights towards the target areas
+    #[arg(long, default_value_t = 60)]
+    iterations: u32,
+
+    /// Seed for the initial site positions and the colors
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Random number generator algorithm
+    #[arg(long, value_enum, default_value_t = rng::Algorithm::Stdrng)]
+    rng: rng::Algorithm,
+
+    /// Overwrite the output if it exists
+    #[arg(short, long)]
+    force: bool,
+}
+
+/// A labelled, positively valued data item.
+#[derive(Debug, Clone)]
+struct Item {
+    label: String,
+    value: f64,
+}
+
+/// Items from CSV rows or a JSON array. A first CSV row without a number
+/// is taken as the header; any other row or object without a label and a
+/// value is an error. Items without a positive value are left out, with a
+/// warning.
+fn parse_items(data: &str) -> Result<Vec<Item>, String> {
+    let items: Vec<Item> = if data.trim_start().starts_with('[') {
+        export::json_objects(data)?
+            .iter()
+            .enumerate()
+            .map(|(i, object)| {
+                let label = object
+                    .get("label")
+                    .or_else(|| object.get("name"))
+                    .ok_or_else(|| format!("item {} has no label or name", i + 1))?;
+                let value = object
+                    .get("value")
+                    .ok_or_else(|| format!("item {} has no value", i + 1))?;
+                let value = value
+                    .parse()
+                    .map_err(|err| format!("item {}: invalid value \"{value}\": {err}", i + 1))?;
+                Ok(Item {
+                    label: label.to_string(),
+                    value,
+                })
+            })
+            .collect::<Result<_, String>>()?
+    } else {
+        data.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .enumerate()
+            .filter_map(|(row, (i, line))| {
+                let item = line
+                    .rsplit_once(',')
+                    .and_then(|(label, value)| Some((label, value.trim().parse().ok()?)))
+                    .map(|(label, value)| Item {
+                        label: label.trim().trim_matches('"').to_string(),
+                        value,
+                    });
+                match item {
+                    Some(item) => Some(Ok(item)),
+                    None if row == 0 => None,
+                    None => Some(Err(format!(
+                        "line {} is not \"label,value\": {line}",
+                        i + 1
+                    ))),
+                }
+            })
+            .collect::<Result<_, String>>()?
+    };
+    let count = items.len();
+    let items: Vec<Item> = items.into_iter().filter(|item| item.value > 0.0).collect();
+    if items.len() < count {
+        eprintln!(
+            "Warning: {} of {count} items have no positive value and were left out",
+            count - items.len()
+        );
+    }
+    if items.is_empty() {
+        return Err("no items with a positive value".to_string());
+    }
+    Ok(items)
+}
+
+/// Power diagram on the pixel grid: each pixel goes to the site minimizing
+/// its squared distance minus the site's weight.
+pub fn power_labels(width: u32, height: u32, sites: &[(f64, f64)], weights: &[f64]) -> Vec<u32> {
+    let mut labels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let (px, py) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+            let power = |&((sx, sy), weight): &(&(f64, f64), &f64)| {
+                (px - sx).powi(2) + (py - sy).powi(2) - weight
+            };
+            let nearest = (0..)
+                .zip(sites.iter().zip(weights))
+                .min_by(|(_, a), (_, b)| power(a).total_cmp(&power(b)));
+            labels.push(nearest.map_or(0, |(label, _)| label));
+        }
+    }
+    labels
+}
+
+/// Labels of the power diagram after `iterations` rounds of moving each site
+/// to its cell's centroid and growing or shrinking its weight by the area it
+/// lacks.
+fn layout(items: &[Item], args: &TreemapArgs, rng: &mut impl Rng) -> Vec<u32> {
+    let (width, height) = args.size;
+    let total: f64 = items.iter().map(|item| item.value).sum();
+    let area = f64::from(width) * f64::from(height);
+    let targets: Vec<f64> = items.iter().map(|item| item.value / total * area).collect();
+    let mut sites: Vec<(f64, f64)> = items
+        .iter()
+        .map(|_| {
+            (
+                rng.random_range(0.0..f64::from(width)),
+                rng.random_range(0.0..f64::from(height)),
+            )
+        })
+        .collect();
+    let mut weights = vec![0.0; items.len()];
+    let mut labels = power_labels(width, height, &sites, &weights);
+    for i in 0..args.iterations {
+        eprint!("\rFitting cell areas... {i} / {}", args.iterations);
+        let cells = centroids(&labels, width, items.len());
+        for (site, &(x, y, count)) in sites.iter_mut().zip(&cells) {
+            if count > 0.0 {
+                *site = (x, y);
+            }
+        }
+        let counts: Vec<f64> = cells.iter().map(|&(_, _, count)| count).collect();
+        adjust_weights(&sites, &mut weights, &counts, &targets);
+        labels = power_labels(width, height, &sites, &weights);
+    }
+    eprintln!("\rFitting cell areas... {0} / {0}", args.iterations);
+    labels
+}
+
+/// Grows or shrinks each power-diagram weight by the area its cell lacks
+/// against `targets`, given the cells' current pixel `counts`.
+pub fn adjust_weights(sites: &[(f64, f64)], weights: &mut [f64], counts: &[f64], targets: &[f64]) {
+    for ((weight, count), target) in weights.iter_mut().zip(counts).zip(targets) {
+        // A weight adds to a cell roughly as a circle's squared radius does
+        *weight += (target - count) / std::f64::consts::PI;
+    }
+    // A weight above the squared distance to a neighbour can swallow it
+    let caps: Vec<f64> = sites
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y))| {
+            sites
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, &(ox, oy))| (x - ox).powi(2) + (y - oy).powi(2))
+                .fold(f64::INFINITY, f64::min)
+        })
+        .collect();
+    let floor = weights.iter().copied().fold(f64::INFINITY, f64::min);
+    for (weight, cap) in weights.iter_mut().zip(caps) {
+        *weight = (*weight - floor).min(cap);
+    }
+}
+
+/// The centroid of each cell and its pixel count.
+fn centroids(labels: &[u32], width: u32, cells: usize) -> Vec<(f64, f64, f64)> {
+    let mut sums = vec![(0.0, 0.0, 0.0); cells];
+    for (i, &label) in (0..).zip(labels) {
+        let (count, sx, sy) = &mut sums[label as usize];
+        *count += 1.0;
+        *sx += f64::from(i % width) + 0.5;
+        *sy += f64::from(i / width) + 0.5;
+    }
+    sums.into_iter()
+        .map(|(count, sx, sy)| (sx / count, sy / count, count))
+        .collect()
+}
+
+pub fn run(args: &TreemapArgs) {
+    exit_on_err(
+        output::check_clobber(&args.output, args.force),
+        "write output",
+    );
+    let (width, height) = args.size;
+    exit_on_err(
+        crate::check_pixels(width, height, args.max_pixels),
+        "check size",
+    );
+    let data = exit_on_err(std::fs::read_to_string(&args.input), "read data");
+    let items = exit_on_err(parse_items(&data), "parse data");
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    println!("Seed: {seed}");
+    println!("Items: {}", items.len());
+
+    let labels = layout(&items, args, &mut args.rng.seeded(seed));
+    let colors = colorize::safe_colors(
+        &labels::adjacency(&labels, width, items.len()),
+        &mut args.rng.seeded(seed),
+    );
+    let centers = centroids(&labels, width, items.len());
+    let total: f64 = items.iter().map(|item| item.value).sum();
+    let area = f64::from(width) * f64::from(height);
+    let worst = items
+        .iter()
+        .zip(&centers)
+        .map(|(item, &(_, _, count))| (count / (item.value / total * area) - 1.0).abs())
+        .fold(0.0, f64::max);
+    println!("Largest area error: {:.1}%", worst * 100.0);
+
+    let is_svg = args
+        .output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    let result = output::save(&args.output, args.force, |path| {
+        if is_svg {
+            let mut svg = export::svg(&labels, &colors, width, height);
+            svg.truncate(svg.len() - "</svg>\n".len());
+            svg.push_str(
+                "<g font-family=\"sans-serif\" font-size=\"12\" text-anchor=\"middle\">\n",
+            );
+            for ((item, &(x, y, count)), &fill) in items.iter().zip(&centers).zip(&colors) {
+                // An empty cell has no centroid to put its label at
+                if count == 0.0 {
+                    continue;
+                }
+                let ink = if color::luminance(fill) > 128.0 {
+                    "#000000"
+                } else {
+                    "#ffffff"
+                };
+                let _ = writeln!(
+                    svg,
+                    "<text x=\"{x:.1}\" y=\"{:.1}\" fill=\"{ink}\">{}</text>",
+                    y + 4.0,
+                    escape(&item.label)
+                );
+            }
+            svg.push_str("</g>\n</svg>\n");
+            std::fs::write(path, svg).map_err(image::ImageError::IoError)
+        } else {
+            let mut img = image::RgbImage::from_fn(width, height, |x, y| {
+                image::Rgb(colors[labels[(y * width + x) as usize] as usize])
+            });
+            for ((item, &(x, y, count)), &fill) in items.iter().zip(&centers).zip(&colors) {
+                // Skip labels that would spill far outside their cell
+                let text_width = font::text_width(&item.label, 1);
+                if f64::from(text_width).powi(2) > count {
+                    continue;
+                }
+                let ink = if color::luminance(fill) > 128.0 {
+                    [0; 3]
+                } else {
+                    [255; 3]
+                };
+                #[allow(clippy::cast_possible_truncation)]
+                font::draw_text(
+                    &mut img,
+                    x as i64 - i64::from(text_width / 2),
+                    y as i64 - i64::from(font::GLYPH_HEIGHT / 2),
+                    &item.label,
+                    1,
+                    ink,
+                );
+            }
+            img.save(path)
+        }
+    });
+    exit_on_err(result, "save treemap");
+    eprintln!("Saved treemap to {}", args.output.display());
+}
+
+/// `text` with the characters XML reserves replaced by entities.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,185 @@
+//! Tileable cellular (Worley) noise textures.
+
+use std::path::PathBuf;
+
+use rand::prelude::*;
+
+use crate::generate::parse_size;
+use crate::{exit_on_err, output, rng};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct WorleyArgs {
+    /// Output image file path
+    output: PathBuf,
+
+    /// Size of the texture, e.g. "512x512"
+    #[arg(long, default_value = "512x512", value_parser = parse_size)]
+    size: (u32, u32),
+
+    /// Refuse sizes with more pixels than this
+    #[arg(long, default_value_t = crate::DEFAULT_MAX_PIXELS, value_parser = clap::value_parser!(u64).range(1..))]
+    max_pixels: u64,
+
+    /// Feature points across the width in the first octave; the height gets
+    /// as many as keep cells square, so the texture tiles seamlessly
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    frequency: u32,
+
+    /// Number of octaves layered on top of each other, each at double the
+    /// frequency of the last
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=8))]
+    octaves: u32,
+
+    /// Amplitude multiplier from one octave to the next
+    #[arg(long, default_value_t = 0.5)]
+    gain: f64,
+
+    /// Which distances make up the texture
+    #[arg(long, value_enum, default_value_t = Feature::F1)]
+    feature: Feature,
+
+    /// Seed for the feature points
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Random number generator algorithm
+    #[arg(long, value_enum, default_value_t = rng::Algorithm::Stdrng)]
+    rng: rng::Algorithm,
+
+    /// Overwrite the output if it exists
+    #[arg(short, long)]
+    force: bool,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Distance to the nearest feature point: bright cell borders
+    F1,
+    /// Distance to the second-nearest feature point
+    F2,
+    /// Second-nearest minus nearest distance: dark cracks along cell edges
+    #[value(name = "f2-f1")]
+    F2MinusF1,
+}
+
+/// One octave: a grid of cells wrapping around at its edges, each holding a
+/// feature point at a random offset within it.
+struct Octave {
+    columns: u32,
+    rows: u32,
+    /// Feature point of each cell in cell units, row by row
+    points: Vec<(f64, f64)>,
+}
+
+impl Octave {
+    fn new(columns: u32, rows: u32, rng: &mut impl Rng) -> Self {
+        let points = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                (
+                    f64::from(x) + rng.random::<f64>(),
+                    f64::from(y) + rng.random::<f64>(),
+                )
+            })
+            .collect();
+        Self {
+            columns,
+            rows,
+            points,
+        }
+    }
+
+    /// Distances `(f1, f2)`, in cell units, from `(u, v)` in `[0, 1)` texture
+    /// coordinates to the nearest and second-nearest feature points.
+    fn distances(&self, u: f64, v: f64) -> (f64, f64) {
+        let (x, y) = (u * f64::from(self.columns), v * f64::from(self.rows));
+        #[allow(clippy::cast_possible_truncation)]
+        let (cx, cy) = (x.floor() as i64, y.floor() as i64);
+        let (mut f1, mut f2) = (f64::INFINITY, f64::INFINITY);
+        // With one point per cell, the nearest two always lie within two cells
+        for dy in -2..=2 {
+            for dx in -2..=2 {
+                let (nx, ny) = (cx + dx, cy + dy);
+                let (columns, rows) = (i64::from(self.columns), i64::from(self.rows));
+                #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+                let index = (ny.rem_euclid(rows) * columns + nx.rem_euclid(columns)) as usize;
+                let (px, py) = self.points[index];
+                // Shift the wrapped cell's point next to this one
+                #[allow(clippy::cast_precision_loss)]
+                let (px, py) = (
+                    px + (nx - nx.rem_euclid(columns)) as f64,
+                    py + (ny - ny.rem_euclid(rows)) as f64,
+                );
+                let dist = (px - x).hypot(py - y);
+                if dist < f1 {
+                    (f1, f2) = (dist, f1);
+                } else if dist < f2 {
+                    f2 = dist;
+                }
+            }
+        }
+        (f1, f2)
+    }
+}
+
+pub fn run(args: &WorleyArgs) {
+    exit_on_err(
+        output::check_clobber(&args.output, args.force),
+        "write output",
+    );
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    println!("Seed: {seed}");
+    let mut rng = args.rng.seeded(seed);
+
+    let (width, height) = args.size;
+    exit_on_err(
+        crate::check_pixels(width, height, args.max_pixels),
+        "check size",
+    );
+    let octaves: Vec<Octave> = (0..args.octaves)
+        .map(|octave| {
+            let columns = args.frequency << octave;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let rows =
+                ((f64::from(columns) * f64::from(height) / f64::from(width)).round() as u32).max(1);
+            Octave::new(columns, rows, &mut rng)
+        })
+        .collect();
+
+    let mut values = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let u = (f64::from(x) + 0.5) / f64::from(width);
+            let v = (f64::from(y) + 0.5) / f64::from(height);
+            let mut amplitude = 1.0;
+            let mut value = 0.0;
+            for octave in &octaves {
+                let (f1, f2) = octave.distances(u, v);
+                value += amplitude
+                    * match args.feature {
+                        Feature::F1 => f1,
+                        Feature::F2 => f2,
+                        Feature::F2MinusF1 => f2 - f1,
+                    };
+                amplitude *= args.gain;
+            }
+            values.push(value);
+        }
+    }
+
+    let max = values
+        .iter()
+        .copied()
+        .fold(0.0, f64::max)
+        .max(f64::MIN_POSITIVE);
+    let texture = image::ImageBuffer::<image::Luma<u16>, _>::from_fn(width, height, |x, y| {
+        let value = values[(y * width + x) as usize] / max;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        image::Luma([(value * f64::from(u16::MAX)).round() as u16])
+    });
+    exit_on_err(
+        output::save(&args.output, args.force, |path| texture.save(path)),
+        "save texture",
+    );
+    eprintln!("Saved worley noise to {}", args.output.display());
+}